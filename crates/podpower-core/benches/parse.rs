@@ -0,0 +1,106 @@
+//! `cargo bench -p podpower-core [FILTER]`: decoding the golden test
+//! fixtures, alone and through every enabled decoder the way a scan does.
+
+use podpower_core::{
+    APPLE_MANUFACTURER_ID, AdvertisementData, EncryptionKey, decode_airpods_data, default_decoders,
+    refine_with_key,
+};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const BATCH: u64 = 1_000;
+const MEASURE: Duration = Duration::from_secs(1);
+
+/// Time `f` for about [`MEASURE`] after a warm-up batch and print the mean
+/// per call, unless a command-line filter leaves it out
+fn bench(name: &str, mut f: impl FnMut()) {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    if filter.is_some_and(|filter| !name.contains(&filter)) {
+        return;
+    }
+    for _ in 0..BATCH {
+        f();
+    }
+    let start = Instant::now();
+    let mut iterations = 0;
+    while start.elapsed() < MEASURE {
+        for _ in 0..BATCH {
+            f();
+        }
+        iterations += BATCH;
+    }
+    let nanos = start.elapsed().as_nanos() as f64 / iterations as f64;
+    println!("{:<36} {:>10.1} ns/iter", name, nanos);
+}
+
+/// The payloads in `tests/fixtures`
+fn fixtures() -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures: Vec<(String, Vec<u8>)> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hex"))
+        .map(|path| {
+            let text = std::fs::read_to_string(&path).unwrap();
+            let digits: String = text
+                .lines()
+                .filter(|line| !line.trim_start().starts_with('#'))
+                .flat_map(|line| line.chars().filter(char::is_ascii_hexdigit))
+                .collect();
+            let bytes = (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+                .collect();
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            (name, bytes)
+        })
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+fn main() {
+    let fixtures = fixtures();
+
+    for (name, data) in &fixtures {
+        bench(&format!("decode/{}", name), || {
+            let _ = black_box(decode_airpods_data(black_box(data)));
+        });
+    }
+
+    bench("decode/all_fixtures", || {
+        for (_, data) in &fixtures {
+            let _ = black_box(decode_airpods_data(black_box(data)));
+        }
+    });
+
+    // What a scan does with every advertisement it sees
+    let decoders = default_decoders();
+    let advertisements: Vec<HashMap<u16, Vec<u8>>> = fixtures
+        .iter()
+        .map(|(_, data)| HashMap::from([(APPLE_MANUFACTURER_ID, data.clone())]))
+        .collect();
+    let no_service_data = HashMap::new();
+    bench("decoders/all_fixtures", || {
+        for manufacturer_data in &advertisements {
+            let advertisement = AdvertisementData {
+                manufacturer_data,
+                service_data: &no_service_data,
+                rssi: Some(-50),
+            };
+            for decoder in &decoders {
+                let _ = black_box(decoder.decode(black_box(&advertisement)));
+            }
+        }
+    });
+
+    let key = EncryptionKey::new([0x42; 16]);
+    let (_, data) = &fixtures[0];
+    let status = decode_airpods_data(data).unwrap();
+    bench("refine_with_key", || {
+        let mut status = status.clone();
+        black_box(refine_with_key(&mut status, black_box(data), &key));
+    });
+}
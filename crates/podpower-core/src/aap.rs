@@ -0,0 +1,135 @@
+//! Packets of Apple's accessory protocol (AAP), which AirPods speak over an
+//! L2CAP channel to the host they are connected to.
+//!
+//! Unlike the advertisement, this carries state only the connected host
+//! sees, like the noise-control mode. The layout comes from reverse
+//! engineering (see the LibrePods project); only the packets podpower uses
+//! are described here.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// L2CAP PSM the AirPods accept AAP connections on
+pub const PSM: u16 = 0x1001;
+
+/// First packet after connecting, without which the AirPods stay silent
+pub const HANDSHAKE: [u8; 16] = [
+    0x00, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Ask for notifications of every kind, which start with the current state
+pub const REQUEST_NOTIFICATIONS: [u8; 10] =
+    [0x04, 0x00, 0x04, 0x00, 0x0f, 0x00, 0xff, 0xff, 0xff, 0xff];
+
+/// Every packet after the handshake starts with this
+const HEADER: [u8; 4] = [0x04, 0x00, 0x04, 0x00];
+/// Opcode of control commands and their notifications, followed by a
+/// 2-byte little-endian opcode, the control's id and its value
+const CONTROL: u16 = 0x0009;
+const LISTENING_MODE: u8 = 0x0d;
+
+/// Noise control of AirPods Pro, AirPods Max and recent Beats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ListeningMode {
+    Off,
+    /// Active noise cancellation
+    Anc,
+    Transparency,
+    /// Blends the other two by the surrounding noise (AirPods Pro 2)
+    Adaptive,
+}
+
+impl ListeningMode {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(ListeningMode::Off),
+            0x02 => Some(ListeningMode::Anc),
+            0x03 => Some(ListeningMode::Transparency),
+            0x04 => Some(ListeningMode::Adaptive),
+            _ => None,
+        }
+    }
+
+    fn byte(self) -> u8 {
+        match self {
+            ListeningMode::Off => 0x01,
+            ListeningMode::Anc => 0x02,
+            ListeningMode::Transparency => 0x03,
+            ListeningMode::Adaptive => 0x04,
+        }
+    }
+}
+
+impl fmt::Display for ListeningMode {
+    /// The same snake_case name as in JSON
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ListeningMode::Off => "off",
+            ListeningMode::Anc => "anc",
+            ListeningMode::Transparency => "transparency",
+            ListeningMode::Adaptive => "adaptive",
+        })
+    }
+}
+
+impl FromStr for ListeningMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(ListeningMode::Off),
+            "anc" => Ok(ListeningMode::Anc),
+            "transparency" => Ok(ListeningMode::Transparency),
+            "adaptive" => Ok(ListeningMode::Adaptive),
+            _ => Err(format!("unknown listening mode: {}", s)),
+        }
+    }
+}
+
+/// The listening mode `packet` reports, if it is a listening mode
+/// notification
+pub fn parse_listening_mode(packet: &[u8]) -> Option<ListeningMode> {
+    match control(packet)? {
+        (LISTENING_MODE, value) => ListeningMode::from_byte(value),
+        _ => None,
+    }
+}
+
+/// Command switching the noise control to `mode`, which the AirPods confirm
+/// with a notification of the new mode. Adaptive only exists on AirPods
+/// Pro 2, and others ignore it.
+pub fn listening_mode_packet(mode: ListeningMode) -> [u8; 11] {
+    let [h0, h1, h2, h3] = HEADER;
+    let [o0, o1] = CONTROL.to_le_bytes();
+    [
+        h0,
+        h1,
+        h2,
+        h3,
+        o0,
+        o1,
+        LISTENING_MODE,
+        mode.byte(),
+        0x00,
+        0x00,
+        0x00,
+    ]
+}
+
+/// The id and first value byte of a control notification
+fn control(packet: &[u8]) -> Option<(u8, u8)> {
+    let rest = packet.strip_prefix(&HEADER)?;
+    let (opcode, rest) = rest.split_first_chunk::<2>()?;
+    if u16::from_le_bytes(*opcode) != CONTROL {
+        return None;
+    }
+    match rest {
+        [id, value, ..] => Some((*id, *value)),
+        _ => None,
+    }
+}
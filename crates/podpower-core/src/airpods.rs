@@ -0,0 +1,526 @@
+use crate::decoder::{AdvertisementData, Decoded, VendorDecoder, Warning};
+use crate::encrypted::{EncryptionKey, refine_with_key};
+use crate::status::{
+    APPROXIMATE_PRECISION, AirPodsStatus, Component, ConnectionState, EarDetection, Rounding,
+};
+use std::fmt;
+
+pub const APPLE_MANUFACTURER_ID: u16 = 0x004c; // Apple Inc.
+pub const AIRPODS_DATA_LENGTH: usize = 27;
+
+/// Continuity message type of proximity pairing messages
+pub const PROXIMITY_PAIRING_TYPE: u8 = 0x07;
+
+// Byte positions in the 27-byte manufacturer data
+const BYTE_TYPE: usize = 0;
+const BYTE_LENGTH: usize = 1;
+const BYTE_MODEL_HIGH: usize = 3;
+const BYTE_MODEL_LOW: usize = 4;
+const BYTE_STATUS: usize = 5;
+const BYTE_BATTERY_PODS: usize = 6;
+const BYTE_BATTERY_CASE_AND_CHARGING: usize = 7;
+const BYTE_LID: usize = 8;
+const BYTE_COLOR: usize = 9;
+const BYTE_CONNECTION_STATE: usize = 10;
+
+// Bit masks
+const MASK_FLIP_BIT: u8 = 0x20; // Bit 5
+const MASK_ON_HEAD: u8 = 0x02; // Bit 1, over-ear devices only
+const MASK_IN_EAR_THIS: u8 = 0x02; // Bit 1, the broadcasting pod, in-ear devices only
+const MASK_IN_EAR_OTHER: u8 = 0x08; // Bit 3, the other pod, in-ear devices only
+const MASK_IN_EAR: u8 = MASK_IN_EAR_THIS | MASK_IN_EAR_OTHER;
+const MASK_BOTH_IN_CASE: u8 = 0x04; // Bit 2, in-ear devices only
+const MASK_LID_CLOSED: u8 = 0x08; // Bit 3 of the lid byte
+const MASK_CHARGING_LEFT: u8 = 0x01;
+const MASK_CHARGING_RIGHT: u8 = 0x02;
+const MASK_CHARGING_CASE: u8 = 0x04;
+const BATTERY_DISCONNECTED: u8 = 15;
+
+/// Extract the high nibble (4 bits) from a byte
+#[inline]
+fn high_nibble(byte: u8) -> u8 {
+    (byte >> 4) & 0x0f
+}
+
+/// Extract the low nibble (4 bits) from a byte
+#[inline]
+fn low_nibble(byte: u8) -> u8 {
+    byte & 0x0f
+}
+
+/// Why manufacturer data could not be decoded as an AirPods status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// Not the 27 bytes of a proximity pairing message
+    WrongLength(usize),
+    /// Shorter than the vendor's message
+    Truncated(usize),
+    /// Not a message type that carries battery levels
+    Unrecognized,
+    /// Another kind of Apple Continuity message, e.g. from an AirTag
+    WrongType(u8),
+    /// The message's own length field disagrees with the payload
+    LengthMismatch(u8),
+    /// A single-battery device without a battery reading
+    NoBattery,
+    /// A model id without a name, rejected by [`decode_airpods_data_strict`]
+    UnknownModel(u16),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::WrongLength(len) => {
+                write!(
+                    f,
+                    "wrong length ({} bytes, expected {})",
+                    len, AIRPODS_DATA_LENGTH
+                )
+            }
+            ParseError::Truncated(len) => write!(f, "too short ({} bytes)", len),
+            ParseError::Unrecognized => write!(f, "not a battery message"),
+            ParseError::WrongType(message_type) => match message_type_name(*message_type) {
+                Some(name) => write!(f, "not a proximity pairing message ({})", name),
+                None => write!(
+                    f,
+                    "not a proximity pairing message (type 0x{:02x})",
+                    message_type
+                ),
+            },
+            ParseError::LengthMismatch(length) => write!(
+                f,
+                "length field says {} bytes, expected {}",
+                length,
+                AIRPODS_DATA_LENGTH - 2
+            ),
+            ParseError::NoBattery => write!(f, "no battery level reported"),
+            ParseError::UnknownModel(model) => write!(f, "unknown model id 0x{:04x}", model),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Names of the Continuity messages commonly seen next to AirPods
+fn message_type_name(message_type: u8) -> Option<&'static str> {
+    Some(match message_type {
+        0x05 => "AirDrop",
+        0x09 => "AirPlay target",
+        0x0c => "Handoff",
+        0x0f => "Nearby Action",
+        0x10 => "Nearby Info",
+        0x12 => "Find My",
+        _ => return None,
+    })
+}
+
+/// What the device is doing with its source device, `None` for values that
+/// aren't known
+fn connection_state(byte: u8) -> Option<ConnectionState> {
+    // See: https://github.com/d4rken-org/capod (ConnectionState of DualApplePods)
+    Some(match byte {
+        0x00 => ConnectionState::Disconnected,
+        0x04 => ConnectionState::Idle,
+        0x05 => ConnectionState::Music,
+        // Call, ringing, hanging up
+        0x06 | 0x07 | 0x09 => ConnectionState::Call,
+        _ => return None,
+    })
+}
+
+/// Whether the left pod is in the high nibble of the battery byte
+pub(crate) fn is_flipped(data: &[u8]) -> bool {
+    (data[BYTE_STATUS] & MASK_FLIP_BIT) == 0
+}
+
+/// The 2-byte device model identifier, e.g. `0x0E20` for AirPods Pro
+pub fn model_id(data: &[u8]) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *data.get(BYTE_MODEL_HIGH)?,
+        *data.get(BYTE_MODEL_LOW)?,
+    ]))
+}
+
+/// The housing color code. Apple doesn't document the values, but a pair
+/// always advertises the same one, which helps tell identical models apart.
+pub fn color_id(data: &[u8]) -> Option<u8> {
+    data.get(BYTE_COLOR).copied()
+}
+
+/// Marketing name for a model identifier, `None` when it isn't known
+pub fn model_name(model_id: u16) -> Option<&'static str> {
+    // See: https://github.com/d4rken-org/capod/blob/5860bbffb6b2e59feca450bc234595314e842366/app/src/main/java/eu/darken/capod/pods/core/apple/airpods/AirPodsGen4.kt#L78
+    Some(match model_id {
+        0x0220 => "AirPods 1",
+        0x0F20 => "AirPods 2",
+        0x1320 => "AirPods 3",
+        0x1920 => "AirPods 4",
+        0x0E20 => "AirPods Pro",
+        0x1420 | 0x2420 => "AirPods Pro 2",
+        0x2720 => "AirPods Pro 3",
+        0x0A20 | 0x1F20 => "AirPods Max",
+        0x0B20 => "Powerbeats Pro",
+        0x1720 => "Beats Studio Pro",
+        0x2520 => "Beats Solo Buds",
+        _ => return None,
+    })
+}
+
+/// Models whose case has no battery of its own (it charges the pods from
+/// the cable), so whatever they advertise for it means nothing
+pub const NO_CASE_BATTERY: &[&str] = &["Beats Solo Buds"];
+
+/// Whether a model reports one battery (headphones) rather than two pods and
+/// a case
+fn is_single_battery(model_id: u16) -> bool {
+    match model_id {
+        0x0A20 | 0x1F20 | 0x1720 => true,
+        // Ear-hook earbuds, but with pods and a case like AirPods
+        0x0B20 => false,
+        // Unknown models: AirPods Max were the only 0x?A models so far
+        _ => model_name(model_id).is_none() && (model_id >> 8) & 0x0f == 0x0a,
+    }
+}
+
+/// Parse AirPods manufacturer data from BLE advertisement
+///
+/// Returns `None` for data that isn't an AirPods status; see
+/// [`decode_airpods_data`] for the reason.
+pub fn parse_airpods_data(data: &[u8]) -> Option<AirPodsStatus> {
+    decode_airpods_data(data).ok()
+}
+
+/// Like [`parse_airpods_data`], but says why the data was rejected
+///
+/// # BLE Packet Structure (27 bytes)
+/// Based on reverse engineering from OpenPods project:
+/// - Byte 0: Message type, `0x07` for proximity pairing
+/// - Byte 1: Length of the rest of the message (`0x19`)
+/// - Byte 3-4: Device model identifier
+/// - Byte 5: Status flags: flip bit (determines left/right orientation),
+///   on-head detection for AirPods Max, in-ear and in-case detection for
+///   earbuds
+/// - Byte 6: Left and right pod battery levels (4 bits each)
+/// - Byte 7: Case battery + charging status
+///   - High nibble (bits 4-7): Charging flags
+///   - Low nibble (bits 0-3): Case battery level
+/// - Byte 8: Case lid state (bit 3 set while closed)
+/// - Byte 10: Connection state with the source device (idle, music, call)
+pub fn decode_airpods_data(data: &[u8]) -> Result<AirPodsStatus, ParseError> {
+    if data.len() != AIRPODS_DATA_LENGTH {
+        return Err(ParseError::WrongLength(data.len()));
+    }
+    // Other Apple broadcasts can happen to be 27 bytes long too
+    if data[BYTE_TYPE] != PROXIMITY_PAIRING_TYPE {
+        return Err(ParseError::WrongType(data[BYTE_TYPE]));
+    }
+    if data[BYTE_LENGTH] as usize != AIRPODS_DATA_LENGTH - 2 {
+        return Err(ParseError::LengthMismatch(data[BYTE_LENGTH]));
+    }
+
+    // Check if left/right are flipped
+    let flip = is_flipped(data);
+
+    // Detect model from 2-byte identifier
+    let model_full = ((data[BYTE_MODEL_HIGH] as u16) << 8) | (data[BYTE_MODEL_LOW] as u16);
+    let model = model_name(model_full).unwrap_or("AirPods");
+
+    // Check if this is a single-battery device (AirPods Max)
+    let is_max_device = is_single_battery(model_full);
+
+    let battery_byte = data[BYTE_BATTERY_PODS];
+
+    let case_charge_byte = data[BYTE_BATTERY_CASE_AND_CHARGING];
+    let case_battery_raw = low_nibble(case_charge_byte);
+    let charging_flags = high_nibble(case_charge_byte);
+    let connection_state = connection_state(data[BYTE_CONNECTION_STATE]);
+
+    if is_max_device {
+        // For single-battery devices (AirPods Max), use low nibble of byte 6
+        let single_raw = low_nibble(battery_byte);
+        let battery = battery_level(single_raw).ok_or(ParseError::NoBattery)?;
+        // The charging flag of the low nibble's side is 0x02 whichever way
+        // round the flip bit says it is; the case flag doubles as "cable
+        // connected". Max keep reporting both once full, so only a battery
+        // below 100% is actually charging.
+        let plugged_in = (charging_flags & (MASK_CHARGING_RIGHT | MASK_CHARGING_CASE)) != 0;
+        let charging = plugged_in && battery < 100;
+
+        let components = vec![Component {
+            name: "headphones".into(),
+            battery,
+            charging,
+            precision: APPROXIMATE_PRECISION,
+            raw: Some(single_raw),
+        }];
+
+        Ok(AirPodsStatus::OverEar {
+            model: model.into(),
+            battery,
+            components,
+            on_head: (data[BYTE_STATUS] & MASK_ON_HEAD) != 0,
+            plugged_in,
+            connection_state,
+        })
+    } else {
+        // For dual-pod devices (AirPods, AirPods Pro), extract left and right
+        let (left_raw, right_raw) = if flip {
+            (high_nibble(battery_byte), low_nibble(battery_byte))
+        } else {
+            (low_nibble(battery_byte), high_nibble(battery_byte))
+        };
+
+        let left = battery_level(left_raw);
+        let right = battery_level(right_raw);
+        let case = battery_level(case_battery_raw).filter(|_| !NO_CASE_BATTERY.contains(&model));
+
+        // Parse charging flags (respecting flip bit)
+        let (left_mask, right_mask) = if flip {
+            (MASK_CHARGING_LEFT, MASK_CHARGING_RIGHT)
+        } else {
+            (MASK_CHARGING_RIGHT, MASK_CHARGING_LEFT)
+        };
+        let charging_left = (charging_flags & left_mask) != 0;
+        let charging_right = (charging_flags & right_mask) != 0;
+        let charging_case = (charging_flags & MASK_CHARGING_CASE) != 0;
+
+        // Build components array (only include connected components)
+        let mut components = Vec::new();
+
+        if let Some(left_battery) = left {
+            components.push(Component {
+                name: "left".into(),
+                battery: left_battery,
+                charging: charging_left,
+                precision: APPROXIMATE_PRECISION,
+                raw: Some(left_raw),
+            });
+        }
+
+        if let Some(right_battery) = right {
+            components.push(Component {
+                name: "right".into(),
+                battery: right_battery,
+                charging: charging_right,
+                precision: APPROXIMATE_PRECISION,
+                raw: Some(right_raw),
+            });
+        }
+
+        if let Some(case_battery) = case {
+            components.push(Component {
+                name: "case".into(),
+                battery: case_battery,
+                charging: charging_case,
+                precision: APPROXIMATE_PRECISION,
+                raw: Some(case_battery_raw),
+            });
+        }
+
+        // Pods only charge in the case, so that counts as in the case too
+        let in_case =
+            (data[BYTE_STATUS] & MASK_BOTH_IN_CASE) != 0 || (charging_left && charging_right);
+        let in_ear = (data[BYTE_STATUS] & MASK_IN_EAR) != 0;
+        let lid_closed = (data[BYTE_LID] & MASK_LID_CLOSED) != 0;
+        let stored = in_case && !in_ear && lid_closed;
+        // The broadcasting pod is the one whose battery is in the low
+        // nibble, the right one when flipped. A pod without a reading
+        // isn't connected, whatever its bit says.
+        let (left_in_ear_mask, right_in_ear_mask) = if flip {
+            (MASK_IN_EAR_OTHER, MASK_IN_EAR_THIS)
+        } else {
+            (MASK_IN_EAR_THIS, MASK_IN_EAR_OTHER)
+        };
+        let ear_detection = EarDetection {
+            left: left.is_some() && (data[BYTE_STATUS] & left_in_ear_mask) != 0,
+            right: right.is_some() && (data[BYTE_STATUS] & right_in_ear_mask) != 0,
+        };
+
+        // Calculate top-level battery: minimum of connected earbuds (ignore case)
+        let battery = match (left, right) {
+            (Some(l), Some(r)) => Some(l.min(r)),
+            (Some(l), None) => Some(l),
+            (None, Some(r)) => Some(r),
+            (None, None) => None,
+        };
+
+        Ok(AirPodsStatus::InEar {
+            model: model.into(),
+            battery,
+            components,
+            connection_state,
+            stored: Some(stored),
+            in_ear: Some(ear_detection),
+        })
+    }
+}
+
+/// Like [`decode_airpods_data`], but also rejects models without a name
+/// instead of calling them AirPods
+pub fn decode_airpods_data_strict(data: &[u8]) -> Result<AirPodsStatus, ParseError> {
+    let status = decode_airpods_data(data)?;
+    match model_id(data) {
+        Some(model) if model_name(model).is_none() => Err(ParseError::UnknownModel(model)),
+        _ => Ok(status),
+    }
+}
+
+/// Best effort for proximity pairing messages that [`decode_airpods_data`]
+/// rejects for their length, e.g. from firmware that appends or drops
+/// trailing bytes: the fields are read at their usual positions anyway.
+/// Says what it had to guess, including a model without a name. Fails for
+/// other messages and ones too short to hold the battery levels.
+pub fn decode_airpods_data_best_effort(
+    data: &[u8],
+) -> Result<(AirPodsStatus, Vec<Warning>), ParseError> {
+    match data.first() {
+        Some(&PROXIMITY_PAIRING_TYPE) => {}
+        Some(&message_type) => return Err(ParseError::WrongType(message_type)),
+        None => return Err(ParseError::Truncated(0)),
+    }
+    if data.len() <= BYTE_CONNECTION_STATE {
+        return Err(ParseError::Truncated(data.len()));
+    }
+    let mut warnings = Vec::new();
+    let expected = (AIRPODS_DATA_LENGTH - 2) as u8;
+    if data.len() != AIRPODS_DATA_LENGTH {
+        warnings.push(Warning::Length {
+            actual: data.len(),
+            expected: AIRPODS_DATA_LENGTH,
+        });
+    } else if data[BYTE_LENGTH] != expected {
+        warnings.push(Warning::LengthField {
+            actual: data[BYTE_LENGTH],
+            expected,
+        });
+    }
+    let mut padded = [0u8; AIRPODS_DATA_LENGTH];
+    let len = data.len().min(AIRPODS_DATA_LENGTH);
+    padded[..len].copy_from_slice(&data[..len]);
+    padded[BYTE_LENGTH] = expected;
+    let status = decode_airpods_data(&padded)?;
+    if let Some(model) = model_id(data).filter(|model| model_name(*model).is_none()) {
+        warnings.push(Warning::UnknownModel(model));
+    }
+    warnings.extend(battery_warnings(&padded));
+    Ok((status, warnings))
+}
+
+/// The battery nibbles of a proximity pairing message that are neither a
+/// level nor [`BATTERY_DISCONNECTED`], which the decoder leaves out
+fn battery_warnings(data: &[u8]) -> Vec<Warning> {
+    let model = model_id(data).unwrap_or_default();
+    let battery_byte = data[BYTE_BATTERY_PODS];
+    let case = ("case", low_nibble(data[BYTE_BATTERY_CASE_AND_CHARGING]));
+    let nibbles = if is_single_battery(model) {
+        vec![("headphones", low_nibble(battery_byte))]
+    } else if is_flipped(data) {
+        vec![
+            ("left", high_nibble(battery_byte)),
+            ("right", low_nibble(battery_byte)),
+            case,
+        ]
+    } else {
+        vec![
+            ("left", low_nibble(battery_byte)),
+            ("right", high_nibble(battery_byte)),
+            case,
+        ]
+    };
+    // Whatever these advertise for their case means nothing
+    let no_case = model_name(model).is_some_and(|name| NO_CASE_BATTERY.contains(&name));
+    nibbles
+        .into_iter()
+        .filter(|&(component, _)| !(no_case && component == "case"))
+        .filter(|&(_, raw)| raw > 10 && raw != BATTERY_DISCONNECTED)
+        .map(|(component, raw)| Warning::BatteryOutOfRange { component, raw })
+        .collect()
+}
+
+/// [`decode_airpods_data_best_effort`] without the warnings
+pub fn decode_airpods_data_lenient(data: &[u8]) -> Option<AirPodsStatus> {
+    decode_airpods_data_best_effort(data)
+        .ok()
+        .map(|(status, _)| status)
+}
+
+/// Decoder for AirPods and Beats proximity pairing messages, best effort
+/// unless [`AppleDecoder::with_strict`]
+#[derive(Debug, Clone, Default)]
+pub struct AppleDecoder {
+    key: Option<EncryptionKey>,
+    strict: bool,
+}
+
+impl AppleDecoder {
+    pub const NAME: &'static str = "apple";
+
+    /// Decrypt exact battery levels with `key`, see [`crate::encrypted`]
+    pub fn with_key(key: EncryptionKey) -> Self {
+        Self {
+            key: Some(key),
+            strict: false,
+        }
+    }
+
+    /// Reject messages that aren't exactly as long as a proximity pairing
+    /// message or are of a model without a name, see
+    /// [`decode_airpods_data_strict`], instead of decoding them with
+    /// [`Decoded::warnings`]
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+impl VendorDecoder for AppleDecoder {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn decode<'a>(&self, advertisement: &AdvertisementData<'a>) -> Option<Decoded<'a>> {
+        let data = advertisement
+            .manufacturer_data
+            .get(&APPLE_MANUFACTURER_ID)?;
+        let (status, warnings) = if self.strict {
+            let status = decode_airpods_data_strict(data);
+            let warnings = match status {
+                Ok(_) => battery_warnings(data),
+                Err(_) => Vec::new(),
+            };
+            (status, warnings)
+        } else {
+            match decode_airpods_data_best_effort(data) {
+                Ok((status, warnings)) => (Ok(status), warnings),
+                Err(e) => (Err(e), Vec::new()),
+            }
+        };
+        let status = status.map(|mut status| {
+            // A key that doesn't match leaves the approximate levels
+            if let Some(key) = &self.key {
+                refine_with_key(&mut status, data, key);
+            }
+            status
+        });
+        Some(Decoded {
+            data,
+            status,
+            warnings,
+        })
+    }
+
+    fn decode_partial(&self, data: &[u8]) -> Option<(AirPodsStatus, Vec<Warning>)> {
+        decode_airpods_data_best_effort(data).ok()
+    }
+}
+
+/// Convert raw battery value (0-10) to percentage (5-100%), see
+/// [`Rounding::Nearest5`]. Returns None if the device is disconnected
+/// (value 15)
+fn battery_level(raw: u8) -> Option<u8> {
+    match raw {
+        0..=10 => Some(Rounding::Nearest5.level(raw)),
+        BATTERY_DISCONNECTED => None,
+        _ => None,
+    }
+}
@@ -0,0 +1,105 @@
+//! Pluggable decoders, one per vendor (or protocol) of earbuds.
+//!
+//! A scanner hands every advertisement to each [`VendorDecoder`] it was given;
+//! a decoder picks out its own manufacturer or service data and turns it into
+//! an [`AirPodsStatus`], whose in-ear/over-ear shape fits other vendors'
+//! earbuds and headphones too. Decoders for vendors other than Apple sit
+//! behind cargo features of the same name.
+
+use crate::airpods::{AppleDecoder, ParseError};
+use crate::status::AirPodsStatus;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// The parts of an advertisement decoders look at
+#[derive(Debug, Clone, Copy)]
+pub struct AdvertisementData<'a> {
+    /// Manufacturer specific data by Bluetooth SIG company identifier
+    pub manufacturer_data: &'a HashMap<u16, Vec<u8>>,
+    /// Service data by 16-bit service UUID
+    pub service_data: &'a HashMap<u16, Vec<u8>>,
+    /// Signal strength in dBm, when reported
+    pub rssi: Option<i16>,
+}
+
+/// A decoder's take on an advertisement that had data for it
+#[derive(Debug, Clone)]
+pub struct Decoded<'a> {
+    /// The manufacturer or service data that was decoded
+    pub data: &'a [u8],
+    pub status: Result<AirPodsStatus, ParseError>,
+    /// What the decoder had to guess to come up with `status`
+    pub warnings: Vec<Warning>,
+}
+
+/// Something about an advertisement a decoder made the best of instead of
+/// rejecting it, see [`crate::AppleDecoder::with_strict`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    /// Not as long as the vendor's message, whose fields were read at their
+    /// usual positions anyway
+    Length { actual: usize, expected: usize },
+    /// The message's own length field disagrees with the payload
+    LengthField { actual: u8, expected: u8 },
+    /// A model id without a name, decoded with the layout of the vendor's
+    /// earbuds
+    UnknownModel(u16),
+    /// A battery reading that is neither a level nor "disconnected", so the
+    /// component was left out
+    BatteryOutOfRange { component: &'static str, raw: u8 },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::Length { actual, expected } => write!(
+                f,
+                "payload is {} bytes, expected {}, fields read at their usual positions",
+                actual, expected
+            ),
+            Warning::LengthField { actual, expected } => {
+                write!(
+                    f,
+                    "length field says {} bytes, expected {}",
+                    actual, expected
+                )
+            }
+            Warning::UnknownModel(model) => {
+                write!(f, "unknown model id 0x{:04x}, layout guessed", model)
+            }
+            Warning::BatteryOutOfRange { component, raw } => write!(
+                f,
+                "{} battery nibble 0x{:x} out of range, left out",
+                component, raw
+            ),
+        }
+    }
+}
+
+pub trait VendorDecoder: fmt::Debug + Send + Sync {
+    /// Short lowercase name, e.g. `apple`
+    fn name(&self) -> &'static str;
+
+    /// `None` when the advertisement carries no data for this decoder
+    fn decode<'a>(&self, advertisement: &AdvertisementData<'a>) -> Option<Decoded<'a>>;
+
+    /// Whatever can still be read from `data` that [`Self::decode`]
+    /// rejected, for a degraded result when nothing else was found, with
+    /// what had to be guessed for it. None by default.
+    fn decode_partial(&self, data: &[u8]) -> Option<(AirPodsStatus, Vec<Warning>)> {
+        let _ = data;
+        None
+    }
+}
+
+/// One decoder per vendor enabled at compile time, Apple first
+pub fn default_decoders() -> Vec<Arc<dyn VendorDecoder>> {
+    #[cfg_attr(not(any(feature = "samsung", feature = "fastpair")), allow(unused_mut))]
+    let mut decoders: Vec<Arc<dyn VendorDecoder>> = vec![Arc::new(AppleDecoder::default())];
+    #[cfg(feature = "samsung")]
+    decoders.push(Arc::new(crate::galaxy::SamsungDecoder));
+    #[cfg(feature = "fastpair")]
+    decoders.push(Arc::new(crate::fastpair::FastPairDecoder));
+    decoders
+}
@@ -0,0 +1,160 @@
+//! The encrypted half of the proximity pairing message.
+//!
+//! Bytes 11-26 are AES-128 encrypted with a key the AirPods share with the
+//! devices they are paired with. Decrypted, they carry battery levels in 1%
+//! steps instead of the 10% buckets of the cleartext nibbles. Hosts only get
+//! the key through pairing, so it has to be supplied by the user (on macOS it
+//! can be read from the Bluetooth keychain entries; BlueZ does not store it).
+
+use crate::airpods::{AIRPODS_DATA_LENGTH, is_flipped};
+use crate::status::{AirPodsStatus, EXACT_PRECISION};
+use aes::Aes128;
+use aes::cipher::{BlockDecrypt, KeyInit, generic_array::GenericArray};
+use std::fmt;
+use std::str::FromStr;
+
+const ENCRYPTED_START: usize = 11;
+const ENCRYPTED_LENGTH: usize = 16;
+
+// Byte positions in the decrypted block
+const BYTE_BATTERY_LOW_NIBBLE_POD: usize = 1;
+const BYTE_BATTERY_HIGH_NIBBLE_POD: usize = 2;
+const BYTE_BATTERY_CASE: usize = 3;
+
+const MASK_LEVEL: u8 = 0x7f;
+const LEVEL_UNKNOWN: u8 = 0x7f;
+
+/// The 16-byte proximity encryption key of one pair of AirPods
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; ENCRYPTED_LENGTH]);
+
+impl EncryptionKey {
+    pub fn new(bytes: [u8; ENCRYPTED_LENGTH]) -> Self {
+        Self(bytes)
+    }
+}
+
+// Keep the key out of logs
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// A key that isn't 32 hex digits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidKey;
+
+impl fmt::Display for InvalidKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} bytes as hex digits (spaces and colons allowed)",
+            ENCRYPTED_LENGTH
+        )
+    }
+}
+
+impl std::error::Error for InvalidKey {}
+
+/// Parses hex like `0123...ef`, `01:23:...` or `01 23 ...`
+impl FromStr for EncryptionKey {
+    type Err = InvalidKey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: Vec<u8> = s
+            .bytes()
+            .filter(|b| !b.is_ascii_whitespace() && *b != b':')
+            .collect();
+        if digits.len() != ENCRYPTED_LENGTH * 2 {
+            return Err(InvalidKey);
+        }
+        let mut key = [0; ENCRYPTED_LENGTH];
+        for (byte, pair) in key.iter_mut().zip(digits.chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| InvalidKey)?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| InvalidKey)?;
+        }
+        Ok(Self(key))
+    }
+}
+
+/// Replace the bucketed battery levels of `status` with the exact ones from
+/// the encrypted part of `data`, the message `status` was decoded from.
+///
+/// Returns `false`, leaving `status` alone, when the decrypted levels don't
+/// agree with the cleartext ones, which is what a wrong key looks like.
+pub fn refine_with_key(status: &mut AirPodsStatus, data: &[u8], key: &EncryptionKey) -> bool {
+    if data.len() != AIRPODS_DATA_LENGTH {
+        return false;
+    }
+    let mut block =
+        GenericArray::clone_from_slice(&data[ENCRYPTED_START..ENCRYPTED_START + ENCRYPTED_LENGTH]);
+    Aes128::new(&GenericArray::from(key.0)).decrypt_block(&mut block);
+
+    let level = |byte: usize| match block[byte] & MASK_LEVEL {
+        LEVEL_UNKNOWN => None,
+        level if level <= 100 => Some(level),
+        _ => None,
+    };
+    // Same order as the nibbles of the cleartext battery byte
+    let (left, right) = if is_flipped(data) {
+        (
+            level(BYTE_BATTERY_HIGH_NIBBLE_POD),
+            level(BYTE_BATTERY_LOW_NIBBLE_POD),
+        )
+    } else {
+        (
+            level(BYTE_BATTERY_LOW_NIBBLE_POD),
+            level(BYTE_BATTERY_HIGH_NIBBLE_POD),
+        )
+    };
+    let exact = |name: &str| match name {
+        "left" => left,
+        "right" => right,
+        "case" => level(BYTE_BATTERY_CASE),
+        "headphones" => level(BYTE_BATTERY_LOW_NIBBLE_POD),
+        _ => None,
+    };
+
+    // Cleartext levels are the middle of a 10% bucket (or 100)
+    let plausible = status.components().iter().all(|component| {
+        exact(&component.name).is_none_or(|exact| exact.abs_diff(component.battery) <= 10)
+    });
+    if !plausible {
+        return false;
+    }
+
+    match status {
+        AirPodsStatus::InEar {
+            battery,
+            components,
+            ..
+        } => {
+            for component in components.iter_mut() {
+                if let Some(exact) = exact(&component.name) {
+                    component.battery = exact;
+                    component.precision = EXACT_PRECISION;
+                }
+            }
+            *battery = components
+                .iter()
+                .filter(|c| c.name != "case")
+                .map(|c| c.battery)
+                .min();
+        }
+        AirPodsStatus::OverEar {
+            battery,
+            components,
+            ..
+        } => {
+            for component in components.iter_mut() {
+                if let Some(exact) = exact(&component.name) {
+                    component.battery = exact;
+                    component.precision = EXACT_PRECISION;
+                    *battery = exact;
+                }
+            }
+        }
+    }
+    true
+}
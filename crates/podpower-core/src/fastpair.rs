@@ -0,0 +1,105 @@
+//! Google Fast Pair battery notifications.
+//!
+//! Fast Pair devices (Pixel Buds, many Sony and JBL models) that are paired
+//! advertise "account data" as service data of the Fast Pair service. Next to
+//! the account key filter it can carry an unencrypted battery field with one
+//! byte each for the left bud, right bud and case: the high bit is the
+//! charging flag, the rest the level in percent, or `0x7f` when unknown.
+
+use crate::airpods::ParseError;
+use crate::decoder::{AdvertisementData, Decoded, VendorDecoder};
+use crate::status::{AirPodsStatus, Component, EXACT_PRECISION};
+
+/// 16-bit UUID of the Fast Pair service
+pub const FAST_PAIR_SERVICE_UUID: u16 = 0xfe2c;
+
+// Field types, in the low nibble of each field's header byte
+const FIELD_BATTERY_SHOW_UI: u8 = 0b0011;
+const FIELD_BATTERY_HIDE_UI: u8 = 0b0100;
+const BATTERY_FIELD_LENGTH: usize = 3;
+/// Length of the model ID advertised in pairing mode
+const MODEL_ID_LENGTH: usize = 3;
+
+const MASK_CHARGING: u8 = 0x80;
+const MASK_LEVEL: u8 = 0x7f;
+
+/// Decode Fast Pair service data as an in-ear status
+///
+/// Only account data with a battery field decodes; the 3-byte model ID that
+/// devices advertise while in pairing mode doesn't carry one.
+pub fn decode_fast_pair_data(data: &[u8]) -> Result<AirPodsStatus, ParseError> {
+    if data.len() == MODEL_ID_LENGTH {
+        return Err(ParseError::Unrecognized);
+    }
+    // Byte 0 holds flags, fields follow as (length << 4 | type, value...)
+    let Some((_flags, mut fields)) = data.split_first() else {
+        return Err(ParseError::Truncated(0));
+    };
+    let levels = loop {
+        let Some((&header, rest)) = fields.split_first() else {
+            return Err(ParseError::NoBattery);
+        };
+        let length = (header >> 4) as usize;
+        if rest.len() < length {
+            return Err(ParseError::Truncated(data.len()));
+        }
+        let (value, rest) = rest.split_at(length);
+        match header & 0x0f {
+            FIELD_BATTERY_SHOW_UI | FIELD_BATTERY_HIDE_UI if length == BATTERY_FIELD_LENGTH => {
+                break value;
+            }
+            _ => fields = rest,
+        }
+    };
+
+    let components: Vec<Component> = ["left", "right", "case"]
+        .into_iter()
+        .zip(levels)
+        .filter_map(|(name, &byte)| {
+            let level = byte & MASK_LEVEL;
+            (level <= 100).then(|| Component {
+                name: name.into(),
+                battery: level,
+                charging: (byte & MASK_CHARGING) != 0,
+                precision: EXACT_PRECISION,
+                raw: None,
+            })
+        })
+        .collect();
+    if components.is_empty() {
+        return Err(ParseError::NoBattery);
+    }
+
+    let battery = components
+        .iter()
+        .filter(|c| c.name != "case")
+        .map(|c| c.battery)
+        .min();
+    Ok(AirPodsStatus::InEar {
+        model: "Fast Pair earbuds".into(),
+        battery,
+        components,
+        connection_state: None,
+        stored: None,
+        in_ear: None,
+    })
+}
+
+/// Decoder for Fast Pair account data with a battery field
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FastPairDecoder;
+
+impl VendorDecoder for FastPairDecoder {
+    fn name(&self) -> &'static str {
+        "fastpair"
+    }
+
+    fn decode<'a>(&self, advertisement: &AdvertisementData<'a>) -> Option<Decoded<'a>> {
+        let data = advertisement.service_data.get(&FAST_PAIR_SERVICE_UUID)?;
+        Some(Decoded {
+            data,
+            status: decode_fast_pair_data(data),
+            warnings: Vec::new(),
+        })
+    }
+}
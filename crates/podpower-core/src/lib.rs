@@ -0,0 +1,34 @@
+//! Parsing and data models for AirPods BLE advertisements.
+//!
+//! This crate is deliberately free of any Bluetooth stack or async runtime so
+//! that consumers which already have the raw manufacturer data (from their own
+//! scanner, a capture file, or a test fixture) can decode it cheaply.
+
+pub mod aap;
+pub mod airpods;
+pub mod decoder;
+pub mod encrypted;
+#[cfg(feature = "fastpair")]
+pub mod fastpair;
+#[cfg(feature = "samsung")]
+pub mod galaxy;
+#[cfg(feature = "serde")]
+pub mod payload;
+pub mod status;
+
+pub use aap::ListeningMode;
+pub use airpods::{
+    AIRPODS_DATA_LENGTH, APPLE_MANUFACTURER_ID, AppleDecoder, PROXIMITY_PAIRING_TYPE, ParseError,
+    color_id, decode_airpods_data, decode_airpods_data_best_effort, decode_airpods_data_lenient,
+    decode_airpods_data_strict, model_id, model_name, parse_airpods_data,
+};
+pub use decoder::{AdvertisementData, Decoded, VendorDecoder, Warning, default_decoders};
+pub use encrypted::{EncryptionKey, InvalidKey, refine_with_key};
+#[cfg(feature = "fastpair")]
+pub use fastpair::{FAST_PAIR_SERVICE_UUID, FastPairDecoder, decode_fast_pair_data};
+#[cfg(feature = "samsung")]
+pub use galaxy::{SAMSUNG_MANUFACTURER_ID, SamsungDecoder, decode_galaxy_buds_data};
+pub use status::{
+    APPROXIMATE_PRECISION, AirPodsStatus, Component, ConnectionState, EXACT_PRECISION,
+    EarDetection, NormalizedComponent, NormalizedStatus, Role, Rounding,
+};
@@ -0,0 +1,381 @@
+//! Versioned JSON payloads shared by the CLI and the daemon's APIs.
+
+use crate::aap::ListeningMode;
+use crate::status::{AirPodsStatus, NormalizedStatus, Role};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// Version of the JSON output shape, bumped on incompatible changes
+pub const SCHEMA_VERSION: u32 = 2;
+/// Last version with the `type`-tagged in-ear/over-ear shape of
+/// [`AirPodsStatus`], which the daemon's query socket still speaks
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+/// Oldest version that can still be requested. Version 0 is the unversioned
+/// shape from before `schema_version` existed.
+pub const MIN_SCHEMA_VERSION: u32 = 0;
+
+/// Whether payloads can still be rendered in `version`
+pub fn is_supported(version: u32) -> bool {
+    (MIN_SCHEMA_VERSION..=SCHEMA_VERSION).contains(&version)
+}
+
+/// A status as printed by `--format json` and served by `GET /status`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct StatusPayload {
+    /// Absent in version 0 payloads
+    #[serde(default, skip_serializing_if = "is_unversioned")]
+    pub schema_version: u32,
+    /// The name the device was paired under, e.g. "Viktor's AirPods Pro",
+    /// or the model when it isn't known. Absent in version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub status: StatusBody,
+    /// Derived from the earbuds of in-ear devices. Absent in version 0
+    /// payloads.
+    #[serde(flatten, default)]
+    pub pods: Option<PodsSummary>,
+    /// When the advertisement was received (RFC 3339, UTC). Absent in
+    /// version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_at: Option<String>,
+    /// Milliseconds between `observed_at` and rendering the payload, to grey
+    /// out stale readings from the daemon. Absent in version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_ms: Option<u64>,
+    /// How likely these are the user's own AirPods rather than someone
+    /// else's nearby, from 0 to 100. Absent when the scan was pinned to an
+    /// address, and in version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<u8>,
+    /// The advertisement failed validation, e.g. it had a weak signal or an
+    /// unexpected length, and this is whatever could be read from it. Only
+    /// with `--partial`, and never in version 0 payloads.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub partial: bool,
+    /// Components the device has but didn't report, e.g. an earbud out of
+    /// range, for a fixed layout. Only with `--show-missing`, and never in
+    /// version 0 payloads.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<Role>,
+    /// Noise control of connected AirPods, read over AAP. Only with
+    /// `--listening-mode`, and never in version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listening_mode: Option<ListeningMode>,
+    /// What the scan saw along the way. Only with `--scan-stats`, and never
+    /// in version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan: Option<ScanStats>,
+    /// The advertisement data the status was decoded from, as hex, for
+    /// decoders of their own and reports of unknown models. Only with
+    /// `--raw`, and never in version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_data: Option<String>,
+    /// What had to be guessed to decode the advertisement, e.g. the layout
+    /// of an unknown model, as sentences for people. Empty for a clean
+    /// decode; absent in version 0 payloads and when the producer doesn't
+    /// say.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<String>>,
+}
+
+/// The status itself, in the shape of the payload's version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum StatusBody {
+    /// Version 2 and later: components by role
+    Normalized(NormalizedStatus),
+    /// Versions 0 and 1: `type` is `in_ear` or `over_ear`
+    Legacy(AirPodsStatus),
+}
+
+/// Convenience fields for in-ear devices, so consumers don't have to
+/// recompute them from `components` (minding disconnected earbuds)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PodsSummary {
+    /// Lowest connected earbud
+    pub lowest: Option<u8>,
+    /// Mean of the connected earbuds, rounded
+    pub average: Option<u8>,
+    /// Both earbuds are charging
+    pub pods_charging: bool,
+}
+
+impl PodsSummary {
+    pub fn new(status: &AirPodsStatus) -> Option<Self> {
+        match status {
+            AirPodsStatus::InEar { .. } => Some(Self {
+                lowest: status.lowest(),
+                average: status.average(),
+                pods_charging: status.pods_charging(),
+            }),
+            AirPodsStatus::OverEar { .. } => None,
+        }
+    }
+}
+
+impl StatusPayload {
+    pub fn new(status: AirPodsStatus) -> Self {
+        Self::with_version(status, SCHEMA_VERSION)
+    }
+
+    /// The payload in the shape of an older `version`, for consumers that
+    /// pinned one. Callers check [`is_supported`] first.
+    pub fn with_version(status: AirPodsStatus, version: u32) -> Self {
+        Self {
+            schema_version: version,
+            name: (!is_unversioned(&version)).then(|| status.model().to_string()),
+            pods: if is_unversioned(&version) {
+                None
+            } else {
+                PodsSummary::new(&status)
+            },
+            status: if version > LEGACY_SCHEMA_VERSION {
+                StatusBody::Normalized(NormalizedStatus::from(&status))
+            } else {
+                StatusBody::Legacy(status)
+            },
+            observed_at: None,
+            age_ms: None,
+            confidence: None,
+            partial: false,
+            missing: Vec::new(),
+            listening_mode: None,
+            scan: None,
+            raw_data: None,
+            warnings: None,
+        }
+    }
+
+    /// Add freshness metadata for a reading taken at `observed_at`
+    pub fn observed(mut self, observed_at: SystemTime) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            let age = SystemTime::now()
+                .duration_since(observed_at)
+                .unwrap_or_default();
+            self.observed_at = Some(humantime::format_rfc3339_millis(observed_at).to_string());
+            self.age_ms = Some(age.as_millis() as u64);
+        }
+        self
+    }
+
+    /// Add how likely the reading is of the user's own AirPods
+    pub fn with_confidence(mut self, confidence: Option<u8>) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.confidence = confidence;
+        }
+        self
+    }
+
+    /// Flag a degraded reading, see [`Self::partial`]
+    pub fn with_partial(mut self, partial: bool) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.partial = partial;
+        }
+        self
+    }
+
+    /// List the components named in `missing`, see [`AirPodsStatus::missing`]
+    pub fn with_missing(mut self, missing: &[&str]) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.missing = missing.iter().filter_map(|name| Role::of(name)).collect();
+        }
+        self
+    }
+
+    /// Name the device by what it was paired under, when known
+    pub fn with_name(mut self, name: Option<String>) -> Self {
+        if !is_unversioned(&self.schema_version) && name.is_some() {
+            self.name = name;
+        }
+        self
+    }
+
+    /// Add the noise control read from connected AirPods
+    pub fn with_listening_mode(mut self, listening_mode: Option<ListeningMode>) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.listening_mode = listening_mode;
+        }
+        self
+    }
+
+    /// Add what the scan saw
+    pub fn with_scan(mut self, scan: Option<ScanStats>) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.scan = scan;
+        }
+        self
+    }
+
+    /// Add the bytes the status was decoded from, see [`Self::raw_data`]
+    pub fn with_raw_data(mut self, data: Option<&[u8]>) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.raw_data = data.map(|data| data.iter().map(|b| format!("{:02x}", b)).collect());
+        }
+        self
+    }
+
+    /// Say what had to be guessed to decode the status, see [`Self::warnings`]
+    pub fn with_warnings(mut self, warnings: &[String]) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.warnings = Some(warnings.to_vec());
+        }
+        self
+    }
+}
+
+/// What a scan saw, to tell a quiet radio from AirPods that are heard but
+/// turned down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ScanStats {
+    /// How long the scan ran, in milliseconds
+    pub duration_ms: u64,
+    /// Distinct advertisements of the enabled vendors. A device repeating
+    /// the same one counts once.
+    pub advertisements: u32,
+    /// Those of them from Apple devices, including ones that turned out not
+    /// to be AirPods
+    pub apple_advertisements: u32,
+    /// Addresses the advertisements came from
+    pub devices_considered: u32,
+    /// Those of them none of whose advertisements were accepted, for a weak
+    /// signal, a filter or an unreadable payload
+    pub devices_rejected: u32,
+}
+
+/// An error, e.g.
+/// `{"schema_version": 1, "error": "AirPods not found", "code": "not_found"}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ErrorPayload {
+    /// Absent in version 0 payloads
+    #[serde(default, skip_serializing_if = "is_unversioned")]
+    pub schema_version: u32,
+    pub error: String,
+    /// Stable identifier of the kind of error, like `not_found` or
+    /// `bluetooth_off`. Absent in version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// What the scan that found nothing saw. Only with `--scan-stats`, and
+    /// never in version 0 payloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan: Option<ScanStats>,
+}
+
+impl ErrorPayload {
+    pub fn new(error: impl Into<String>) -> Self {
+        Self::with_version(error, SCHEMA_VERSION)
+    }
+
+    pub fn with_version(error: impl Into<String>, version: u32) -> Self {
+        Self {
+            schema_version: version,
+            error: error.into(),
+            code: None,
+            scan: None,
+        }
+    }
+
+    /// Attach an error code, unless this is a version 0 payload
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.code = Some(code.into());
+        }
+        self
+    }
+
+    /// Add what the scan saw
+    pub fn with_scan(mut self, scan: Option<ScanStats>) -> Self {
+        if !is_unversioned(&self.schema_version) {
+            self.scan = scan;
+        }
+        self
+    }
+}
+
+/// No AirPods in range, as printed with `--empty-ok`:
+/// `{"schema_version": 2, "type": "none"}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct EmptyPayload {
+    /// Absent in version 0 payloads
+    #[serde(default, skip_serializing_if = "is_unversioned")]
+    pub schema_version: u32,
+    /// Always `none`, where a status has its device type
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl EmptyPayload {
+    pub fn with_version(version: u32) -> Self {
+        Self {
+            schema_version: version,
+            kind: "none".to_string(),
+        }
+    }
+}
+
+/// Flat status for Home Assistant's command_line and RESTful sensors, as
+/// printed by `--format hass` and served by `GET /hass`. Every key is always
+/// there, `null` when it doesn't apply, so sensor templates never hit an
+/// undefined one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct HassPayload {
+    /// Whether AirPods were found. Everything else is `null` when not.
+    pub available: bool,
+    pub model: Option<String>,
+    /// The name the device was paired under, or the model when it isn't
+    /// known
+    pub name: Option<String>,
+    /// The lowest earbud of in-ear devices, the headphones of over-ear ones
+    pub battery: Option<u8>,
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+    pub case: Option<u8>,
+    pub headphones: Option<u8>,
+    pub left_charging: Option<bool>,
+    pub right_charging: Option<bool>,
+    pub case_charging: Option<bool>,
+    pub headphones_charging: Option<bool>,
+}
+
+impl HassPayload {
+    /// `status` is `None` when no AirPods were found
+    pub fn new(status: Option<&AirPodsStatus>, name: Option<String>) -> Self {
+        let Some(status) = status else {
+            return Self::default();
+        };
+        let mut payload = Self {
+            available: true,
+            model: Some(status.model().to_string()),
+            name: name.or_else(|| Some(status.model().to_string())),
+            battery: status.battery(),
+            ..Self::default()
+        };
+        for component in status.components() {
+            let (battery, charging) = match Role::of(&component.name) {
+                Some(Role::Left) => (&mut payload.left, &mut payload.left_charging),
+                Some(Role::Right) => (&mut payload.right, &mut payload.right_charging),
+                Some(Role::Case) => (&mut payload.case, &mut payload.case_charging),
+                Some(Role::Headset) => (&mut payload.headphones, &mut payload.headphones_charging),
+                None => continue,
+            };
+            *battery = Some(component.battery);
+            *charging = Some(component.charging);
+        }
+        payload
+    }
+}
+
+fn is_unversioned(version: &u32) -> bool {
+    *version == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
@@ -0,0 +1,452 @@
+use crate::airpods::NO_CASE_BATTERY;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Precision of the cleartext battery levels, which come in 10% steps
+/// reported as the middle of the step (`x * 10 + 5`)
+pub const APPROXIMATE_PRECISION: u8 = 10;
+/// Precision of battery levels decrypted with the device's key
+pub const EXACT_PRECISION: u8 = 1;
+
+/// A single component (earbud, case, or headphones) with its battery status
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Component {
+    pub name: String,
+    pub battery: u8,
+    pub charging: bool,
+    /// How many percentage points `battery` may be off by: 10 for the
+    /// cleartext levels, 1 for decrypted ones
+    #[cfg_attr(feature = "serde", serde(default = "approximate_precision"))]
+    pub precision: u8,
+    /// The 0-10 reading `battery` was mapped from, for devices that report
+    /// one
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub raw: Option<u8>,
+}
+
+impl Component {
+    /// Whether `battery` is an estimate rather than an exact reading
+    pub fn is_approximate(&self) -> bool {
+        self.precision > EXACT_PRECISION
+    }
+}
+
+/// How a 0-10 [`Component::raw`] reading maps to a battery level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// The middle of the 10% step, `raw * 10 + 5`, and 100 for 10
+    #[default]
+    Nearest5,
+    /// The bottom of the step, `raw * 10`
+    Nearest10,
+    /// The reading itself
+    Raw,
+}
+
+impl Rounding {
+    /// The level of a `raw` reading from 0 to 10
+    pub fn level(self, raw: u8) -> u8 {
+        match self {
+            Rounding::Nearest5 if raw >= 10 => 100,
+            Rounding::Nearest5 => raw * 10 + 5,
+            Rounding::Nearest10 => raw * 10,
+            Rounding::Raw => raw,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn approximate_precision() -> u8 {
+    APPROXIMATE_PRECISION
+}
+
+/// What the device is doing with the host it is connected to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ConnectionState {
+    Disconnected,
+    Idle,
+    Music,
+    /// On a call, including while it rings or hangs up
+    Call,
+}
+
+impl fmt::Display for ConnectionState {
+    /// The same snake_case name as in JSON
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConnectionState::Disconnected => "disconnected",
+            ConnectionState::Idle => "idle",
+            ConnectionState::Music => "music",
+            ConnectionState::Call => "call",
+        })
+    }
+}
+
+/// Which earbuds are in an ear
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct EarDetection {
+    pub left: bool,
+    pub right: bool,
+}
+
+/// Main AirPods status with unified component-based structure
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum AirPodsStatus {
+    InEar {
+        model: String,
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        battery: Option<u8>,
+        components: Vec<Component>,
+        /// What the earbuds are doing with their source device, for devices
+        /// that report it
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        connection_state: Option<ConnectionState>,
+        /// Both earbuds are in the case and its lid is closed, for devices
+        /// that report it
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        stored: Option<bool>,
+        /// Which earbuds are in an ear, for devices that report it
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        in_ear: Option<EarDetection>,
+    },
+    OverEar {
+        model: String,
+        battery: u8,
+        components: Vec<Component>,
+        /// Whether the headphones are being worn
+        #[cfg_attr(feature = "serde", serde(default))]
+        on_head: bool,
+        /// Connected to a charger, even when no longer charging because the
+        /// battery is full
+        #[cfg_attr(feature = "serde", serde(default))]
+        plugged_in: bool,
+        /// What the headphones are doing with their source device, for
+        /// devices that report it
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        connection_state: Option<ConnectionState>,
+    },
+}
+
+impl AirPodsStatus {
+    pub fn model(&self) -> &str {
+        match self {
+            AirPodsStatus::InEar { model, .. } | AirPodsStatus::OverEar { model, .. } => model,
+        }
+    }
+
+    /// Top-level battery level (lowest earbud for in-ear devices)
+    pub fn battery(&self) -> Option<u8> {
+        match self {
+            AirPodsStatus::InEar { battery, .. } => *battery,
+            AirPodsStatus::OverEar { battery, .. } => Some(*battery),
+        }
+    }
+
+    pub fn connection_state(&self) -> Option<ConnectionState> {
+        match self {
+            AirPodsStatus::InEar {
+                connection_state, ..
+            }
+            | AirPodsStatus::OverEar {
+                connection_state, ..
+            } => *connection_state,
+        }
+    }
+
+    /// Whether the earbuds are put away in the closed case, `false` when the
+    /// device doesn't say
+    pub fn stored(&self) -> bool {
+        match self {
+            AirPodsStatus::InEar { stored, .. } => stored.unwrap_or(false),
+            AirPodsStatus::OverEar { .. } => false,
+        }
+    }
+
+    /// Which earbuds are in an ear, `None` for over-ear devices and ones
+    /// that don't say
+    pub fn in_ear(&self) -> Option<EarDetection> {
+        match self {
+            AirPodsStatus::InEar { in_ear, .. } => *in_ear,
+            AirPodsStatus::OverEar { .. } => None,
+        }
+    }
+
+    /// Names of the components being worn: earbuds in an ear, or
+    /// headphones on a head. `None` when the device doesn't say.
+    pub fn worn(&self) -> Option<Vec<&'static str>> {
+        let worn = match self {
+            AirPodsStatus::InEar { in_ear, .. } => {
+                let in_ear = (*in_ear)?;
+                [("left", in_ear.left), ("right", in_ear.right)]
+                    .into_iter()
+                    .filter_map(|(name, worn)| worn.then_some(name))
+                    .collect()
+            }
+            AirPodsStatus::OverEar { on_head: true, .. } => vec!["headphones"],
+            AirPodsStatus::OverEar { .. } => Vec::new(),
+        };
+        Some(worn)
+    }
+
+    /// The status with the levels of approximate components mapped from
+    /// their raw reading by `rounding`, keeping any offset added to the
+    /// default mapping, see [`Self::calibrated`]. Exact levels, like
+    /// decrypted ones, stay as they are.
+    pub fn rounded(&self, rounding: Rounding) -> AirPodsStatus {
+        self.map_levels(|component| {
+            let Some(raw) = component.raw.filter(|_| component.is_approximate()) else {
+                return component.battery;
+            };
+            if rounding == Rounding::Raw {
+                return raw;
+            }
+            let offset = i16::from(component.battery) - i16::from(Rounding::default().level(raw));
+            (i16::from(rounding.level(raw)) + offset).clamp(0, 100) as u8
+        })
+    }
+
+    /// The status with `offset(name)` percentage points added to the level
+    /// of every component, within 0-100
+    pub fn calibrated(&self, offset: impl Fn(&str) -> i8) -> AirPodsStatus {
+        self.map_levels(|component| {
+            (i16::from(component.battery) + i16::from(offset(&component.name))).clamp(0, 100) as u8
+        })
+    }
+
+    /// The status with every component at `level(component)`, and the
+    /// top-level battery to match
+    fn map_levels(&self, level: impl Fn(&Component) -> u8) -> AirPodsStatus {
+        let mut status = self.clone();
+        match &mut status {
+            AirPodsStatus::InEar {
+                battery,
+                components,
+                ..
+            } => {
+                components.iter_mut().for_each(|c| c.battery = level(c));
+                *battery = components
+                    .iter()
+                    .filter(|c| c.name != "case")
+                    .map(|c| c.battery)
+                    .min();
+            }
+            AirPodsStatus::OverEar {
+                battery,
+                components,
+                ..
+            } => {
+                components.iter_mut().for_each(|c| c.battery = level(c));
+                if let Some(headphones) = components.first() {
+                    *battery = headphones.battery;
+                }
+            }
+        }
+        status
+    }
+
+    pub fn components(&self) -> &[Component] {
+        match self {
+            AirPodsStatus::InEar { components, .. } | AirPodsStatus::OverEar { components, .. } => {
+                components
+            }
+        }
+    }
+
+    /// Connected earbuds of in-ear devices, none for over-ear ones
+    fn pods(&self) -> impl Iterator<Item = &Component> {
+        let components = match self {
+            AirPodsStatus::InEar { components, .. } => components.as_slice(),
+            AirPodsStatus::OverEar { .. } => &[],
+        };
+        components
+            .iter()
+            .filter(|c| c.name == "left" || c.name == "right")
+    }
+
+    /// Lowest connected earbud, ignoring the case
+    pub fn lowest(&self) -> Option<u8> {
+        self.pods().map(|c| c.battery).min()
+    }
+
+    /// Mean of the connected earbuds, rounded, ignoring the case
+    pub fn average(&self) -> Option<u8> {
+        let (sum, count) = self.pods().fold((0u32, 0u32), |(sum, count), c| {
+            (sum + c.battery as u32, count + 1)
+        });
+        (count > 0).then(|| ((sum + count / 2) / count) as u8)
+    }
+
+    /// Whether both earbuds are connected and charging
+    pub fn pods_charging(&self) -> bool {
+        self.pods().filter(|c| c.charging).count() == 2
+    }
+
+    /// Names of the components a device of this kind has but that weren't
+    /// reported, e.g. an earbud that is out of range
+    pub fn missing(&self) -> Vec<&'static str> {
+        let expected: &[&'static str] = match self {
+            AirPodsStatus::InEar { model, .. } if NO_CASE_BATTERY.contains(&model.as_str()) => {
+                &["left", "right"]
+            }
+            AirPodsStatus::InEar { .. } => &["left", "right", "case"],
+            AirPodsStatus::OverEar { .. } => &["headphones"],
+        };
+        expected
+            .iter()
+            .copied()
+            .filter(|name| !self.components().iter().any(|c| c.name == *name))
+            .collect()
+    }
+}
+
+/// What a component is, independent of vendor and form factor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Role {
+    Left,
+    Right,
+    Case,
+    /// Single-battery headphones or headsets
+    Headset,
+}
+
+impl Role {
+    /// Role of a component by its [`Component::name`]
+    pub fn of(name: &str) -> Option<Self> {
+        Some(match name {
+            "left" => Role::Left,
+            "right" => Role::Right,
+            "case" => Role::Case,
+            "headphones" => Role::Headset,
+            _ => return None,
+        })
+    }
+}
+
+/// A component in the [`NormalizedStatus`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct NormalizedComponent {
+    pub role: Role,
+    /// Battery level in percent
+    pub level: u8,
+    pub charging: bool,
+    /// How many percentage points `level` may be off by
+    pub precision: u8,
+    /// The 0-10 reading `level` was mapped from, for devices that report
+    /// one
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub raw: Option<u8>,
+}
+
+/// Device-agnostic status: a flat list of components by role instead of the
+/// in-ear/over-ear split of [`AirPodsStatus`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct NormalizedStatus {
+    pub model: String,
+    /// Lowest earbud, or the headset
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub battery: Option<u8>,
+    pub components: Vec<NormalizedComponent>,
+    /// Whether the device is being worn, for devices that report it
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub on_head: Option<bool>,
+    /// Whether the device is connected to a charger, for devices that
+    /// report it separately from `charging`
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub plugged_in: Option<bool>,
+    /// What the device is doing with its source device, for devices that
+    /// report it
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub connection_state: Option<ConnectionState>,
+    /// Whether the earbuds are in the closed case, for devices that report it
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub stored: Option<bool>,
+    /// Which earbuds are in an ear, for devices that report it
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub in_ear: Option<EarDetection>,
+}
+
+impl From<&AirPodsStatus> for NormalizedStatus {
+    fn from(status: &AirPodsStatus) -> Self {
+        let (on_head, plugged_in, stored) = match status {
+            AirPodsStatus::InEar { stored, .. } => (None, None, *stored),
+            AirPodsStatus::OverEar {
+                on_head,
+                plugged_in,
+                ..
+            } => (Some(*on_head), Some(*plugged_in), None),
+        };
+        Self {
+            model: status.model().to_string(),
+            battery: status.battery(),
+            components: status
+                .components()
+                .iter()
+                .filter_map(|c| {
+                    Some(NormalizedComponent {
+                        role: Role::of(&c.name)?,
+                        level: c.battery,
+                        charging: c.charging,
+                        precision: c.precision,
+                        raw: c.raw,
+                    })
+                })
+                .collect(),
+            on_head,
+            plugged_in,
+            connection_state: status.connection_state(),
+            stored,
+            in_ear: status.in_ear(),
+        }
+    }
+}
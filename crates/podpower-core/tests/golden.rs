@@ -0,0 +1,203 @@
+//! Golden tests: every `fixtures/*.hex` payload is decoded and compared with
+//! the JSON next to it. Run with `UPDATE_GOLDEN=1` to rewrite the JSON after
+//! an intended change, and review the diff.
+
+use podpower_core::decode_airpods_data;
+use podpower_core::payload::StatusPayload;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hex"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// The bytes of a fixture, hex digits with `#` comments
+fn payload(path: &Path) -> Vec<u8> {
+    let text = std::fs::read_to_string(path).unwrap();
+    let digits: String = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.chars().filter(char::is_ascii_hexdigit))
+        .collect();
+    bytes(&digits)
+}
+
+fn bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// The latest JSON output for a decoded payload, or the reason it was rejected
+fn decoded(data: &[u8]) -> Value {
+    match decode_airpods_data(data) {
+        Ok(status) => serde_json::to_value(StatusPayload::new(status)).unwrap(),
+        Err(e) => json!({ "error": e.to_string() }),
+    }
+}
+
+#[test]
+fn fixtures_decode_to_their_golden_output() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no fixtures found");
+
+    let mut mismatches = Vec::new();
+    for path in &fixtures {
+        let actual = decoded(&payload(path));
+        let golden = path.with_extension("json");
+        if update {
+            let text = serde_json::to_string_pretty(&actual).unwrap() + "\n";
+            std::fs::write(&golden, text).unwrap();
+            continue;
+        }
+        let expected: Value = std::fs::read_to_string(&golden)
+            .map(|text| serde_json::from_str(&text).unwrap())
+            .unwrap_or_else(|e| panic!("{}: {}", golden.display(), e));
+        if actual != expected {
+            mismatches.push(format!(
+                "{}:\nexpected {}\n     got {}",
+                path.file_name().unwrap().to_string_lossy(),
+                expected,
+                actual
+            ));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{} of {} fixtures changed (UPDATE_GOLDEN=1 to accept):\n{}",
+        mismatches.len(),
+        fixtures.len(),
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn rounding_maps_the_raw_readings() {
+    use podpower_core::Rounding;
+
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/airpods_2_in_ear.hex");
+    let status = decode_airpods_data(&payload(&fixture)).unwrap();
+    let levels = |rounding| {
+        let status = status.rounded(rounding);
+        let levels: Vec<u8> = status.components().iter().map(|c| c.battery).collect();
+        (status.battery(), levels)
+    };
+    assert_eq!(levels(Rounding::Nearest5), (Some(85), vec![85, 95, 45]));
+    assert_eq!(levels(Rounding::Nearest10), (Some(80), vec![80, 90, 40]));
+    assert_eq!(levels(Rounding::Raw), (Some(8), vec![8, 9, 4]));
+}
+
+#[test]
+fn hass_payload_keeps_every_key() {
+    use podpower_core::payload::HassPayload;
+
+    let fixture =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/airpods_max_on_head.hex");
+    let status = decode_airpods_data(&payload(&fixture)).unwrap();
+    let found = serde_json::to_value(HassPayload::new(Some(&status), None)).unwrap();
+    let missing = serde_json::to_value(HassPayload::new(None, None)).unwrap();
+
+    assert_eq!(found["available"], true);
+    assert_eq!(found["name"], found["model"]);
+    assert_eq!(found["battery"], found["headphones"]);
+    assert_eq!(found["left"], Value::Null);
+    assert_eq!(missing["available"], false);
+    assert_eq!(missing["battery"], Value::Null);
+    let keys = |value: &Value| {
+        value
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(keys(&found), keys(&missing));
+}
+
+#[test]
+fn raw_data_is_the_payload_as_hex() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/airpods_2_in_ear.hex");
+    let data = payload(&fixture);
+    let status = decode_airpods_data(&data).unwrap();
+    let raw_data = |version| {
+        StatusPayload::with_version(status.clone(), version)
+            .with_raw_data(Some(&data))
+            .raw_data
+    };
+
+    let hex = raw_data(2).unwrap();
+    assert_eq!(hex.len(), data.len() * 2);
+    assert_eq!(bytes(&hex), data);
+    assert_eq!(raw_data(0), None);
+}
+
+#[test]
+fn lenient_decoding_warns_where_strict_decoding_rejects() {
+    use podpower_core::{
+        ParseError, Warning, decode_airpods_data_best_effort, decode_airpods_data_strict,
+    };
+
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/airpods_2_in_ear.hex");
+    let data = payload(&fixture);
+    let warnings = |data: &[u8]| decode_airpods_data_best_effort(data).unwrap().1;
+    assert!(decode_airpods_data_strict(&data).is_ok());
+    assert_eq!(warnings(&data), []);
+
+    let mut unknown = data.clone();
+    unknown[3..5].copy_from_slice(&[0x2b, 0x20]);
+    assert_eq!(
+        decode_airpods_data_strict(&unknown),
+        Err(ParseError::UnknownModel(0x2b20))
+    );
+    assert_eq!(warnings(&unknown), [Warning::UnknownModel(0x2b20)]);
+
+    let long = [data.as_slice(), &[0, 0]].concat();
+    assert_eq!(
+        decode_airpods_data_strict(&long),
+        Err(ParseError::WrongLength(long.len()))
+    );
+    assert_eq!(
+        warnings(&long),
+        [Warning::Length {
+            actual: long.len(),
+            expected: data.len()
+        }]
+    );
+    assert_eq!(
+        decode_airpods_data_best_effort(&data[..4]),
+        Err(ParseError::Truncated(4))
+    );
+}
+
+#[test]
+fn battery_nibbles_out_of_range_are_warned_about() {
+    use podpower_core::{Warning, decode_airpods_data_best_effort};
+
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/airpods_2_in_ear.hex");
+    let mut data = payload(&fixture);
+    // The case's nibble, whichever way round the pods are
+    data[7] = (data[7] & 0xf0) | 0x0c;
+    let (status, warnings) = decode_airpods_data_best_effort(&data).unwrap();
+
+    assert!(status.components().iter().all(|c| c.name != "case"));
+    assert_eq!(
+        warnings,
+        [Warning::BatteryOutOfRange {
+            component: "case",
+            raw: 0x0c
+        }]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "case battery nibble 0xc out of range, left out"
+    );
+}
@@ -0,0 +1,201 @@
+//! Property tests: the decoders index fixed offsets into untrusted bytes, so
+//! they get hammered with generated payloads and must never panic or report
+//! a level outside 0-100. Payloads come from a seeded generator, so a failure
+//! names the seed that reproduces it.
+
+use podpower_core::{
+    AIRPODS_DATA_LENGTH, AirPodsStatus, EncryptionKey, PROXIMITY_PAIRING_TYPE, ParseError,
+    decode_airpods_data, decode_airpods_data_lenient, refine_with_key,
+};
+
+const CASES: u64 = 20_000;
+
+/// SplitMix64, plenty for spreading bytes around
+struct Bytes(u64);
+
+impl Bytes {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+
+    /// Arbitrary bytes of a length around the interesting ones
+    fn any(&mut self) -> Vec<u8> {
+        let len = (self.next() % 64) as usize;
+        self.fill(len)
+    }
+
+    /// A frame that passes the length and type checks, so the battery
+    /// fields get decoded
+    fn proximity_pairing(&mut self) -> Vec<u8> {
+        let mut data = self.fill(AIRPODS_DATA_LENGTH);
+        data[0] = PROXIMITY_PAIRING_TYPE;
+        data[1] = (AIRPODS_DATA_LENGTH - 2) as u8;
+        data
+    }
+}
+
+/// The levels a status reports are percentages, and the summaries agree
+/// with the components
+fn assert_sane(status: &AirPodsStatus, seed: u64) {
+    for component in status.components() {
+        assert!(
+            component.battery <= 100,
+            "seed {}: {} at {}%",
+            seed,
+            component.name,
+            component.battery
+        );
+    }
+    if let Some(battery) = status.battery() {
+        assert!(battery <= 100, "seed {}: battery at {}%", seed, battery);
+    }
+    if let (Some(lowest), Some(average)) = (status.lowest(), status.average()) {
+        assert!(lowest <= average, "seed {}: lowest above average", seed);
+        assert!(average <= 100, "seed {}: average at {}%", seed, average);
+    }
+    if let AirPodsStatus::InEar { battery, .. } = status {
+        assert_eq!(*battery, status.lowest(), "seed {}", seed);
+    }
+}
+
+#[test]
+fn arbitrary_bytes_never_panic() {
+    for seed in 0..CASES {
+        let data = Bytes(seed).any();
+        if let Ok(status) = decode_airpods_data(&data) {
+            assert_sane(&status, seed);
+        }
+        if let Some(status) = decode_airpods_data_lenient(&data) {
+            assert_sane(&status, seed);
+        }
+    }
+}
+
+#[test]
+fn valid_frames_always_decode_to_percentages() {
+    for seed in 0..CASES {
+        let data = Bytes(seed).proximity_pairing();
+        match decode_airpods_data(&data) {
+            Ok(status) => assert_sane(&status, seed),
+            // Headphones without a level
+            Err(ParseError::NoBattery) => {}
+            Err(e) => panic!("seed {}: rejected: {}", seed, e),
+        }
+    }
+}
+
+#[test]
+fn every_battery_byte_decodes_for_every_form_factor() {
+    // AirPods Pro, AirPods Max, Powerbeats Pro, Beats Solo Buds and unknown
+    for model in [0x0e20u16, 0x0a20, 0x0b20, 0x2520, 0xffff] {
+        for pods in 0..=255u8 {
+            for case in 0..=255u8 {
+                let mut data = Bytes(u64::from(model)).proximity_pairing();
+                data[3..5].copy_from_slice(&model.to_be_bytes());
+                data[6] = pods;
+                data[7] = case;
+                let seed = u64::from(pods) << 8 | u64::from(case);
+                match decode_airpods_data(&data) {
+                    Ok(status) => assert_sane(&status, seed),
+                    // Headphones without a level
+                    Err(e) => assert_eq!(e, ParseError::NoBattery),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn lenient_decoding_never_panics_on_short_or_long_frames() {
+    for seed in 0..CASES {
+        let mut bytes = Bytes(seed);
+        let len = (bytes.next() % 40) as usize;
+        let mut data = bytes.fill(len);
+        if let Some(first) = data.first_mut() {
+            *first = PROXIMITY_PAIRING_TYPE;
+        }
+        if let Some(status) = decode_airpods_data_lenient(&data) {
+            assert_sane(&status, seed);
+        }
+    }
+}
+
+#[test]
+fn decrypting_with_any_key_keeps_levels_in_range() {
+    for seed in 0..CASES {
+        let mut bytes = Bytes(seed);
+        let data = bytes.proximity_pairing();
+        let key = EncryptionKey::new(bytes.fill(16).try_into().unwrap());
+        let Ok(mut status) = decode_airpods_data(&data) else {
+            continue;
+        };
+        refine_with_key(&mut status, &data, &key);
+        assert_sane(&status, seed);
+    }
+}
+
+#[test]
+fn aap_parser_never_panics() {
+    for seed in 0..CASES {
+        let mut bytes = Bytes(seed);
+        let mut data = bytes.any();
+        // Often enough a control notification to reach the value
+        if data.len() > 6 && seed % 2 == 0 {
+            data[..6].copy_from_slice(&[0x04, 0x00, 0x04, 0x00, 0x09, 0x00]);
+        }
+        let _ = podpower_core::aap::parse_listening_mode(&data);
+    }
+}
+
+#[test]
+fn listening_mode_command_reads_back_as_its_mode() {
+    use podpower_core::ListeningMode;
+    use podpower_core::aap::{listening_mode_packet, parse_listening_mode};
+    for mode in [
+        ListeningMode::Off,
+        ListeningMode::Anc,
+        ListeningMode::Transparency,
+        ListeningMode::Adaptive,
+    ] {
+        assert_eq!(
+            parse_listening_mode(&listening_mode_packet(mode)),
+            Some(mode)
+        );
+    }
+}
+
+#[cfg(feature = "samsung")]
+#[test]
+fn galaxy_buds_decoder_never_panics() {
+    for seed in 0..CASES {
+        let mut bytes = Bytes(seed);
+        let mut data = bytes.any();
+        // Often enough a Buds beacon to reach the battery bytes
+        if data.len() > 1 && seed % 2 == 0 {
+            data[0] = 0x42;
+            data[1] = 0x09;
+        }
+        if let Ok(status) = podpower_core::decode_galaxy_buds_data(&data) {
+            assert_sane(&status, seed);
+        }
+    }
+}
+
+#[cfg(feature = "fastpair")]
+#[test]
+fn fast_pair_decoder_never_panics() {
+    for seed in 0..CASES {
+        let data = Bytes(seed).any();
+        if let Ok(status) = podpower_core::decode_fast_pair_data(&data) {
+            assert_sane(&status, seed);
+        }
+    }
+}
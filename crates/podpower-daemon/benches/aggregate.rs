@@ -0,0 +1,104 @@
+//! `cargo bench -p podpower-daemon [FILTER]`: folding a busy scan's
+//! advertisements into one sighting per pair, as the daemon does every pass.
+
+use podpower_core::{AirPodsStatus, Component};
+use podpower_daemon::Sighting;
+use podpower_daemon::fingerprint::{Fingerprint, merge, same_pair};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const BATCH: u64 = 100;
+const MEASURE: Duration = Duration::from_secs(1);
+
+/// Time `f` for about [`MEASURE`] after a warm-up batch and print the mean
+/// per call, unless a command-line filter leaves it out
+fn bench(name: &str, mut f: impl FnMut()) {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    if filter.is_some_and(|filter| !name.contains(&filter)) {
+        return;
+    }
+    for _ in 0..BATCH {
+        f();
+    }
+    let start = Instant::now();
+    let mut iterations = 0;
+    while start.elapsed() < MEASURE {
+        for _ in 0..BATCH {
+            f();
+        }
+        iterations += BATCH;
+    }
+    let nanos = start.elapsed().as_nanos() as f64 / iterations as f64;
+    println!("{:<36} {:>12.1} ns/iter", name, nanos);
+}
+
+fn component(name: &str, battery: u8) -> Component {
+    Component {
+        name: name.to_string(),
+        battery,
+        charging: false,
+        precision: 10,
+        raw: None,
+    }
+}
+
+/// `count` advertisements from `pairs` pairs of the same model, each pair
+/// rotating through a few addresses, interleaved the way a scan sees them
+fn advertisements(pairs: usize, count: usize) -> Vec<Sighting> {
+    (0..count)
+        .map(|i| {
+            let pair = i % pairs;
+            // Levels far enough apart that pairs don't pass for each other
+            let level = 15 + (pair as u8 % 4) * 25;
+            let color = (pair / 4) as u8;
+            let status = AirPodsStatus::InEar {
+                model: "AirPods Pro".to_string(),
+                battery: Some(level),
+                components: vec![
+                    component("left", level),
+                    component("right", level),
+                    component("case", 100 - level),
+                ],
+                connection_state: None,
+                stored: Some(false),
+                in_ear: None,
+            };
+            Sighting {
+                fingerprint: Fingerprint {
+                    vendor: "apple".to_string(),
+                    model: status.model().to_string(),
+                    color: Some(color),
+                },
+                status,
+                address: format!("5E:90:1B:{:02X}:{:02X}:44", pair, i / pairs % 3),
+                rssi: Some(-40 - (i % 7) as i16),
+                connected: false,
+                confidence: None,
+                partial: false,
+                listening_mode: None,
+                name: None,
+                data: Vec::new(),
+                warnings: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+fn main() {
+    for (pairs, count) in [(1, 30), (5, 300), (20, 1_000)] {
+        let advertisements = advertisements(pairs, count);
+        bench(&format!("merge/{}_pairs_{}_adverts", pairs, count), || {
+            let mut sightings = Vec::new();
+            for sighting in &advertisements {
+                merge(&mut sightings, black_box(sighting.clone()));
+            }
+            black_box(sightings);
+        });
+    }
+
+    let advertisements = advertisements(2, 2);
+    let (a, b) = (&advertisements[0], &advertisements[1]);
+    bench("same_pair", || {
+        black_box(same_pair(black_box(a), black_box(b)));
+    });
+}
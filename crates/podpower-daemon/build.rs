@@ -0,0 +1,15 @@
+//! Generates the gRPC server of the `grpc` feature from `proto/`, with a
+//! bundled `protoc` so building needs no system install.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/podpower.proto");
+        // SAFETY: build scripts are single-threaded
+        unsafe { std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/podpower.proto"], &["proto"])?;
+    }
+    Ok(())
+}
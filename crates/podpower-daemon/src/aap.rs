@@ -0,0 +1,216 @@
+//! State the AirPods only share with the host they are connected to, read
+//! over an AAP channel, see [`podpower_core::aap`]. Linux only: the channel
+//! is a raw L2CAP socket to the AirPods' public address, which BlueZ lets
+//! any process open alongside its own connection.
+
+use crate::scan::find_connected;
+use btleplug::api::{BDAddr, Central, Manager as _};
+use btleplug::platform::Manager;
+use podpower_core::ListeningMode;
+use podpower_core::aap::{self, HANDSHAKE, REQUEST_NOTIFICATIONS};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the AirPods to accept the channel, and then for the
+/// packet asked for
+pub const TIMEOUT: Duration = Duration::from_secs(2);
+
+const BTPROTO_L2CAP: libc::c_int = 0;
+/// Address type of classic (BR/EDR) connections, which AAP runs over
+const BDADDR_BREDR: u8 = 0;
+/// Larger than any packet the AirPods send
+const MAX_PACKET: usize = 1024;
+
+/// `struct sockaddr_l2` from BlueZ's `l2cap.h`
+#[repr(C)]
+struct SockaddrL2 {
+    l2_family: libc::sa_family_t,
+    /// Little-endian
+    l2_psm: u16,
+    /// Least significant byte first
+    l2_bdaddr: [u8; 6],
+    l2_cid: u16,
+    l2_bdaddr_type: u8,
+}
+
+/// An open AAP channel, past the handshake and subscribed to notifications
+pub struct Channel {
+    socket: OwnedFd,
+}
+
+impl Channel {
+    /// Connect to the AirPods at `address`, the address they are connected
+    /// to this host with rather than the one they advertise from. Blocks for
+    /// up to [`TIMEOUT`].
+    pub fn connect(address: BDAddr) -> io::Result<Self> {
+        // SAFETY: plain socket(2), the descriptor is owned right after
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_BLUETOOTH,
+                libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+                BTPROTO_L2CAP,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a fresh descriptor nothing else owns
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+        // L2CAP sockets time out connect(2) by the send timeout
+        set_timeout(&socket, libc::SO_SNDTIMEO)?;
+        set_timeout(&socket, libc::SO_RCVTIMEO)?;
+
+        let mut bdaddr = address.into_inner();
+        bdaddr.reverse();
+        let sockaddr = SockaddrL2 {
+            l2_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+            l2_psm: aap::PSM.to_le(),
+            l2_bdaddr: bdaddr,
+            l2_cid: 0,
+            l2_bdaddr_type: BDADDR_BREDR,
+        };
+        // SAFETY: `sockaddr` is a valid `sockaddr_l2` of the given length
+        let connected = unsafe {
+            libc::connect(
+                socket.as_raw_fd(),
+                (&sockaddr as *const SockaddrL2).cast(),
+                size_of::<SockaddrL2>() as libc::socklen_t,
+            )
+        };
+        if connected < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let channel = Self { socket };
+        channel.send(&HANDSHAKE)?;
+        channel.send(&REQUEST_NOTIFICATIONS)?;
+        Ok(channel)
+    }
+
+    pub fn send(&self, packet: &[u8]) -> io::Result<()> {
+        // SAFETY: `packet` is valid for its length
+        let sent = unsafe {
+            libc::send(
+                self.socket.as_raw_fd(),
+                packet.as_ptr().cast(),
+                packet.len(),
+                libc::MSG_NOSIGNAL,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// The next packet, failing with [`io::ErrorKind::WouldBlock`] after
+    /// [`TIMEOUT`]
+    fn receive(&self) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0; MAX_PACKET];
+        // SAFETY: `buffer` is valid for its length
+        let received = unsafe {
+            libc::recv(
+                self.socket.as_raw_fd(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                0,
+            )
+        };
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if received == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buffer.truncate(received as usize);
+        Ok(buffer)
+    }
+
+    /// The first packet `parse` makes something of, or `None` when none
+    /// arrives within [`TIMEOUT`]
+    pub fn wait_for<T>(&self, parse: impl Fn(&[u8]) -> Option<T>) -> io::Result<Option<T>> {
+        let deadline = Instant::now() + TIMEOUT;
+        while Instant::now() < deadline {
+            match self.receive() {
+                Ok(packet) => {
+                    if let Some(value) = parse(&packet) {
+                        return Ok(Some(value));
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn set_timeout(socket: &OwnedFd, option: libc::c_int) -> io::Result<()> {
+    let timeout = libc::timeval {
+        tv_sec: TIMEOUT.as_secs() as libc::time_t,
+        tv_usec: TIMEOUT.subsec_micros() as libc::suseconds_t,
+    };
+    // SAFETY: `timeout` is a valid `timeval` of the given length
+    let set = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            option,
+            (&timeout as *const libc::timeval).cast(),
+            size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    };
+    if set < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Name and address of the connected AirPods to open a channel to: the first
+/// connected device whose name contains `model`, or without one, says
+/// AirPods or Beats
+pub async fn connected_airpods(
+    model: Option<&str>,
+) -> Result<Option<(String, BDAddr)>, btleplug::Error> {
+    let manager = Manager::new().await?;
+    let Some(adapter) = manager.adapters().await?.into_iter().next() else {
+        return Ok(None);
+    };
+    let found = find_connected(adapter.peripherals().await?, |name| match model {
+        Some(model) => name.contains(model),
+        None => name.contains("AirPods") || name.contains("Beats"),
+    })
+    .await?;
+    Ok(found.map(|(name, properties)| (name, properties.address)))
+}
+
+/// The noise control the AirPods at `address` report when asked, `None`
+/// for models without one
+pub async fn read_listening_mode(address: BDAddr) -> io::Result<Option<ListeningMode>> {
+    tokio::task::spawn_blocking(move || {
+        Channel::connect(address)?.wait_for(aap::parse_listening_mode)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Switch the AirPods at `address` to `mode`, failing unless they confirm it
+/// within [`TIMEOUT`]
+pub async fn set_listening_mode(address: BDAddr, mode: ListeningMode) -> io::Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let channel = Channel::connect(address)?;
+        channel.send(&aap::listening_mode_packet(mode))?;
+        let confirmed = channel
+            .wait_for(|packet| aap::parse_listening_mode(packet).filter(|now| *now == mode))?;
+        match confirmed {
+            Some(_) => Ok(()),
+            None => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("the AirPods didn't switch to {}", mode),
+            )),
+        }
+    })
+    .await
+    .map_err(io::Error::other)?
+}
@@ -0,0 +1,55 @@
+//! Per-device offsets for batteries that consistently misreport, e.g. aged
+//! pods that read 10% high.
+
+use crate::Sighting;
+use serde::Deserialize;
+
+/// A `[[calibration]]` entry of the config file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Calibration {
+    /// The name the device was paired under, see [`Sighting::name`]
+    pub name: Option<String>,
+    /// [`podpower_core::AirPodsStatus::model`], ignoring case
+    pub model: Option<String>,
+    /// Percentage points added to each component's level, negative for
+    /// components that read high
+    pub left: i8,
+    pub right: i8,
+    pub case: i8,
+    pub headphones: i8,
+}
+
+impl Calibration {
+    /// Whether the entry is for `sighting`. One without a name or model is
+    /// for every device.
+    pub fn matches(&self, sighting: &Sighting) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|name| sighting.name.as_ref() == Some(name))
+            && self
+                .model
+                .as_ref()
+                .is_none_or(|model| model.eq_ignore_ascii_case(sighting.status.model()))
+    }
+
+    fn offset(&self, component: &str) -> i8 {
+        match component {
+            "left" => self.left,
+            "right" => self.right,
+            "case" => self.case,
+            "headphones" => self.headphones,
+            _ => 0,
+        }
+    }
+}
+
+/// `sighting` with the offsets of the first of `calibrations` that is for it
+pub fn calibrate(mut sighting: Sighting, calibrations: &[Calibration]) -> Sighting {
+    if let Some(calibration) = calibrations.iter().find(|c| c.matches(&sighting)) {
+        sighting.status = sighting
+            .status
+            .calibrated(|component| calibration.offset(component));
+    }
+    sighting
+}
@@ -0,0 +1,587 @@
+//! Local query socket for talking to a running daemon.
+//!
+//! This is a Unix domain socket on Unix and a named pipe on Windows. The
+//! protocol is line based: the client writes a command (`status` for the
+//! latest reading, `daemon` for the daemon's own [`DaemonStatus`], `pause` or
+//! `resume` to switch scanning off and on, `snooze SECS` to hold back
+//! notifications, `fleet` for every pair a `--fleet` daemon heard from) and
+//! the daemon answers with a single JSON [`Response`]
+//! line. The socket itself requires the `json` feature; the answers' types
+//! are always there for the other servers to share.
+
+#[cfg(feature = "json")]
+use crate::events::Snooze;
+use crate::fleet::{FleetDevice, Retention};
+#[cfg(feature = "json")]
+use crate::monitor::ScanSwitch;
+use crate::monitor::{Diagnostics, PowerProfile, Reading};
+#[cfg(feature = "json")]
+use crate::scan::SCAN_TIMEOUT_SECS;
+use podpower_core::payload::LEGACY_SCHEMA_VERSION;
+use podpower_core::{AirPodsStatus, ListeningMode};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "json")]
+use std::io;
+#[cfg(feature = "json")]
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "json")]
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "json")]
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+#[cfg(feature = "json")]
+use tokio::sync::watch;
+#[cfg(feature = "json")]
+use tokio::time::timeout;
+
+#[cfg(feature = "json")]
+pub use imp::{ControlListener, bind};
+
+pub const SOCKET_NAME: &str = "podpower.sock";
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\podpower";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Status {
+        /// JSON schema version of `status`, 0 from daemons that predate it
+        #[serde(default)]
+        schema_version: u32,
+        /// `null` when the latest scan did not find AirPods
+        status: Option<AirPodsStatus>,
+        /// Unix timestamp (milliseconds) of the latest scan
+        observed_at: u64,
+        /// See [`crate::Sighting::name`]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// See [`crate::Sighting::confidence`]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        confidence: Option<u8>,
+        /// See [`crate::Sighting::partial`]
+        #[serde(default)]
+        partial: bool,
+        /// See [`crate::Sighting::listening_mode`]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        listening_mode: Option<ListeningMode>,
+        /// See [`crate::Sighting::data`]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        data: Vec<u8>,
+        /// See [`crate::Sighting::warnings`]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    },
+    Daemon(DaemonStatus),
+    /// Answer to `pause` and `resume`
+    Scanning {
+        paused: bool,
+        /// Whether the command changed anything
+        changed: bool,
+    },
+    /// Answer to `fleet`
+    Fleet(FleetStatus),
+    /// Answer to `snooze`
+    Snoozed {
+        /// Unix timestamp (milliseconds) the snooze ends at, `None` when it
+        /// was ended
+        until: Option<u64>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl From<&Reading> for Response {
+    fn from(reading: &Reading) -> Self {
+        Response::Status {
+            schema_version: LEGACY_SCHEMA_VERSION,
+            status: reading.status.clone(),
+            observed_at: millis(reading.observed_at),
+            name: reading.name.clone(),
+            confidence: reading.confidence,
+            partial: reading.partial,
+            listening_mode: reading.listening_mode,
+            data: reading.data.clone(),
+            warnings: reading.warnings.clone(),
+        }
+    }
+}
+
+/// Answer to `daemon`: what the daemon is doing, for `podpower daemon status`.
+/// Timestamps are Unix milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub version: String,
+    pub uptime_secs: u64,
+    /// `None` when no adapter was found
+    pub adapter: Option<String>,
+    pub scan: ScanMode,
+    /// Scanning was paused with `podpower daemon pause`
+    #[serde(default)]
+    pub paused: bool,
+    /// When notifications snoozed with `podpower snooze` resume
+    #[serde(default)]
+    pub snoozed_until: Option<u64>,
+    pub last_scan_at: Option<u64>,
+    /// When a scan last found AirPods
+    #[serde(default)]
+    pub last_sighting_at: Option<u64>,
+    /// Why the latest scan failed
+    pub last_error: Option<String>,
+    /// Devices heard from in the last few minutes, most recent first
+    pub devices: Vec<DeviceStatus>,
+    pub sinks: Vec<SinkStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanMode {
+    /// `--interval`, which the profile scales
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub profile: PowerProfile,
+    /// The wait after the latest scan, shorter while the battery is low or
+    /// charging just changed
+    #[serde(default)]
+    pub wait_secs: u64,
+    /// Scanning less often because no AirPods were found in a while
+    #[serde(default)]
+    pub backing_off: bool,
+    /// Not scanning until a device connects to this host
+    #[serde(default)]
+    pub sleeping: bool,
+    /// Whether connected devices' GATT Battery Service is read when no
+    /// advertisement matched
+    pub gatt_fallback: bool,
+}
+
+/// Every pair a daemon running with `--fleet` heard from, see
+/// [`crate::fleet`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetStatus {
+    /// Whether the daemon keeps track of a fleet at all
+    pub enabled: bool,
+    /// In the order they were first seen
+    pub devices: Vec<FleetDeviceStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetDeviceStatus {
+    /// See [`FleetDevice::name`]
+    pub name: Option<String>,
+    pub vendor: String,
+    pub model: String,
+    /// See [`crate::fingerprint::Fingerprint::color`], what `[[devices]]`
+    /// entries can name pairs of the same model by
+    pub color: Option<u8>,
+    pub status: AirPodsStatus,
+    pub rssi: Option<i16>,
+    /// Connected to this host as of the latest scan
+    pub connected: bool,
+    pub last_seen_at: u64,
+    /// Not heard from for a while, so the levels are probably out of date,
+    /// see [`FleetDevice::is_stale`]
+    #[serde(default)]
+    pub stale: bool,
+}
+
+impl FleetStatus {
+    pub fn new(diagnostics: &Diagnostics) -> Self {
+        let Some(fleet) = &diagnostics.fleet else {
+            return Self {
+                enabled: false,
+                devices: Vec::new(),
+            };
+        };
+        let now = SystemTime::now();
+        Self {
+            enabled: true,
+            devices: fleet
+                .devices
+                .iter()
+                .map(|device| FleetDeviceStatus::new(device, &fleet.retention, now))
+                .collect(),
+        }
+    }
+}
+
+impl FleetDeviceStatus {
+    fn new(device: &FleetDevice, retention: &Retention, now: SystemTime) -> Self {
+        Self {
+            name: device.name.clone(),
+            vendor: device.fingerprint.vendor.clone(),
+            model: device.fingerprint.model.clone(),
+            color: device.fingerprint.color,
+            status: device.status.clone(),
+            rssi: device.rssi,
+            connected: device.connected,
+            last_seen_at: millis(device.last_seen),
+            stale: device.is_stale(retention, now),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceStatus {
+    pub address: String,
+    pub vendor: String,
+    /// `None` when the advertisement was rejected
+    pub model: Option<String>,
+    pub rssi: Option<i16>,
+    pub connected: bool,
+    pub last_seen_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SinkStatus {
+    pub name: String,
+    /// Address, URL or path the sink delivers to
+    pub target: Option<String>,
+    pub running_since: u64,
+    /// The latest failed delivery, cleared by the next successful one
+    pub last_error: Option<String>,
+    pub last_error_at: Option<u64>,
+}
+
+/// Delivery failures a sink reports while it keeps running; a sink that
+/// fails for good stops the daemon instead
+#[derive(Debug, Clone, Default)]
+pub struct SinkHealth(Arc<Mutex<Option<(SystemTime, String)>>>);
+
+impl SinkHealth {
+    pub fn failed(&self, error: impl std::fmt::Display) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some((SystemTime::now(), error.to_string()));
+    }
+
+    pub fn succeeded(&self) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    fn last_error(&self) -> Option<(SystemTime, String)> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// A sink the daemon runs, as listed by [`DaemonStatus::sinks`]
+#[derive(Debug, Clone)]
+pub struct RunningSink {
+    pub name: String,
+    pub target: Option<String>,
+    pub started_at: SystemTime,
+    pub health: SinkHealth,
+}
+
+impl From<&RunningSink> for SinkStatus {
+    fn from(sink: &RunningSink) -> Self {
+        let last_error = sink.health.last_error();
+        SinkStatus {
+            name: sink.name.clone(),
+            target: sink.target.clone(),
+            running_since: millis(sink.started_at),
+            last_error_at: last_error.as_ref().map(|(at, _)| millis(*at)),
+            last_error: last_error.map(|(_, error)| error),
+        }
+    }
+}
+
+/// What the query socket reports about the daemon itself, the switch
+/// `pause` and `resume` flip, and the [`Snooze`] `snooze` sets
+#[cfg(feature = "json")]
+#[derive(Debug, Clone)]
+pub struct Introspection {
+    pub diagnostics: watch::Receiver<Diagnostics>,
+    pub sinks: watch::Receiver<Vec<RunningSink>>,
+    pub switch: ScanSwitch,
+    pub snooze: Snooze,
+}
+
+#[cfg(feature = "json")]
+impl Introspection {
+    fn status(&self) -> DaemonStatus {
+        let diagnostics = self.diagnostics.borrow().clone();
+        let mut devices: Vec<DeviceStatus> = diagnostics
+            .devices
+            .iter()
+            .map(|device| DeviceStatus {
+                address: device.address.clone(),
+                vendor: device.vendor.to_string(),
+                model: device.model.clone(),
+                rssi: device.rssi,
+                connected: device.connected,
+                last_seen_at: millis(device.last_seen),
+            })
+            .collect();
+        devices.sort_by_key(|device| std::cmp::Reverse(device.last_seen_at));
+        DaemonStatus {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_secs: diagnostics
+                .started_at
+                .elapsed()
+                .unwrap_or_default()
+                .as_secs(),
+            adapter: diagnostics.adapter,
+            scan: ScanMode {
+                interval_secs: diagnostics.interval.as_secs(),
+                profile: diagnostics.profile,
+                wait_secs: diagnostics.next_wait.as_secs(),
+                backing_off: diagnostics.backing_off,
+                sleeping: diagnostics.sleeping,
+                gatt_fallback: diagnostics.gatt_fallback,
+            },
+            paused: self.switch.is_paused(),
+            snoozed_until: self.snooze.until().map(millis),
+            last_scan_at: diagnostics.last_scan.map(millis),
+            last_sighting_at: diagnostics.last_sighting.map(millis),
+            last_error: diagnostics.last_error,
+            devices,
+            sinks: self.sinks.borrow().iter().map(SinkStatus::from).collect(),
+        }
+    }
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// `$XDG_RUNTIME_DIR/podpower.sock` (falling back to the temp directory) on
+/// Unix, `\\.\pipe\podpower` on Windows
+#[cfg(feature = "json")]
+pub fn default_socket_path() -> PathBuf {
+    #[cfg(unix)]
+    return std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(SOCKET_NAME);
+    #[cfg(windows)]
+    return PathBuf::from(PIPE_NAME);
+}
+
+/// Answer queries on `listener` until it fails.
+#[cfg(feature = "json")]
+pub async fn serve_control(
+    mut listener: ControlListener,
+    readings: watch::Receiver<Option<Reading>>,
+    introspection: Introspection,
+) -> io::Result<()> {
+    loop {
+        let stream = listener.accept().await?;
+        let readings = readings.clone();
+        let introspection = introspection.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, readings, introspection).await {
+                tracing::warn!("Query socket client error: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(feature = "json")]
+async fn handle<S>(
+    stream: S,
+    mut readings: watch::Receiver<Option<Reading>>,
+    introspection: Introspection,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        let response = match line {
+            "status" => status(&mut readings).await,
+            "daemon" => Response::Daemon(introspection.status()),
+            "fleet" => Response::Fleet(FleetStatus::new(&introspection.diagnostics.borrow())),
+            "pause" => Response::Scanning {
+                paused: true,
+                changed: introspection.switch.pause(),
+            },
+            "resume" => Response::Scanning {
+                paused: false,
+                changed: introspection.switch.resume(),
+            },
+            _ if line.starts_with("snooze ") => snooze(&introspection.snooze, &line[7..]),
+            other => Response::Error {
+                message: format!("unknown command: {}", other),
+            },
+        };
+        let mut json = serde_json::to_string(&response).map_err(io::Error::other)?;
+        json.push('\n');
+        writer.write_all(json.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// `snooze SECS`, 0 to end the snooze
+#[cfg(feature = "json")]
+fn snooze(snooze: &Snooze, secs: &str) -> Response {
+    let Ok(secs) = secs.trim().parse() else {
+        return Response::Error {
+            message: format!("invalid snooze duration: {}", secs),
+        };
+    };
+    match snooze.snooze(Duration::from_secs(secs)) {
+        Ok(until) => Response::Snoozed {
+            until: until.map(millis),
+        },
+        Err(e) => Response::Error {
+            message: e.to_string(),
+        },
+    }
+}
+
+#[cfg(feature = "json")]
+async fn status(readings: &mut watch::Receiver<Option<Reading>>) -> Response {
+    // A socket-activated daemon receives its first query before any scan has
+    // finished, so give the initial scan a chance to complete.
+    let first_scan = Duration::from_secs(SCAN_TIMEOUT_SECS + 2);
+    match timeout(first_scan, readings.wait_for(Option::is_some)).await {
+        Ok(Ok(reading)) => reading.as_ref().expect("waited for a reading").into(),
+        _ => Response::Error {
+            message: "No scan has completed yet".into(),
+        },
+    }
+}
+
+/// Send `command` to the daemon listening on `path` and return its answer.
+#[cfg(feature = "json")]
+pub async fn request(path: &Path, command: &str) -> io::Result<Response> {
+    let stream = imp::connect(path).await?;
+    let (reader, mut writer) = tokio::io::split(stream);
+    writer
+        .write_all(format!("{}\n", command).as_bytes())
+        .await?;
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+    serde_json::from_str(&line).map_err(io::Error::other)
+}
+
+#[cfg(all(unix, feature = "json"))]
+mod imp {
+    use std::io;
+    use std::os::fd::FromRawFd;
+    use std::path::Path;
+    use tokio::net::{UnixListener, UnixStream};
+
+    /// First file descriptor passed by systemd socket activation
+    const SD_LISTEN_FDS_START: i32 = 3;
+
+    pub struct ControlListener(UnixListener);
+
+    impl ControlListener {
+        pub(super) async fn accept(&mut self) -> io::Result<UnixStream> {
+            Ok(self.0.accept().await?.0)
+        }
+    }
+
+    /// Listen on `path`, or adopt the socket passed by systemd when the
+    /// daemon was started through socket activation.
+    pub async fn bind(path: &Path) -> io::Result<ControlListener> {
+        if let Some(listener) = activated_listener()? {
+            return Ok(ControlListener(listener));
+        }
+
+        if path.exists() {
+            if UnixStream::connect(path).await.is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("another daemon is already listening on {}", path.display()),
+                ));
+            }
+            // Left behind by a daemon that didn't shut down cleanly
+            std::fs::remove_file(path)?;
+        }
+        UnixListener::bind(path).map(ControlListener)
+    }
+
+    pub(super) async fn connect(path: &Path) -> io::Result<UnixStream> {
+        UnixStream::connect(path).await
+    }
+
+    /// The listening socket handed over by systemd (`LISTEN_PID`/`LISTEN_FDS`)
+    fn activated_listener() -> io::Result<Option<UnixListener>> {
+        let for_us = std::env::var("LISTEN_PID")
+            .ok()
+            .and_then(|pid| pid.parse::<u32>().ok())
+            .is_some_and(|pid| pid == std::process::id());
+        let fds = std::env::var("LISTEN_FDS")
+            .ok()
+            .and_then(|n| n.parse::<i32>().ok())
+            .unwrap_or(0);
+        if !for_us || fds < 1 {
+            return Ok(None);
+        }
+
+        // SAFETY: systemd guarantees fd 3 is an open listening socket owned by
+        // this process when LISTEN_PID matches and LISTEN_FDS >= 1.
+        let listener =
+            unsafe { std::os::unix::net::UnixListener::from_raw_fd(SD_LISTEN_FDS_START) };
+        listener.set_nonblocking(true)?;
+        UnixListener::from_std(listener).map(Some)
+    }
+}
+
+#[cfg(all(windows, feature = "json"))]
+mod imp {
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use tokio::net::windows::named_pipe::{
+        ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions,
+    };
+    use tokio::time::sleep;
+
+    /// All pipe instances are busy serving other clients
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    /// A named pipe server; each client gets its own pipe instance, and the
+    /// next one is created before the current client is handed out so there
+    /// is always an instance waiting for connections.
+    pub struct ControlListener {
+        name: PathBuf,
+        next: NamedPipeServer,
+    }
+
+    impl ControlListener {
+        pub(super) async fn accept(&mut self) -> io::Result<NamedPipeServer> {
+            self.next.connect().await?;
+            let next = ServerOptions::new().create(&self.name)?;
+            Ok(std::mem::replace(&mut self.next, next))
+        }
+    }
+
+    pub async fn bind(path: &Path) -> io::Result<ControlListener> {
+        let next = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(path)
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!(
+                        "could not create {} ({}); is another daemon running?",
+                        path.display(),
+                        e
+                    ),
+                )
+            })?;
+        Ok(ControlListener {
+            name: path.to_path_buf(),
+            next,
+        })
+    }
+
+    pub(super) async fn connect(path: &Path) -> io::Result<NamedPipeClient> {
+        loop {
+            match ClientOptions::new().open(path) {
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                    sleep(Duration::from_millis(50)).await
+                }
+                result => return result,
+            }
+        }
+    }
+}
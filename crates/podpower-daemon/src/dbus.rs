@@ -0,0 +1,138 @@
+use crate::monitor::Reading;
+use podpower_core::{AirPodsStatus, Component};
+use tokio::sync::watch;
+use zbus::object_server::{InterfaceRef, SignalEmitter};
+use zbus::{connection, interface};
+
+pub const BUS_NAME: &str = "org.podpower.Battery";
+pub const OBJECT_PATH: &str = "/org/podpower/Battery";
+
+/// Battery levels are exposed as `i` properties, with -1 meaning the
+/// component is not currently reported (e.g. a pod that is out of range).
+const NOT_AVAILABLE: i32 = -1;
+
+#[derive(Default)]
+struct Battery {
+    status: Option<AirPodsStatus>,
+}
+
+impl Battery {
+    fn component(&self, name: &str) -> Option<&Component> {
+        self.status
+            .as_ref()?
+            .components()
+            .iter()
+            .find(|c| c.name == name)
+    }
+
+    fn level(&self, name: &str) -> i32 {
+        self.component(name)
+            .map_or(NOT_AVAILABLE, |c| i32::from(c.battery))
+    }
+
+    fn charging(&self, name: &str) -> bool {
+        self.component(name).is_some_and(|c| c.charging)
+    }
+}
+
+#[interface(name = "org.podpower.Battery")]
+impl Battery {
+    /// Whether AirPods were found by the most recent scan
+    #[zbus(property)]
+    fn present(&self) -> bool {
+        self.status.is_some()
+    }
+
+    #[zbus(property)]
+    fn model(&self) -> String {
+        self.status
+            .as_ref()
+            .map(|s| s.model().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Top-level battery level (lowest earbud for in-ear devices)
+    #[zbus(property)]
+    fn battery(&self) -> i32 {
+        self.status
+            .as_ref()
+            .and_then(AirPodsStatus::battery)
+            .map_or(NOT_AVAILABLE, i32::from)
+    }
+
+    #[zbus(property)]
+    fn left(&self) -> i32 {
+        self.level("left")
+    }
+
+    #[zbus(property)]
+    fn right(&self) -> i32 {
+        self.level("right")
+    }
+
+    #[zbus(property)]
+    fn case(&self) -> i32 {
+        self.level("case")
+    }
+
+    #[zbus(property)]
+    fn headphones(&self) -> i32 {
+        self.level("headphones")
+    }
+
+    #[zbus(property)]
+    fn left_charging(&self) -> bool {
+        self.charging("left")
+    }
+
+    #[zbus(property)]
+    fn right_charging(&self) -> bool {
+        self.charging("right")
+    }
+
+    #[zbus(property)]
+    fn case_charging(&self) -> bool {
+        self.charging("case")
+    }
+
+    #[zbus(property)]
+    fn headphones_charging(&self) -> bool {
+        self.charging("headphones")
+    }
+}
+
+/// Register `org.podpower.Battery` on the session bus and keep its properties
+/// in sync with `readings`, emitting `PropertiesChanged` on every change.
+pub async fn serve_dbus(mut readings: watch::Receiver<Option<Reading>>) -> zbus::Result<()> {
+    let conn = connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Battery::default())?
+        .build()
+        .await?;
+    let iface: InterfaceRef<Battery> = conn.object_server().interface(OBJECT_PATH).await?;
+
+    while readings.changed().await.is_ok() {
+        let status = readings
+            .borrow_and_update()
+            .as_ref()
+            .and_then(|r| r.status.clone());
+        let mut battery = iface.get_mut().await;
+        battery.status = status;
+        emit_changes(&battery, iface.signal_emitter()).await?;
+    }
+    Ok(())
+}
+
+async fn emit_changes(battery: &Battery, emitter: &SignalEmitter<'_>) -> zbus::Result<()> {
+    battery.present_changed(emitter).await?;
+    battery.model_changed(emitter).await?;
+    battery.battery_changed(emitter).await?;
+    battery.left_changed(emitter).await?;
+    battery.right_changed(emitter).await?;
+    battery.case_changed(emitter).await?;
+    battery.headphones_changed(emitter).await?;
+    battery.left_charging_changed(emitter).await?;
+    battery.right_charging_changed(emitter).await?;
+    battery.case_charging_changed(emitter).await?;
+    battery.headphones_charging_changed(emitter).await
+}
@@ -0,0 +1,81 @@
+//! Notifications on the desktop of this host: the freedesktop notification
+//! service on Linux and the BSDs, Notification Center on macOS and toasts on
+//! Windows.
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Show a notification, without waiting for it to be dismissed
+#[cfg(all(unix, not(target_os = "macos")))]
+pub async fn show(title: &str, body: &str) -> Result<(), Error> {
+    use std::collections::HashMap;
+    use zbus::zvariant::Value;
+
+    let connection = zbus::Connection::session().await?;
+    connection
+        .call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &(
+                "podpower",
+                0u32,
+                "battery-low",
+                title,
+                body,
+                Vec::<&str>::new(),
+                HashMap::<&str, Value>::new(),
+                // The server's default timeout
+                -1i32,
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Show a notification, without waiting for it to be dismissed. Through
+/// `osascript`, so it appears as Script Editor's: UNUserNotificationCenter
+/// only serves signed app bundles.
+#[cfg(target_os = "macos")]
+pub async fn show(title: &str, body: &str) -> Result<(), Error> {
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    );
+    let output = tokio::process::Command::new("osascript")
+        .args(["-e", &script])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(format!(
+            "osascript failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// `"..."` with backslashes and double quotes escaped
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Show a toast, without waiting for it to be dismissed. Toasts need the id
+/// of an installed app to appear under, so they appear as PowerShell's.
+#[cfg(windows)]
+pub async fn show(title: &str, body: &str) -> Result<(), Error> {
+    use tauri_winrt_notification::Toast;
+
+    let (title, body) = (title.to_string(), body.to_string());
+    tokio::task::spawn_blocking(move || {
+        Toast::new(Toast::POWERSHELL_APP_ID)
+            .title(&title)
+            .text1(&body)
+            .show()
+    })
+    .await??;
+    Ok(())
+}
@@ -0,0 +1,429 @@
+//! Transitions between successive statuses, for hooks and notifications.
+
+use crate::fingerprint::Fingerprint;
+use podpower_core::AirPodsStatus;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Battery level at or below which a component counts as low by default
+pub const DEFAULT_LOW_BATTERY: u8 = 20;
+/// A fully charged component has to drop below this before it can report
+/// `Charged` again, so 100% <-> 99% flicker on the charger stays quiet
+const CHARGED_REARM_BELOW: u8 = 90;
+/// How many times faster than usual a component has to drain to report
+/// `FastDrain` by default
+pub const DEFAULT_FAST_DRAIN: f64 = 2.0;
+/// Percentage points a discharge has to cover before its rate means
+/// anything, as levels only come in 10% steps
+const MIN_DRAIN_POINTS: u8 = 30;
+/// Weight of the latest discharge in the usual drain rate
+const BASELINE_WEIGHT: f64 = 0.3;
+/// Discharges the usual drain rate has to be made of before `FastDrain`
+/// compares against it, so one odd discharge doesn't set the bar
+pub const MIN_BASELINE_DISCHARGES: u32 = 2;
+/// Longer gaps between readings mean the AirPods were away, which starts a
+/// new discharge
+const MAX_DRAIN_GAP: Duration = Duration::from_secs(2 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// AirPods were found after being absent (or on the first scan)
+    Connected,
+    /// AirPods were found by the previous scan but not by this one
+    Disconnected,
+    /// A component that isn't charging dropped to the low-battery level
+    Low { component: String },
+    /// A component reached 100% on the charger
+    Charged { component: String },
+    /// A component drains much faster than it usually does, rates in
+    /// percent per hour
+    FastDrain {
+        component: String,
+        rate: u32,
+        usual: u32,
+    },
+    /// An earbud went into an ear, or headphones onto a head
+    Worn { component: String },
+    /// An earbud came out of an ear, or headphones off a head
+    Removed { component: String },
+}
+
+impl Event {
+    /// Name used in hook environments and logs, e.g. `low`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Connected => "connected",
+            Event::Disconnected => "disconnected",
+            Event::Low { .. } => "low",
+            Event::Charged { .. } => "charged",
+            Event::FastDrain { .. } => "fast_drain",
+            Event::Worn { .. } => "worn",
+            Event::Removed { .. } => "removed",
+        }
+    }
+
+    pub fn component(&self) -> Option<&str> {
+        match self {
+            Event::Low { component }
+            | Event::Charged { component }
+            | Event::FastDrain { component, .. }
+            | Event::Worn { component }
+            | Event::Removed { component } => Some(component),
+            Event::Connected | Event::Disconnected => None,
+        }
+    }
+}
+
+/// Turns a sequence of statuses into [`Event`]s. Each component of each
+/// pair, told apart by their [`Fingerprint`], reports `Low` once, and again
+/// only after it has charged or risen above the level. `Charged` works the same way, for components that are charging or
+/// were at the previous status, so AirPods found already full and off the
+/// charger don't report it.
+///
+/// `FastDrain` compares the current discharge of each component of each
+/// pair with its usual drain rate: a
+/// moving average over the earlier discharges, starting from the
+/// [`Baselines`] of the model, e.g. learned from the history. It stays quiet
+/// until the usual rate is made of [`MIN_BASELINE_DISCHARGES`], which
+/// without a history means watching that many discharges after every
+/// start. It reports once per discharge.
+///
+/// `Worn` and `Removed` report changes between successive statuses of a
+/// device that says what is worn, so neither fires on the first status
+/// after `Connected`.
+#[derive(Debug)]
+pub struct EventTracker {
+    low_battery: u8,
+    quiet_when_stored: bool,
+    once_per_discharge: bool,
+    fast_drain: f64,
+    present: bool,
+    /// What the previous status said was worn
+    worn: Option<Vec<&'static str>>,
+    /// These are all by pair and component
+    low: HashSet<(Fingerprint, String)>,
+    charged: HashSet<(Fingerprint, String)>,
+    /// Components that were charging at the previous status of their pair
+    charging: HashSet<(Fingerprint, String)>,
+    drains: HashMap<(Fingerprint, String), Drain>,
+    baselines: Baselines,
+}
+
+/// Usual drain rates by model and component, to start the pairs of the
+/// model from instead of from nothing, see [`EventTracker::with_baselines`]
+#[derive(Debug, Clone, Default)]
+pub struct Baselines(HashMap<(String, String), Drain>);
+
+impl Baselines {
+    /// Learn from the `level` of `component` of a pair of `model`, recorded
+    /// at `at`. Levels have to come oldest first.
+    pub fn record(
+        &mut self,
+        model: &str,
+        component: &str,
+        level: u8,
+        charging: bool,
+        at: SystemTime,
+    ) {
+        let drain = self
+            .0
+            .entry((model.to_string(), component.to_string()))
+            .or_default();
+        if charging {
+            drain.finish();
+        } else {
+            drain.update(level, at, 0.0);
+        }
+    }
+
+    /// The usual drain rate of `component` of `model` in percent per hour,
+    /// counting the discharge the last level was part of, and how many
+    /// discharges it is made of
+    pub fn usual(&self, model: &str, component: &str) -> Option<(f64, u32)> {
+        let mut drain = self
+            .0
+            .get(&(model.to_string(), component.to_string()))?
+            .clone();
+        drain.finish();
+        Some((drain.usual?, drain.discharges))
+    }
+}
+
+/// Drain history of one component
+#[derive(Debug, Clone, Default)]
+struct Drain {
+    current: Option<Discharge>,
+    /// Usual rate in percent per hour
+    usual: Option<f64>,
+    /// Discharges `usual` is made of
+    discharges: u32,
+}
+
+/// A stretch of readings with the level going down and not charging
+#[derive(Debug, Clone)]
+struct Discharge {
+    start: (u8, SystemTime),
+    last: (u8, SystemTime),
+    reported: bool,
+}
+
+impl Discharge {
+    fn new(level: u8, at: SystemTime) -> Self {
+        Self {
+            start: (level, at),
+            last: (level, at),
+            reported: false,
+        }
+    }
+
+    /// Percent per hour, once the discharge covered enough of the battery
+    fn rate(&self) -> Option<f64> {
+        let points = self.start.0.checked_sub(self.last.0)?;
+        let hours = self.last.1.duration_since(self.start.1).ok()?.as_secs_f64() / 3600.0;
+        (points >= MIN_DRAIN_POINTS && hours > 0.0).then(|| f64::from(points) / hours)
+    }
+}
+
+impl Drain {
+    /// Fold the current discharge into the usual rate
+    fn finish(&mut self) {
+        let Some(rate) = self.current.take().and_then(|discharge| discharge.rate()) else {
+            return;
+        };
+        self.usual = Some(match self.usual {
+            Some(usual) => usual * (1.0 - BASELINE_WEIGHT) + rate * BASELINE_WEIGHT,
+            None => rate,
+        });
+        self.discharges += 1;
+    }
+
+    /// Track `level`, returning the current and usual rate when it is at
+    /// least `factor` times the usual one for the first time this discharge
+    fn update(&mut self, level: u8, at: SystemTime, factor: f64) -> Option<(f64, f64)> {
+        let continues = self.current.as_ref().is_some_and(|discharge| {
+            level <= discharge.last.0
+                && at
+                    .duration_since(discharge.last.1)
+                    .is_ok_and(|gap| gap <= MAX_DRAIN_GAP)
+        });
+        if !continues {
+            self.finish();
+            self.current = Some(Discharge::new(level, at));
+            return None;
+        }
+        let discharge = self.current.as_mut()?;
+        discharge.last = (level, at);
+        let (rate, usual) = (discharge.rate()?, self.usual?);
+        if factor <= 0.0
+            || self.discharges < MIN_BASELINE_DISCHARGES
+            || discharge.reported
+            || rate < usual * factor
+        {
+            return None;
+        }
+        discharge.reported = true;
+        Some((rate, usual))
+    }
+}
+
+impl EventTracker {
+    pub fn new(low_battery: u8) -> Self {
+        Self {
+            low_battery,
+            quiet_when_stored: false,
+            once_per_discharge: false,
+            fast_drain: DEFAULT_FAST_DRAIN,
+            present: false,
+            worn: None,
+            low: HashSet::new(),
+            charged: HashSet::new(),
+            charging: HashSet::new(),
+            drains: HashMap::new(),
+            baselines: Baselines::default(),
+        }
+    }
+
+    /// Start the usual drain rates of pairs from `baselines` rather than
+    /// from their first discharges
+    pub fn with_baselines(mut self, baselines: Baselines) -> Self {
+        self.baselines = baselines;
+        self
+    }
+
+    /// Hold back `Low` while the earbuds are put away in the closed case,
+    /// see [`AirPodsStatus::stored`]. They report it once taken out if
+    /// they are still low.
+    pub fn with_quiet_when_stored(mut self, quiet: bool) -> Self {
+        self.quiet_when_stored = quiet;
+        self
+    }
+
+    /// Report `Low` at most once per discharge: a component rising above
+    /// the level again, e.g. a reading flickering between two 10% steps,
+    /// only re-arms it once it has been charging
+    pub fn with_once_per_discharge(mut self, once: bool) -> Self {
+        self.once_per_discharge = once;
+        self
+    }
+
+    /// Report `FastDrain` when a component drains `factor` times faster
+    /// than usual, never when it is 0
+    pub fn with_fast_drain(mut self, factor: f64) -> Self {
+        self.fast_drain = factor;
+        self
+    }
+
+    /// Take over the thresholds of `other`, e.g. after the config changed,
+    /// keeping track of what was already reported
+    pub fn reconfigure(&mut self, other: EventTracker) {
+        self.low_battery = other.low_battery;
+        self.quiet_when_stored = other.quiet_when_stored;
+        self.once_per_discharge = other.once_per_discharge;
+        self.fast_drain = other.fast_drain;
+    }
+
+    /// Feed the latest status (`None` when not found) of the pair with
+    /// `fingerprint` observed at `observed_at` and get the events it
+    /// triggered. Without a fingerprint, pairs of a model share their drain
+    /// rates.
+    pub fn update(
+        &mut self,
+        status: Option<&AirPodsStatus>,
+        fingerprint: Option<&Fingerprint>,
+        observed_at: SystemTime,
+    ) -> Vec<Event> {
+        let mut events = Vec::new();
+        let Some(status) = status else {
+            self.drains.values_mut().for_each(Drain::finish);
+            self.worn = None;
+            if std::mem::take(&mut self.present) {
+                events.push(Event::Disconnected);
+            }
+            return events;
+        };
+        if !std::mem::replace(&mut self.present, true) {
+            events.push(Event::Connected);
+        }
+
+        let worn = status.worn();
+        if let (Some(before), Some(now)) = (&self.worn, &worn) {
+            for component in now.iter().filter(|c| !before.contains(c)) {
+                events.push(Event::Worn {
+                    component: component.to_string(),
+                });
+            }
+            for component in before.iter().filter(|c| !now.contains(c)) {
+                events.push(Event::Removed {
+                    component: component.to_string(),
+                });
+            }
+        }
+        self.worn = worn;
+
+        let quiet = self.quiet_when_stored && status.stored();
+        let pair = fingerprint.cloned().unwrap_or_else(|| Fingerprint {
+            vendor: String::new(),
+            model: status.model().to_string(),
+            color: None,
+        });
+        for component in status.components() {
+            let key = (pair.clone(), component.name.clone());
+            let low = !component.charging && component.battery <= self.low_battery;
+            if !low {
+                if component.charging || !self.once_per_discharge {
+                    self.low.remove(&key);
+                }
+            } else if !quiet && self.low.insert(key.clone()) {
+                events.push(Event::Low {
+                    component: component.name.clone(),
+                });
+            }
+
+            let was_charging = if component.charging {
+                !self.charging.insert(key.clone())
+            } else {
+                self.charging.remove(&key)
+            };
+            if component.battery < CHARGED_REARM_BELOW {
+                self.charged.remove(&key);
+            } else if component.battery >= 100
+                && (component.charging || was_charging)
+                && self.charged.insert(key.clone())
+            {
+                events.push(Event::Charged {
+                    component: component.name.clone(),
+                });
+            }
+
+            let baselines = &self.baselines;
+            let drain = self.drains.entry(key).or_insert_with(|| {
+                let (usual, discharges) = baselines
+                    .usual(status.model(), &component.name)
+                    .map_or((None, 0), |(usual, discharges)| (Some(usual), discharges));
+                Drain {
+                    current: None,
+                    usual,
+                    discharges,
+                }
+            });
+            if component.charging || status.stored() {
+                drain.finish();
+            } else if let Some((rate, usual)) =
+                drain.update(component.battery, observed_at, self.fast_drain)
+            {
+                events.push(Event::FastDrain {
+                    component: component.name.clone(),
+                    rate: rate.round() as u32,
+                    usual: usual.round() as u32,
+                });
+            }
+        }
+        events
+    }
+}
+
+/// Holds back notifications until a point in time, set with `podpower
+/// snooze`. Events are still tracked meanwhile, so the ones that happened
+/// during the snooze aren't reported once it ends.
+#[derive(Debug, Clone, Default)]
+pub struct Snooze(Arc<Mutex<Option<SystemTime>>>);
+
+/// The longest [`Snooze`]: notifications held back for longer are better
+/// turned off
+pub const MAX_SNOOZE: Duration = Duration::from_secs(366 * 24 * 60 * 60);
+
+impl Snooze {
+    /// Snooze for `duration` from now, replacing an earlier snooze; a zero
+    /// `duration` ends it. Returns when it ends. Fails for more than
+    /// [`MAX_SNOOZE`], leaving the snooze as it was.
+    pub fn snooze(
+        &self,
+        duration: Duration,
+    ) -> Result<Option<SystemTime>, Box<dyn std::error::Error + Send + Sync>> {
+        if duration > MAX_SNOOZE {
+            return Err(format!("snooze for at most {} seconds", MAX_SNOOZE.as_secs()).into());
+        }
+        let until = if duration.is_zero() {
+            None
+        } else {
+            Some(
+                SystemTime::now()
+                    .checked_add(duration)
+                    .ok_or("snooze ends too far in the future")?,
+            )
+        };
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = until;
+        Ok(until)
+    }
+
+    /// When the snooze ends, `None` when not snoozed
+    pub fn until(&self) -> Option<SystemTime> {
+        let until = *self.0.lock().unwrap_or_else(|e| e.into_inner());
+        until.filter(|until| *until > SystemTime::now())
+    }
+
+    pub fn is_snoozed(&self) -> bool {
+        self.until().is_some()
+    }
+}
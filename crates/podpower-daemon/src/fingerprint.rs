@@ -0,0 +1,185 @@
+//! Recognizing a pair when its address doesn't: AirPods rotate their
+//! advertising address every few minutes, sometimes in the middle of a scan.
+
+use crate::scan::Sighting;
+use podpower_core::{AirPodsStatus, AppleDecoder, color_id};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Two sightings of one pair differ by at most this many steps of their
+/// battery precision
+const MAX_LEVEL_STEPS: u8 = 1;
+/// ...and by at most this much signal strength, in dBm
+const MAX_RSSI_DIFFERENCE: i16 = 12;
+
+pub const KNOWN_PAIRS_FILE: &str = "known_pairs.json";
+
+/// What stays the same across a pair's advertisements
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// [`podpower_core::VendorDecoder::name`] of the decoder that matched
+    pub vendor: String,
+    pub model: String,
+    /// [`color_id`], AirPods only
+    pub color: Option<u8>,
+}
+
+impl Fingerprint {
+    /// The fingerprint of `status`, decoded by `vendor` from `data`
+    pub fn of(vendor: &str, status: &AirPodsStatus, data: &[u8]) -> Self {
+        Self {
+            vendor: vendor.to_string(),
+            model: status.model().to_string(),
+            color: if vendor == AppleDecoder::NAME {
+                color_id(data)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Whether two sightings from the same scan are probably one pair under
+/// different addresses: the same fingerprint, the same components at about
+/// the same levels and charging the same way, and about the same signal
+/// strength. Two identical pairs lying side by side look the same too, and
+/// get counted once.
+pub fn same_pair(a: &Sighting, b: &Sighting) -> bool {
+    if a.fingerprint != b.fingerprint {
+        return false;
+    }
+    let (left, right) = (a.status.components(), b.status.components());
+    let levels_match = left.len() == right.len()
+        && left.iter().all(|component| {
+            right.iter().any(|other| {
+                let tolerance = component.precision.max(other.precision) * MAX_LEVEL_STEPS;
+                other.name == component.name
+                    && other.charging == component.charging
+                    && other.battery.abs_diff(component.battery) <= tolerance
+            })
+        });
+    let rssi_match = match (a.rssi, b.rssi) {
+        (Some(a), Some(b)) => (a - b).abs() <= MAX_RSSI_DIFFERENCE,
+        _ => true,
+    };
+    levels_match && rssi_match
+}
+
+/// Add `sighting` to the pairs seen so far in a scan, replacing the earlier
+/// sighting of the same pair: the same address, or the same pair under a new
+/// one, see [`same_pair`]
+pub fn merge(sightings: &mut Vec<Sighting>, sighting: Sighting) {
+    match sightings.iter_mut().find(|known| {
+        (known.address == sighting.address
+            && known.fingerprint.vendor == sighting.fingerprint.vendor)
+            || same_pair(known, &sighting)
+    }) {
+        Some(known) => {
+            if known.address != sighting.address {
+                debug!(from = %known.address, to = %sighting.address, "address rotated");
+            }
+            // Connected stays true when only one of the addresses was the
+            // connected peripheral's
+            let connected = known.connected || sighting.connected;
+            let name = sighting.name.or(known.name.take());
+            *known = Sighting {
+                connected,
+                name,
+                ..sighting
+            };
+        }
+        None => sightings.push(sighting),
+    }
+}
+
+/// Fingerprints of pairs seen connected to this host, which are the user's
+/// own, stored as a JSON array. Without the `json` feature they are only
+/// remembered until the process exits.
+#[derive(Debug, Clone, Default)]
+pub struct KnownPairs {
+    #[cfg_attr(not(feature = "json"), allow(dead_code))]
+    path: PathBuf,
+    fingerprints: Vec<Fingerprint>,
+}
+
+impl KnownPairs {
+    /// [`KNOWN_PAIRS_FILE`] in [`crate::data_dir`]
+    pub fn default_path() -> Option<PathBuf> {
+        Some(crate::data_dir()?.join(KNOWN_PAIRS_FILE))
+    }
+
+    /// The pairs stored at `path`, none if there is no file yet
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            fingerprints: read_fingerprints(path)?,
+        })
+    }
+
+    pub fn contains(&self, fingerprint: &Fingerprint) -> bool {
+        self.fingerprints.contains(fingerprint)
+    }
+
+    /// Remember `fingerprint`, writing the file when it is new
+    pub fn remember(&mut self, fingerprint: &Fingerprint) -> io::Result<()> {
+        if self.contains(fingerprint) {
+            return Ok(());
+        }
+        self.fingerprints.push(fingerprint.clone());
+        self.save()
+    }
+
+    #[cfg(feature = "json")]
+    fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_vec_pretty(&self.fingerprints).map_err(io::Error::other)?;
+        std::fs::write(&self.path, json)
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn save(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+fn read_fingerprints(path: &Path) -> io::Result<Vec<Fingerprint>> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn read_fingerprints(_path: &Path) -> io::Result<Vec<Fingerprint>> {
+    Ok(Vec::new())
+}
+
+/// How likely `sighting` is of the user's own pair, from 0 to 100: certain
+/// when it is connected to this host, otherwise up to 60 for a strong
+/// signal, 30 more when a pair with its fingerprint was connected before,
+/// 20 more when it got a connected device's name (see
+/// [`Sighting::name`]) and 10 more when it was the only pair in range
+pub fn confidence(sighting: &Sighting, known: &KnownPairs, pairs_in_range: usize) -> u8 {
+    if sighting.connected {
+        return 100;
+    }
+    // -90 dBm and weaker score nothing, -40 dBm and stronger score 60
+    let signal = sighting
+        .rssi
+        .map_or(20, |rssi| ((rssi + 90).clamp(0, 50) * 6 / 5) as u8);
+    let known = if known.contains(&sighting.fingerprint) {
+        30
+    } else {
+        0
+    };
+    let named = if sighting.name.is_some() { 20 } else { 0 };
+    let alone = if pairs_in_range == 1 { 10 } else { 0 };
+    // Only a connection makes it certain
+    (signal + known + named + alone).min(95)
+}
@@ -0,0 +1,185 @@
+//! Every pair a daemon running with `--fleet` has heard from, e.g. on a
+//! household charging station or a shelf of shared headsets, with the
+//! latest levels of each. Pairs are told apart by their [`Fingerprint`], as
+//! their addresses rotate, and go stale, then forgotten, when they aren't
+//! heard from for a while.
+
+use crate::fingerprint::Fingerprint;
+use crate::scan::Sighting;
+use podpower_core::AirPodsStatus;
+use serde::Deserialize;
+use std::time::{Duration, SystemTime};
+
+/// A `[[devices]]` entry of the config file, naming the pairs of a model,
+/// and for AirPods of one color
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceName {
+    pub name: String,
+    /// [`AirPodsStatus::model`], ignoring case
+    pub model: String,
+    /// [`Fingerprint::color`], as `podpower fleet` shows it
+    pub color: Option<u8>,
+}
+
+impl DeviceName {
+    fn matches(&self, fingerprint: &Fingerprint) -> bool {
+        self.model.eq_ignore_ascii_case(&fingerprint.model)
+            && self
+                .color
+                .is_none_or(|color| fingerprint.color == Some(color))
+    }
+}
+
+/// How long pairs that aren't heard from any more stay in the [`Fleet`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    /// After this long the pair's levels are no longer current, see
+    /// [`FleetDevice::is_stale`]. Zero to never go stale.
+    pub stale_after: Duration,
+    /// After this long the pair is dropped. Zero to keep it.
+    pub forget_after: Duration,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            stale_after: Duration::from_secs(60 * 60),
+            forget_after: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// The pairs heard from, in the order they were first seen
+#[derive(Debug, Clone, Default)]
+pub struct Fleet {
+    pub devices: Vec<FleetDevice>,
+    pub retention: Retention,
+}
+
+/// The latest sighting of one pair
+#[derive(Debug, Clone)]
+pub struct FleetDevice {
+    /// From the first matching [`DeviceName`], otherwise the name it was
+    /// paired under if it ever was connected to this host
+    pub name: Option<String>,
+    /// See [`Sighting::name`], kept from the last time it was connected
+    pub paired_name: Option<String>,
+    pub fingerprint: Fingerprint,
+    pub status: AirPodsStatus,
+    pub rssi: Option<i16>,
+    pub connected: bool,
+    pub last_seen: SystemTime,
+}
+
+impl FleetDevice {
+    /// Not heard from in [`Retention::stale_after`] as of `now`, so its
+    /// levels are probably out of date
+    pub fn is_stale(&self, retention: &Retention, now: SystemTime) -> bool {
+        !retention.stale_after.is_zero() && age(self.last_seen, now) >= retention.stale_after
+    }
+}
+
+impl Fleet {
+    pub fn new(retention: Retention) -> Self {
+        Self {
+            devices: Vec::new(),
+            retention,
+        }
+    }
+
+    /// Fold one scan's `sightings` into the fleet, then forget the pairs
+    /// not heard from in [`Retention::forget_after`], see [`update`]
+    pub fn update(&mut self, sightings: &[Sighting], names: &[DeviceName], at: SystemTime) {
+        update(&mut self.devices, sightings, names, at);
+        let forget_after = self.retention.forget_after;
+        if !forget_after.is_zero() {
+            self.devices
+                .retain(|device| age(device.last_seen, at) < forget_after);
+        }
+    }
+}
+
+fn age(last_seen: SystemTime, now: SystemTime) -> Duration {
+    now.duration_since(last_seen).unwrap_or_default()
+}
+
+/// Fold one scan's `sightings` into `fleet`. A sighting updates the pair
+/// with its paired name, or else the one with its fingerprint whose levels
+/// are closest, unless it was paired under another name; only when each of
+/// those was already updated by this scan is it a new pair. Pairs not in
+/// range keep their last levels.
+fn update(
+    fleet: &mut Vec<FleetDevice>,
+    sightings: &[Sighting],
+    names: &[DeviceName],
+    at: SystemTime,
+) {
+    let mut updated = vec![false; fleet.len()];
+    for sighting in sightings {
+        let candidates = || {
+            fleet
+                .iter()
+                .enumerate()
+                .filter(|(i, device)| !updated[*i] && device.fingerprint == sighting.fingerprint)
+        };
+        let paired = sighting.name.as_ref().and_then(|name| {
+            candidates()
+                .find(|(_, device)| device.paired_name.as_ref() == Some(name))
+                .map(|(i, _)| i)
+        });
+        // Paired under another name is another pair
+        let closest = || {
+            candidates()
+                .filter(|(_, device)| sighting.name.is_none() || device.paired_name.is_none())
+                .min_by_key(|(_, device)| distance(&device.status, &sighting.status))
+                .map(|(i, _)| i)
+        };
+        let known = paired.or_else(closest);
+        let paired_name = sighting
+            .name
+            .clone()
+            .or_else(|| known.and_then(|i| fleet[i].paired_name.clone()));
+        let device = FleetDevice {
+            name: names
+                .iter()
+                .find(|name| name.matches(&sighting.fingerprint))
+                .map(|name| name.name.clone())
+                .or_else(|| paired_name.clone()),
+            paired_name,
+            fingerprint: sighting.fingerprint.clone(),
+            status: sighting.status.clone(),
+            rssi: sighting.rssi,
+            connected: sighting.connected,
+            last_seen: at,
+        };
+        match known {
+            Some(i) => {
+                fleet[i] = device;
+                updated[i] = true;
+            }
+            None => {
+                fleet.push(device);
+                updated.push(true);
+            }
+        }
+    }
+    for (device, _) in fleet
+        .iter_mut()
+        .zip(updated)
+        .filter(|(_, updated)| !updated)
+    {
+        device.connected = false;
+    }
+}
+
+/// Sum of the level differences of the components both report
+fn distance(a: &AirPodsStatus, b: &AirPodsStatus) -> u32 {
+    a.components()
+        .iter()
+        .filter_map(|component| {
+            let other = b.components().iter().find(|c| c.name == component.name)?;
+            Some(u32::from(component.battery.abs_diff(other.battery)))
+        })
+        .sum()
+}
@@ -0,0 +1,87 @@
+//! Fallback for earbuds that don't advertise their battery but expose the
+//! standard GATT Battery Service while connected.
+
+use crate::fingerprint::Fingerprint;
+use crate::scan::Sighting;
+use btleplug::api::bleuuid::BleUuid;
+use btleplug::api::{Central, Peripheral as _};
+use btleplug::platform::Adapter;
+use podpower_core::{AirPodsStatus, Component, EXACT_PRECISION};
+use tracing::debug;
+
+/// [`Fingerprint::vendor`] of sightings read from the Battery Service
+pub const NAME: &str = "gatt";
+
+const BATTERY_SERVICE: u16 = 0x180f;
+const BATTERY_LEVEL: u16 = 0x2a19;
+
+/// Read the Battery Level characteristic of the first connected device that
+/// has one. Devices that aren't connected are left alone rather than
+/// connected to, so this never pairs with someone else's headphones.
+pub async fn read_battery_service(
+    adapter: &Adapter,
+) -> Result<Option<Sighting>, Box<dyn std::error::Error + Send + Sync>> {
+    for peripheral in adapter.peripherals().await? {
+        if !peripheral.is_connected().await.unwrap_or(false) {
+            continue;
+        }
+        let properties = match peripheral.properties().await {
+            Ok(properties) => properties.unwrap_or_default(),
+            Err(e) => {
+                debug!("skipping a connected device whose properties failed: {}", e);
+                continue;
+            }
+        };
+        let name = properties
+            .local_name
+            .clone()
+            .unwrap_or_else(|| properties.address.to_string());
+        // Discovery can fail on devices that are only connected for audio
+        if let Err(e) = peripheral.discover_services().await {
+            debug!(device = %name, "service discovery failed: {}", e);
+            continue;
+        }
+        let Some(characteristic) = peripheral.characteristics().into_iter().find(|c| {
+            c.service_uuid.to_ble_u16() == Some(BATTERY_SERVICE)
+                && c.uuid.to_ble_u16() == Some(BATTERY_LEVEL)
+        }) else {
+            continue;
+        };
+        let value = peripheral.read(&characteristic).await?;
+        let Some(&battery) = value.first().filter(|&&level| level <= 100) else {
+            debug!(device = %name, ?value, "invalid battery level");
+            continue;
+        };
+        debug!(device = %name, battery, "read GATT battery level");
+        let status = AirPodsStatus::OverEar {
+            model: name,
+            battery,
+            components: vec![Component {
+                name: "headphones".into(),
+                battery,
+                // The Battery Level characteristic has no charging state
+                charging: false,
+                precision: EXACT_PRECISION,
+                raw: None,
+            }],
+            on_head: false,
+            plugged_in: false,
+            connection_state: None,
+        };
+        return Ok(Some(Sighting {
+            fingerprint: Fingerprint::of(NAME, &status, &value),
+            status,
+            address: properties.address.to_string(),
+            rssi: properties.rssi,
+            connected: true,
+            name: properties.local_name,
+            // Connected and picked by name, not by a guess
+            confidence: Some(100),
+            partial: false,
+            listening_mode: None,
+            data: value,
+            warnings: Vec::new(),
+        }));
+    }
+    Ok(None)
+}
@@ -0,0 +1,103 @@
+//! gRPC server for the status, the counterpart of [`crate::http`] for
+//! systems that standardize on gRPC. The service is defined in
+//! `proto/podpower.proto`, shipped with the crate.
+
+use crate::monitor::Reading;
+use podpower_core::AirPodsStatus;
+use proto::battery_server::{Battery, BatteryServer};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::UNIX_EPOCH;
+use tokio::sync::watch;
+use tokio_stream::wrappers::WatchStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+/// Messages and server generated from `proto/podpower.proto`
+pub mod proto {
+    tonic::include_proto!("podpower.v1");
+}
+
+type Readings = watch::Receiver<Option<Reading>>;
+
+/// Serve the `podpower.v1.Battery` service on `addr`:
+///
+/// - `Status` - the latest status (`NOT_FOUND` when AirPods are not nearby,
+///   `UNAVAILABLE` before the first scan completes)
+/// - `WatchStatus` - the latest status, then one update per change
+pub async fn serve_grpc(
+    addr: SocketAddr,
+    readings: Readings,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(BatteryServer::new(Service { readings }))
+        .serve(addr)
+        .await
+}
+
+struct Service {
+    readings: Readings,
+}
+
+#[tonic::async_trait]
+impl Battery for Service {
+    async fn status(
+        &self,
+        _: Request<proto::StatusRequest>,
+    ) -> Result<Response<proto::BatteryStatus>, Status> {
+        match &*self.readings.borrow() {
+            Some(reading) => match message(reading) {
+                Some(status) => Ok(Response::new(status)),
+                None => Err(Status::not_found("AirPods not found")),
+            },
+            None => Err(Status::unavailable("No scan has completed yet")),
+        }
+    }
+
+    type WatchStatusStream =
+        Pin<Box<dyn Stream<Item = Result<proto::StatusUpdate, Status>> + Send>>;
+
+    async fn watch_status(
+        &self,
+        _: Request<proto::StatusRequest>,
+    ) -> Result<Response<Self::WatchStatusStream>, Status> {
+        let updates = WatchStream::new(self.readings.clone()).filter_map(|reading| {
+            Some(Ok(proto::StatusUpdate {
+                status: message(&reading?),
+            }))
+        });
+        Ok(Response::new(Box::pin(updates)))
+    }
+}
+
+/// `None` when the scan found no AirPods
+fn message(reading: &Reading) -> Option<proto::BatteryStatus> {
+    let status: &AirPodsStatus = reading.status.as_ref()?;
+    Some(proto::BatteryStatus {
+        model: status.model().to_string(),
+        name: reading
+            .name
+            .clone()
+            .unwrap_or_else(|| status.model().to_string()),
+        battery: status.battery().map(u32::from),
+        components: status
+            .components()
+            .iter()
+            .map(|component| proto::Component {
+                name: component.name.clone(),
+                battery: component.battery.into(),
+                charging: component.charging,
+                precision: component.precision.into(),
+            })
+            .collect(),
+        observed_at_ms: reading
+            .observed_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+        confidence: reading.confidence.map(u32::from),
+        partial: reading.partial,
+        listening_mode: reading.listening_mode.map(|mode| mode.to_string()),
+    })
+}
@@ -0,0 +1,124 @@
+//! Charge cycles and battery wear estimated from the recorded
+//! [`crate::history`].
+
+use crate::history::Sample;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A discharge to this level or below counts towards a charge cycle
+pub const DEEP_DISCHARGE: u8 = 20;
+/// ...which completes once the component charges back to this level
+pub const FULL_CHARGE: u8 = 95;
+/// Longer gaps between two samples mean the AirPods were away, not draining
+const MAX_SAMPLE_GAP: Duration = Duration::from_secs(2 * 60 * 60);
+/// Months with less drain than this are too noisy to estimate a runtime from
+const MIN_MONTHLY_DRAIN: u32 = 50;
+
+/// Wear estimate for one component of one model
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComponentHealth {
+    pub model: String,
+    pub component: String,
+    /// Deep discharges followed by a full charge
+    pub cycles: u32,
+    /// Percentage points discharged in total, divided by 100
+    pub equivalent_cycles: f64,
+    /// Estimated runtime on a full charge, per month with enough use,
+    /// oldest first
+    pub runtime: Vec<MonthlyRuntime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MonthlyRuntime {
+    /// `YYYY-MM`
+    pub month: String,
+    /// Hours from 100% to empty at that month's drain rate
+    pub hours: f64,
+}
+
+impl ComponentHealth {
+    /// Runtime change from the first to the latest month in percent,
+    /// negative when the battery holds less than it used to
+    pub fn runtime_trend(&self) -> Option<f64> {
+        let (first, last) = (self.runtime.first()?, self.runtime.last()?);
+        (self.runtime.len() > 1).then(|| (last.hours / first.hours - 1.0) * 100.0)
+    }
+}
+
+/// Estimate the health of every component in `samples`, which have to be
+/// in chronological order as [`crate::history::History::since`] returns
+/// them
+pub fn health(samples: &[Sample]) -> Vec<ComponentHealth> {
+    let mut order: Vec<(&str, &str)> = Vec::new();
+    let mut by_component: HashMap<(&str, &str), Vec<&Sample>> = HashMap::new();
+    for sample in samples {
+        let key = (sample.model.as_str(), sample.component.as_str());
+        by_component
+            .entry(key)
+            .or_insert_with(|| {
+                order.push(key);
+                Vec::new()
+            })
+            .push(sample);
+    }
+    order
+        .into_iter()
+        .map(|key| component_health(key, &by_component[&key]))
+        .collect()
+}
+
+fn component_health((model, component): (&str, &str), samples: &[&Sample]) -> ComponentHealth {
+    let mut cycles = 0;
+    let mut deep = false;
+    let mut discharged = 0u32;
+    // Drained points and hours per month, in month order
+    let mut months: Vec<(String, u32, f64)> = Vec::new();
+
+    for (i, sample) in samples.iter().enumerate() {
+        if !sample.charging && sample.level <= DEEP_DISCHARGE {
+            deep = true;
+        } else if deep && sample.level >= FULL_CHARGE {
+            cycles += 1;
+            deep = false;
+        }
+
+        let Some(previous) = i.checked_sub(1).map(|i| samples[i]) else {
+            continue;
+        };
+        if previous.charging || sample.charging || sample.level >= previous.level {
+            continue;
+        }
+        let drop = u32::from(previous.level - sample.level);
+        discharged += drop;
+        let Ok(gap) = sample.observed_at.duration_since(previous.observed_at) else {
+            continue;
+        };
+        if gap > MAX_SAMPLE_GAP {
+            continue;
+        }
+        let month = humantime::format_rfc3339(sample.observed_at).to_string()[..7].to_string();
+        match months.last_mut() {
+            Some((last, points, hours)) if *last == month => {
+                *points += drop;
+                *hours += gap.as_secs_f64() / 3600.0;
+            }
+            _ => months.push((month, drop, gap.as_secs_f64() / 3600.0)),
+        }
+    }
+
+    ComponentHealth {
+        model: model.to_string(),
+        component: component.to_string(),
+        cycles,
+        equivalent_cycles: f64::from(discharged) / 100.0,
+        runtime: months
+            .into_iter()
+            .filter(|(_, points, _)| *points >= MIN_MONTHLY_DRAIN)
+            .map(|(month, points, hours)| MonthlyRuntime {
+                month,
+                hours: hours * 100.0 / f64::from(points),
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,133 @@
+//! Battery levels recorded to SQLite, for exports and long-term analysis.
+
+use crate::events::Baselines;
+use crate::monitor::Reading;
+use podpower_core::AirPodsStatus;
+use rusqlite::{Connection, params};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+type Readings = watch::Receiver<Option<Reading>>;
+
+pub const FILE_NAME: &str = "history.sqlite";
+
+/// One component's level from one scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub observed_at: SystemTime,
+    pub model: String,
+    pub component: String,
+    pub level: u8,
+    pub charging: bool,
+}
+
+/// The history database, created on first use
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS samples (
+                observed_at INTEGER NOT NULL,
+                model TEXT NOT NULL,
+                component TEXT NOT NULL,
+                level INTEGER NOT NULL,
+                charging INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS samples_observed_at ON samples (observed_at);",
+        )?;
+        Ok(Self { connection })
+    }
+
+    /// Store a row for every component of `status`
+    pub fn record(
+        &mut self,
+        status: &AirPodsStatus,
+        observed_at: SystemTime,
+    ) -> rusqlite::Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO samples (observed_at, model, component, level, charging)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for component in status.components() {
+                insert.execute(params![
+                    millis(observed_at),
+                    status.model(),
+                    component.name,
+                    component.battery,
+                    component.charging,
+                ])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Samples observed at or after `since`, oldest first
+    pub fn since(&self, since: SystemTime) -> rusqlite::Result<Vec<Sample>> {
+        let mut query = self.connection.prepare(
+            "SELECT observed_at, model, component, level, charging FROM samples
+             WHERE observed_at >= ?1 ORDER BY observed_at, rowid",
+        )?;
+        query
+            .query_map([millis(since)], |row| {
+                Ok(Sample {
+                    observed_at: UNIX_EPOCH + Duration::from_millis(row.get::<_, i64>(0)? as u64),
+                    model: row.get(1)?,
+                    component: row.get(2)?,
+                    level: row.get(3)?,
+                    charging: row.get(4)?,
+                })
+            })?
+            .collect()
+    }
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+/// Usual drain rates learned from `samples`, oldest first. The history
+/// doesn't tell pairs of one model apart, so their discharges all count
+/// toward the model's.
+pub fn baselines(samples: &[Sample]) -> Baselines {
+    let mut baselines = Baselines::default();
+    for sample in samples {
+        baselines.record(
+            &sample.model,
+            &sample.component,
+            sample.level,
+            sample.charging,
+            sample.observed_at,
+        );
+    }
+    baselines
+}
+
+/// [`FILE_NAME`] in [`crate::data_dir`]
+pub fn default_path() -> Option<PathBuf> {
+    Some(crate::data_dir()?.join(FILE_NAME))
+}
+
+/// Record the status every time it changes. Scans that find nothing leave
+/// no rows.
+pub async fn serve_history(mut history: History, mut readings: Readings) -> rusqlite::Result<()> {
+    while readings.changed().await.is_ok() {
+        let Some((status, observed_at)) = readings
+            .borrow_and_update()
+            .as_ref()
+            .and_then(|reading| Some((reading.status.clone()?, reading.observed_at)))
+        else {
+            continue;
+        };
+        history.record(&status, observed_at)?;
+    }
+    Ok(())
+}
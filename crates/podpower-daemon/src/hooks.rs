@@ -0,0 +1,191 @@
+//! Shell commands run on status transitions.
+
+use crate::events::{Baselines, DEFAULT_FAST_DRAIN, DEFAULT_LOW_BATTERY, Event, EventTracker};
+use crate::monitor::Reading;
+use podpower_core::AirPodsStatus;
+use serde::Deserialize;
+use std::io;
+use tokio::process::Command;
+use tokio::sync::watch;
+use tracing::{debug, warn};
+
+type Readings = watch::Receiver<Option<Reading>>;
+
+/// The `[hooks]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Level at or below which `on_low` runs
+    pub low_battery: u8,
+    /// Skip `on_low` while the earbuds are in the closed case
+    pub quiet_when_stored: bool,
+    /// How many times faster than usual a component has to drain for
+    /// `on_fast_drain` to run, 0 to never run it
+    pub fast_drain: f64,
+    pub on_low: Option<String>,
+    pub on_charged: Option<String>,
+    pub on_fast_drain: Option<String>,
+    pub on_connected: Option<String>,
+    pub on_disconnected: Option<String>,
+    pub on_worn: Option<String>,
+    pub on_removed: Option<String>,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            low_battery: DEFAULT_LOW_BATTERY,
+            quiet_when_stored: false,
+            fast_drain: DEFAULT_FAST_DRAIN,
+            on_low: None,
+            on_charged: None,
+            on_fast_drain: None,
+            on_connected: None,
+            on_disconnected: None,
+            on_worn: None,
+            on_removed: None,
+        }
+    }
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        self.on_low.is_none()
+            && self.on_charged.is_none()
+            && self.on_fast_drain.is_none()
+            && self.on_connected.is_none()
+            && self.on_disconnected.is_none()
+            && self.on_worn.is_none()
+            && self.on_removed.is_none()
+    }
+
+    fn tracker(&self) -> EventTracker {
+        EventTracker::new(self.low_battery)
+            .with_quiet_when_stored(self.quiet_when_stored)
+            .with_fast_drain(self.fast_drain)
+    }
+
+    fn command(&self, event: &Event) -> Option<&str> {
+        match event {
+            Event::Low { .. } => self.on_low.as_deref(),
+            Event::Charged { .. } => self.on_charged.as_deref(),
+            Event::FastDrain { .. } => self.on_fast_drain.as_deref(),
+            Event::Connected => self.on_connected.as_deref(),
+            Event::Disconnected => self.on_disconnected.as_deref(),
+            Event::Worn { .. } => self.on_worn.as_deref(),
+            Event::Removed { .. } => self.on_removed.as_deref(),
+        }
+    }
+}
+
+/// Run the configured command through the shell (`sh -c`, `cmd /C` on
+/// Windows) for every transition, without waiting for it to finish. Changes
+/// to `config` apply from the next status on. Usual drain rates for
+/// `fast_drain` start from `baselines`.
+///
+/// The command sees the status in its environment:
+///
+/// - `PODPOWER_EVENT` - `low`, `charged`, `fast_drain`, `connected`,
+///   `disconnected`, `worn` or `removed`
+/// - `PODPOWER_COMPONENT` - the component for all but `connected` and
+///   `disconnected`
+/// - `PODPOWER_DRAIN_RATE` and `PODPOWER_USUAL_DRAIN_RATE` - percent per
+///   hour, for `fast_drain`
+/// - `PODPOWER_MODEL`, `PODPOWER_BATTERY`
+/// - `PODPOWER_CONNECTION_STATE` - `disconnected`, `idle`, `music` or
+///   `call`, when the device reports it
+/// - `PODPOWER_<COMPONENT>` and `PODPOWER_<COMPONENT>_CHARGING` (`0`/`1`),
+///   e.g. `PODPOWER_LEFT=85`
+/// - `PODPOWER_STATUS` - the status as JSON, `null` when disconnected
+pub async fn serve_hooks(
+    mut config: watch::Receiver<HooksConfig>,
+    mut readings: Readings,
+    baselines: Baselines,
+) -> io::Result<()> {
+    let mut tracker = config
+        .borrow_and_update()
+        .tracker()
+        .with_baselines(baselines);
+
+    while readings.changed().await.is_ok() {
+        if config.has_changed().unwrap_or(false) {
+            tracker.reconfigure(config.borrow_and_update().tracker());
+        }
+        let Some((status, fingerprint, observed_at)) =
+            readings.borrow_and_update().as_ref().map(|reading| {
+                (
+                    reading.status.clone(),
+                    reading.fingerprint.clone(),
+                    reading.observed_at,
+                )
+            })
+        else {
+            continue;
+        };
+        for event in tracker.update(status.as_ref(), fingerprint.as_ref(), observed_at) {
+            if let Some(command) = config.borrow().command(&event) {
+                run(command, &event, status.as_ref());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run(command: &str, event: &Event, status: Option<&AirPodsStatus>) {
+    debug!(event = event.name(), command, "running hook");
+    #[cfg(unix)]
+    let mut child = Command::new("sh");
+    #[cfg(unix)]
+    child.arg("-c").arg(command);
+    #[cfg(windows)]
+    let mut child = Command::new("cmd");
+    #[cfg(windows)]
+    child.arg("/C").arg(command);
+
+    child.env("PODPOWER_EVENT", event.name());
+    if let Some(component) = event.component() {
+        child.env("PODPOWER_COMPONENT", component);
+    }
+    if let Event::FastDrain { rate, usual, .. } = event {
+        child.env("PODPOWER_DRAIN_RATE", rate.to_string());
+        child.env("PODPOWER_USUAL_DRAIN_RATE", usual.to_string());
+    }
+    #[cfg(feature = "json")]
+    child.env(
+        "PODPOWER_STATUS",
+        serde_json::to_string(&status).unwrap_or_default(),
+    );
+    if let Some(status) = status {
+        child.env("PODPOWER_MODEL", status.model());
+        if let Some(battery) = status.battery() {
+            child.env("PODPOWER_BATTERY", battery.to_string());
+        }
+        if let Some(state) = status.connection_state() {
+            child.env("PODPOWER_CONNECTION_STATE", state.to_string());
+        }
+        for component in status.components() {
+            let name = component.name.to_uppercase();
+            child.env(format!("PODPOWER_{}", name), component.battery.to_string());
+            child.env(
+                format!("PODPOWER_{}_CHARGING", name),
+                u8::from(component.charging).to_string(),
+            );
+        }
+    }
+
+    let name = event.name();
+    match child.spawn() {
+        Ok(mut child) => {
+            tokio::spawn(async move {
+                match child.wait().await {
+                    Ok(exit) if !exit.success() => {
+                        warn!("on_{} hook exited with {}", name, exit)
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("on_{} hook failed: {}", name, e),
+                }
+            });
+        }
+        Err(e) => warn!("Could not run on_{} hook: {}", name, e),
+    }
+}
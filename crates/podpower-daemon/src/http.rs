@@ -0,0 +1,452 @@
+use crate::control::FleetStatus;
+use crate::metrics;
+use crate::monitor::{DEFAULT_INTERVAL_SECS, Diagnostics, Reading};
+use axum::Json;
+use axum::Router;
+use axum::extract::{FromRef, Query, State};
+use axum::http::header;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use podpower_core::payload::{self, ErrorPayload, HassPayload, SCHEMA_VERSION, StatusPayload};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+type Readings = watch::Receiver<Option<Reading>>;
+type DiagnosticsRx = watch::Receiver<Diagnostics>;
+/// A reading with its SSE event id
+type Numbered = (u64, Reading);
+
+/// How many readings `/status/stream` keeps to replay to clients that
+/// reconnect with `Last-Event-ID`
+const REPLAY_READINGS: usize = 64;
+
+#[derive(Clone)]
+struct AppState {
+    readings: Readings,
+    recent: Recent,
+    diagnostics: DiagnosticsRx,
+}
+
+impl FromRef<AppState> for Readings {
+    fn from_ref(state: &AppState) -> Self {
+        state.readings.clone()
+    }
+}
+
+impl FromRef<AppState> for DiagnosticsRx {
+    fn from_ref(state: &AppState) -> Self {
+        state.diagnostics.clone()
+    }
+}
+
+impl FromRef<AppState> for Recent {
+    fn from_ref(state: &AppState) -> Self {
+        state.recent.clone()
+    }
+}
+
+/// The latest readings, numbered in the order they arrived. The numbers are
+/// the SSE event ids, and start over when the daemon does.
+#[derive(Clone)]
+struct Recent {
+    readings: Arc<Mutex<Kept>>,
+    tx: broadcast::Sender<Numbered>,
+}
+
+struct Kept {
+    next_id: u64,
+    readings: VecDeque<Numbered>,
+}
+
+impl Recent {
+    fn new() -> Self {
+        Self {
+            readings: Arc::new(Mutex::new(Kept {
+                next_id: 1,
+                readings: VecDeque::with_capacity(REPLAY_READINGS),
+            })),
+            tx: broadcast::channel(REPLAY_READINGS).0,
+        }
+    }
+
+    /// Number every reading `readings` sees until the daemon stops
+    async fn record(self, mut readings: Readings) {
+        loop {
+            if let Some(reading) = readings.borrow_and_update().clone() {
+                self.push(reading);
+            }
+            if readings.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn push(&self, reading: Reading) {
+        let mut kept = self.readings.lock().unwrap();
+        let id = kept.next_id;
+        kept.next_id += 1;
+        if kept.readings.len() == REPLAY_READINGS {
+            kept.readings.pop_front();
+        }
+        kept.readings.push_back((id, reading.clone()));
+        // Sent under the lock, so a subscriber sees every reading exactly
+        // once, replayed or live
+        let _ = self.tx.send((id, reading));
+    }
+
+    /// The readings after `last_event_id` and a receiver for the ones still
+    /// to come. Without an id, or with one from before the daemon restarted,
+    /// only the latest reading is replayed.
+    fn subscribe(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (Vec<Numbered>, broadcast::Receiver<Numbered>) {
+        let kept = self.readings.lock().unwrap();
+        let replay = match last_event_id {
+            Some(last) if last < kept.next_id => kept
+                .readings
+                .iter()
+                .filter(|(id, _)| *id > last)
+                .cloned()
+                .collect(),
+            _ => kept.readings.back().cloned().into_iter().collect(),
+        };
+        (replay, self.tx.subscribe())
+    }
+}
+
+/// Serve the REST API on `addr`:
+///
+/// - `GET /` - HTML page with the battery levels that reloads itself, for a
+///   phone or tablet on the LAN to use as a display
+/// - `GET /status` - latest status as JSON (404 when AirPods are not nearby)
+/// - `GET /status/stream` - Server-Sent Events, one `status` event per change.
+///   Events have ids, and a client reconnecting with `Last-Event-ID` gets the
+///   ones it missed first, as far as the last [`REPLAY_READINGS`] go.
+///
+/// Both take `?version=N` to get the JSON in the shape of an older schema
+/// version.
+///
+/// - `GET /badge.svg` - shields.io-style badge for the lowest component, or
+///   the one picked with `?component=left`
+/// - `GET /hass` - flat JSON for Home Assistant's RESTful sensors, see
+///   [`HassPayload`]. Always `200`, with `"available": false` when AirPods
+///   are not nearby.
+/// - `GET /fleet` - every pair a daemon running with `--fleet` heard from,
+///   as JSON, see [`FleetStatus`]
+/// - `GET /metrics` - Prometheus exposition of the battery gauges of
+///   `--format prom-textfile` and the daemon's own counters, see
+///   [`metrics::daemon`]
+/// - `GET /healthz` - liveness probe
+pub async fn serve_http(
+    addr: SocketAddr,
+    readings: Readings,
+    diagnostics: DiagnosticsRx,
+) -> std::io::Result<()> {
+    let recent = Recent::new();
+    tokio::spawn(recent.clone().record(readings.clone()));
+    let app = Router::new()
+        .route("/", get(page))
+        .route("/status", get(status))
+        .route("/status/stream", get(status_stream))
+        .route("/badge.svg", get(badge))
+        .route("/hass", get(hass))
+        .route("/fleet", get(fleet))
+        .route("/metrics", get(prometheus))
+        .route("/healthz", get(healthz))
+        .with_state(AppState {
+            readings,
+            recent,
+            diagnostics,
+        });
+
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await
+}
+
+#[derive(Deserialize)]
+struct VersionQuery {
+    version: Option<u32>,
+}
+
+impl VersionQuery {
+    /// The requested schema version, or a 400 response for unknown ones
+    fn resolve(&self) -> Result<u32, (StatusCode, Json<ErrorPayload>)> {
+        match self.version {
+            None => Ok(SCHEMA_VERSION),
+            Some(version) if payload::is_supported(version) => Ok(version),
+            Some(version) => Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorPayload::new(format!(
+                    "unsupported schema version {}",
+                    version
+                ))),
+            )),
+        }
+    }
+}
+
+async fn status(State(readings): State<Readings>, Query(query): Query<VersionQuery>) -> Response {
+    let version = match query.resolve() {
+        Ok(version) => version,
+        Err(rejection) => return rejection.into_response(),
+    };
+    match &*readings.borrow() {
+        Some(Reading {
+            status: Some(status),
+            observed_at,
+            name,
+            confidence,
+            partial,
+            listening_mode,
+            warnings,
+            ..
+        }) => Json(
+            StatusPayload::with_version(status.clone(), version)
+                .with_name(name.clone())
+                .observed(*observed_at)
+                .with_confidence(*confidence)
+                .with_partial(*partial)
+                .with_listening_mode(*listening_mode)
+                .with_warnings(warnings),
+        )
+        .into_response(),
+        Some(Reading { status: None, .. }) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorPayload::with_version("AirPods not found", version).with_code("not_found")),
+        )
+            .into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorPayload::with_version(
+                "No scan has completed yet",
+                version,
+            )),
+        )
+            .into_response(),
+    }
+}
+
+async fn status_stream(
+    State(recent): State<Recent>,
+    Query(query): Query<VersionQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<ErrorPayload>)> {
+    let version = query.resolve()?;
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok()?.parse().ok());
+    let (replay, rx) = recent.subscribe(last_event_id);
+    // A client that fell too far behind is disconnected rather than skipped
+    // ahead, so it reconnects with `Last-Event-ID` and gets the replay
+    let live = BroadcastStream::new(rx)
+        .take_while(Result::is_ok)
+        .filter_map(Result::ok);
+    let events = tokio_stream::iter(replay)
+        .chain(live)
+        .filter_map(move |(id, reading)| {
+            let event = match &reading.status {
+                Some(status) => Event::default()
+                    .event("status")
+                    .json_data(
+                        StatusPayload::with_version(status.clone(), version)
+                            .with_name(reading.name.clone())
+                            .observed(reading.observed_at)
+                            .with_confidence(reading.confidence)
+                            .with_partial(reading.partial)
+                            .with_listening_mode(reading.listening_mode)
+                            .with_warnings(&reading.warnings),
+                    )
+                    .ok()?,
+                None => Event::default().event("not_found").data(""),
+            };
+            Some(Ok(event.id(id.to_string())))
+        });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct BadgeQuery {
+    component: Option<String>,
+}
+
+async fn badge(State(readings): State<Readings>, Query(query): Query<BadgeQuery>) -> Response {
+    let (message, color) = match &*readings.borrow() {
+        Some(Reading {
+            status: Some(status),
+            ..
+        }) => {
+            let components = status.components().iter();
+            let component = match &query.component {
+                Some(name) => components
+                    .filter(|c| &c.name == name)
+                    .min_by_key(|c| c.battery),
+                None => components.min_by_key(|c| c.battery),
+            };
+            match component {
+                Some(component) => (
+                    format!(
+                        "{}%{}",
+                        component.battery,
+                        if component.charging { " ⚡" } else { "" }
+                    ),
+                    badge_color(component.battery),
+                ),
+                None => ("n/a".to_string(), BADGE_GREY),
+            }
+        }
+        Some(Reading { status: None, .. }) => ("not found".to_string(), BADGE_GREY),
+        None => ("scanning".to_string(), BADGE_GREY),
+    };
+    let label = query.component.as_deref().unwrap_or("AirPods");
+    (
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        badge_svg(label, &message, color),
+    )
+        .into_response()
+}
+
+const BADGE_GREY: &str = "#9f9f9f";
+
+fn badge_color(battery: u8) -> &'static str {
+    match battery {
+        0..=20 => "#e05d44",
+        21..=50 => "#dfb317",
+        _ => "#4c1",
+    }
+}
+
+/// Flat two-part badge in the shields.io style, sized from an approximate
+/// 7px per character in 11px Verdana
+fn badge_svg(label: &str, message: &str, color: &str) -> String {
+    let label = escape_xml(label);
+    let message = escape_xml(message);
+    let text_width = |text: &str| text.chars().count() as u32 * 7 + 10;
+    let label_width = text_width(&label);
+    let message_width = text_width(&message);
+    let width = label_width + message_width;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}"><title>{label}: {message}</title><linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient><clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath><g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g><g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11"><text x="{label_x}" y="14">{label}</text><text x="{message_x}" y="14">{message}</text></g></svg>"##,
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+async fn page(State(readings): State<Readings>) -> Response {
+    let (title, body) = match &*readings.borrow() {
+        Some(Reading {
+            status: Some(status),
+            name,
+            ..
+        }) => {
+            let title = name.clone().unwrap_or_else(|| status.model().to_string());
+            let rows: String = status
+                .components()
+                .iter()
+                .map(|component| {
+                    format!(
+                        r#"<div class="row"><span>{}</span><span style="color:{}">{}%{}</span></div>"#,
+                        escape_xml(&capitalize(&component.name)),
+                        badge_color(component.battery),
+                        component.battery,
+                        if component.charging { " ⚡" } else { "" }
+                    )
+                })
+                .collect();
+            (title, rows)
+        }
+        Some(Reading { status: None, .. }) => (
+            "AirPods".to_string(),
+            r#"<p class="note">not found</p>"#.to_string(),
+        ),
+        None => (
+            "AirPods".to_string(),
+            r#"<p class="note">scanning</p>"#.to_string(),
+        ),
+    };
+    (
+        [(header::CACHE_CONTROL, "no-cache")],
+        Html(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta http-equiv="refresh" content="{refresh}">
+<title>{title}</title>
+<style>
+body {{ margin: 0; min-height: 100vh; display: flex; flex-direction: column; justify-content: center; align-items: center; background: #111; color: #eee; font-family: system-ui, sans-serif; }}
+h1 {{ font-weight: normal; font-size: 6vmin; margin: 0 0 4vmin; }}
+.row {{ display: flex; justify-content: space-between; gap: 8vmin; font-size: 10vmin; }}
+.note {{ font-size: 8vmin; color: #9f9f9f; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+"#,
+            // A reload between scans at the default interval shows each one
+            refresh = DEFAULT_INTERVAL_SECS,
+            title = escape_xml(&title),
+        )),
+    )
+        .into_response()
+}
+
+async fn hass(State(readings): State<Readings>) -> Json<HassPayload> {
+    Json(match &*readings.borrow() {
+        Some(reading) => HassPayload::new(reading.status.as_ref(), reading.name.clone()),
+        None => HassPayload::default(),
+    })
+}
+
+async fn fleet(State(diagnostics): State<DiagnosticsRx>) -> Json<FleetStatus> {
+    Json(FleetStatus::new(&diagnostics.borrow()))
+}
+
+async fn prometheus(
+    State(readings): State<Readings>,
+    State(diagnostics): State<DiagnosticsRx>,
+) -> Response {
+    let mut text = match &*readings.borrow() {
+        Some(reading) => metrics::battery(reading.status.as_ref(), reading.observed_at),
+        None => metrics::battery(None, std::time::SystemTime::now()),
+    };
+    text.push_str(&metrics::daemon(&diagnostics.borrow()));
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text).into_response()
+}
+
+/// "left" to "Left"
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
@@ -0,0 +1,61 @@
+//! BLE scanning and the long-running services built on top of it.
+//!
+//! Everything that needs a Bluetooth stack or the tokio runtime lives here,
+//! keeping `podpower-core` usable by parser-only consumers.
+
+#[cfg(all(feature = "aap", target_os = "linux"))]
+pub mod aap;
+pub mod calibration;
+pub mod control;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+#[cfg(feature = "notify")]
+pub mod desktop;
+pub mod events;
+pub mod fingerprint;
+pub mod fleet;
+pub mod gatt;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "history")]
+pub mod health;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod hooks;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod metrics;
+pub mod monitor;
+#[cfg(feature = "notify")]
+pub mod notify;
+pub mod preflight;
+pub mod scan;
+pub mod simulate;
+pub mod statsd;
+#[cfg(feature = "upower")]
+pub mod upower;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+pub use monitor::{AbsentPolicy, Monitor, PowerProfile, Reading};
+pub use scan::{
+    Advertisement, Bluetooth, Device, RetryPolicy, ScanError, ScanOptions, ScanTally, Scanner,
+    Sighting, SightingFilter, Verdict, scan_all, scan_all_on, scan_for_airpods, scan_for_sighting,
+    scan_on, scan_with,
+};
+
+/// `$XDG_DATA_HOME/podpower` (falling back to `~/.local/share/podpower`) on
+/// Unix, `%LOCALAPPDATA%\podpower` on Windows
+pub fn data_dir() -> Option<std::path::PathBuf> {
+    use std::path::PathBuf;
+
+    #[cfg(unix)]
+    let dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        });
+    #[cfg(windows)]
+    let dir = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    Some(dir?.join("podpower"))
+}
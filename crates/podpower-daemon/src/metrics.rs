@@ -0,0 +1,150 @@
+//! Prometheus exposition format: the battery gauges of `--format
+//! prom-textfile`, and the daemon's own metrics that `GET /metrics` serves
+//! next to them, to alert when the daemon silently stops seeing the AirPods.
+
+use crate::monitor::Diagnostics;
+use podpower_core::AirPodsStatus;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// ```text
+/// # HELP airpods_battery_percent Battery level of an AirPods component.
+/// # TYPE airpods_battery_percent gauge
+/// airpods_battery_percent{model="AirPods Pro",component="left"} 85
+/// ...
+/// airpods_found 1
+/// ```
+///
+/// Only `airpods_found 0` when `status` is `None`, so the levels of the last
+/// sighting don't linger. The textfile collector rejects samples with
+/// timestamps, so when the reading was taken is a metric of its own.
+pub fn battery(status: Option<&AirPodsStatus>, observed_at: SystemTime) -> String {
+    let mut text = String::new();
+    if let Some(status) = status {
+        let model = escape_label(status.model());
+        family(
+            &mut text,
+            "airpods_battery_percent",
+            "gauge",
+            "Battery level of an AirPods component.",
+        );
+        for component in status.components() {
+            let _ = writeln!(
+                text,
+                "airpods_battery_percent{{model=\"{}\",component=\"{}\"}} {}",
+                model,
+                escape_label(&component.name),
+                component.battery
+            );
+        }
+        family(
+            &mut text,
+            "airpods_charging",
+            "gauge",
+            "Whether an AirPods component is charging.",
+        );
+        for component in status.components() {
+            let _ = writeln!(
+                text,
+                "airpods_charging{{model=\"{}\",component=\"{}\"}} {}",
+                model,
+                escape_label(&component.name),
+                u8::from(component.charging)
+            );
+        }
+        family(
+            &mut text,
+            "airpods_observed_timestamp_seconds",
+            "gauge",
+            "When the advertisement was received.",
+        );
+        let _ = writeln!(
+            text,
+            "airpods_observed_timestamp_seconds {}",
+            seconds(observed_at)
+        );
+    }
+    family(
+        &mut text,
+        "airpods_found",
+        "gauge",
+        "Whether the scan found AirPods.",
+    );
+    let _ = writeln!(text, "airpods_found {}", u8::from(status.is_some()));
+    text
+}
+
+/// The monitor's counters and timestamps, see [`Diagnostics`]
+pub fn daemon(diagnostics: &Diagnostics) -> String {
+    let mut text = String::new();
+    let counters = [
+        (
+            "podpower_scans_total",
+            "Scans since the daemon started, failed ones included.",
+            diagnostics.scans,
+        ),
+        (
+            "podpower_scan_errors_total",
+            "Scans that failed, e.g. with the adapter off or gone.",
+            diagnostics.scan_errors,
+        ),
+        (
+            "podpower_advertisements_decoded_total",
+            "Advertisements a decoder read a status from, accepted or not.",
+            diagnostics.advertisements_decoded,
+        ),
+        (
+            "podpower_parse_failures_total",
+            "Advertisements of the enabled vendors no decoder could read.",
+            diagnostics.parse_failures,
+        ),
+    ];
+    for (name, help, value) in counters {
+        family(&mut text, name, "counter", help);
+        let _ = writeln!(text, "{} {}", name, value);
+    }
+    let timestamps = [
+        (
+            "podpower_start_time_seconds",
+            "When the daemon started.",
+            Some(diagnostics.started_at),
+        ),
+        (
+            "podpower_last_scan_timestamp_seconds",
+            "When the latest scan finished, successfully or not.",
+            diagnostics.last_scan,
+        ),
+        (
+            "podpower_last_seen_timestamp_seconds",
+            "When AirPods were last found.",
+            diagnostics.last_sighting,
+        ),
+    ];
+    // Left out until they happen, rather than claiming 1970
+    for (name, help, time) in timestamps {
+        if let Some(time) = time {
+            family(&mut text, name, "gauge", help);
+            let _ = writeln!(text, "{} {}", name, seconds(time));
+        }
+    }
+    text
+}
+
+fn family(text: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, kind);
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Label values must escape backslashes, double quotes and line feeds
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
@@ -0,0 +1,625 @@
+use crate::calibration::Calibration;
+use crate::events::DEFAULT_LOW_BATTERY;
+use crate::fingerprint::Fingerprint;
+use crate::fleet::{DeviceName, Fleet, Retention};
+use crate::scan::{
+    Advertisement, ScanOptions, Sighting, Verdict, adapter_info, connected_devices, scan_all,
+    scan_with, watch_connections,
+};
+use podpower_core::{AirPodsStatus, ListeningMode};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{Notify, watch};
+use tokio::time::sleep;
+
+pub const DEFAULT_INTERVAL_SECS: u64 = 10;
+/// Devices not heard from for this long drop out of [`Diagnostics::devices`];
+/// AirPods rotate their address every few minutes
+const DEVICE_EXPIRY: Duration = Duration::from_secs(10 * 60);
+/// Wait between "continuous" scans, so a scan that finds the AirPods right
+/// away doesn't restart the adapter in a tight loop
+const CONTINUOUS: Duration = Duration::from_secs(1);
+/// How long after a charging change scans stay tightened, so plugging in
+/// or unplugging the case shows up promptly in both pods and the case
+const SETTLING: Duration = Duration::from_secs(60);
+/// Wait before listening for connections again after the Bluetooth stack
+/// went away or wasn't there
+const RECONNECT: Duration = Duration::from_secs(30);
+
+/// How eagerly to scan, trading fresh levels for the host's battery
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerProfile {
+    /// Every three intervals, every interval while the battery is low or
+    /// charging just changed
+    Low,
+    /// Every interval, continuously while the battery is low or charging
+    /// just changed
+    #[default]
+    Balanced,
+    /// Continuously
+    Realtime,
+}
+
+impl PowerProfile {
+    /// Wait between two scans; `urgent` while a component is low or its
+    /// charging state just changed
+    pub fn wait(self, interval: Duration, urgent: bool) -> Duration {
+        match (self, urgent) {
+            (PowerProfile::Low, false) => interval * 3,
+            (PowerProfile::Low, true) | (PowerProfile::Balanced, false) => interval,
+            (PowerProfile::Balanced, true) | (PowerProfile::Realtime, _) => CONTINUOUS,
+        }
+    }
+}
+
+/// Scanning less while no AirPods are around, so a daemon left running
+/// overnight doesn't keep the radio in discovery
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbsentPolicy {
+    /// How long without a sighting before backing off
+    pub after: Duration,
+    /// The wait between scans doubles while backing off, up to this
+    pub max_wait: Duration,
+    /// Instead of backing off, stop scanning until a device connects to
+    /// this host
+    pub sleep: bool,
+}
+
+impl Default for AbsentPolicy {
+    fn default() -> Self {
+        Self {
+            after: Duration::from_secs(10 * 60),
+            max_wait: Duration::from_secs(5 * 60),
+            sleep: false,
+        }
+    }
+}
+
+impl std::fmt::Display for PowerProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PowerProfile::Low => "low",
+            PowerProfile::Balanced => "balanced",
+            PowerProfile::Realtime => "realtime",
+        })
+    }
+}
+
+/// Result of one completed scan
+#[derive(Debug, Clone)]
+pub struct Reading {
+    /// `None` when the scan finished without finding AirPods
+    pub status: Option<AirPodsStatus>,
+    /// Signal strength of the advertisement in dBm, when reported
+    pub rssi: Option<i16>,
+    /// Whether the AirPods are connected to this host
+    pub connected: bool,
+    /// See [`Sighting::name`]
+    pub name: Option<String>,
+    /// See [`Sighting::confidence`]
+    pub confidence: Option<u8>,
+    /// See [`Sighting::partial`]
+    pub partial: bool,
+    /// See [`Sighting::listening_mode`]
+    pub listening_mode: Option<ListeningMode>,
+    /// See [`Sighting::data`]
+    pub data: Vec<u8>,
+    /// See [`Sighting::warnings`]
+    pub warnings: Vec<String>,
+    /// See [`Sighting::fingerprint`]
+    pub fingerprint: Option<Fingerprint>,
+    pub observed_at: SystemTime,
+}
+
+/// What the monitor has been up to, for `podpower daemon status`
+#[derive(Debug, Clone)]
+pub struct Diagnostics {
+    pub started_at: SystemTime,
+    pub interval: Duration,
+    pub profile: PowerProfile,
+    /// The wait after the latest scan, per [`PowerProfile::wait`] and the
+    /// [`AbsentPolicy`]
+    pub next_wait: Duration,
+    /// When AirPods were last found, `None` before the first time
+    pub last_sighting: Option<SystemTime>,
+    /// Backing off per the [`AbsentPolicy`]
+    pub backing_off: bool,
+    /// Not scanning until a device connects, per [`AbsentPolicy::sleep`]
+    pub sleeping: bool,
+    pub gatt_fallback: bool,
+    /// The adapter of the latest scan, see [`adapter_info`]
+    pub adapter: Option<String>,
+    /// When the latest scan finished, successfully or not
+    pub last_scan: Option<SystemTime>,
+    /// Why the latest scan failed, `None` when it succeeded
+    pub last_error: Option<String>,
+    /// The latest advertisement of every device heard from recently
+    pub devices: Vec<Device>,
+    /// Scans since the daemon started, failed ones included
+    pub scans: u64,
+    /// Scans that failed, e.g. with the adapter off or gone
+    pub scan_errors: u64,
+    /// Advertisements a decoder read a status from, whether or not it was
+    /// accepted. An advertisement repeated within a scan counts once.
+    pub advertisements_decoded: u64,
+    /// Advertisements of the enabled vendors no decoder could read
+    pub parse_failures: u64,
+    /// Every pair heard from recently, `None` without
+    /// [`Monitor::with_fleet`]
+    pub fleet: Option<Fleet>,
+}
+
+/// A device advertising through one of the enabled decoders
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub address: String,
+    /// [`podpower_core::VendorDecoder::name`] of the decoder that matched
+    pub vendor: &'static str,
+    /// `None` unless the advertisement was accepted
+    pub model: Option<String>,
+    pub rssi: Option<i16>,
+    pub connected: bool,
+    pub last_seen: SystemTime,
+}
+
+/// Pauses and resumes a [`Monitor`]'s scanning, e.g. while another BLE
+/// peripheral needs the adapter to itself. The latest reading is kept while
+/// paused.
+#[derive(Debug, Clone)]
+pub struct ScanSwitch(Arc<watch::Sender<bool>>);
+
+impl ScanSwitch {
+    /// Stop scanning after the scan in progress, if any. Returns whether
+    /// scanning was running.
+    pub fn pause(&self) -> bool {
+        !self.0.send_replace(true)
+    }
+
+    /// Scan again right away. Returns whether scanning was paused.
+    pub fn resume(&self) -> bool {
+        self.0.send_replace(false)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.0.borrow()
+    }
+}
+
+/// Periodically scans for AirPods and publishes the latest reading, as
+/// often as the [`PowerProfile`] says, and right away when a device
+/// connects to this host.
+///
+/// Subscribers are only woken when the status itself changes; the `rssi`,
+/// `connected` and `observed_at` fields of the current reading are
+/// refreshed silently on every scan.
+pub struct Monitor {
+    interval: Duration,
+    profile: PowerProfile,
+    absent: Option<AbsentPolicy>,
+    scan_options: ScanOptions,
+    calibration: Option<watch::Receiver<Vec<Calibration>>>,
+    low_threshold: Option<watch::Receiver<u8>>,
+    fleet: Option<watch::Receiver<Vec<DeviceName>>>,
+    tx: watch::Sender<Option<Reading>>,
+    diagnostics: watch::Sender<Diagnostics>,
+    switch: ScanSwitch,
+    connected: Arc<Notify>,
+}
+
+impl Monitor {
+    pub fn new(interval: Duration) -> Self {
+        let (tx, _) = watch::channel(None);
+        let scan_options = ScanOptions::default();
+        let (diagnostics, _) = watch::channel(Diagnostics {
+            started_at: SystemTime::now(),
+            interval,
+            profile: PowerProfile::default(),
+            next_wait: interval,
+            last_sighting: None,
+            backing_off: false,
+            sleeping: false,
+            gatt_fallback: scan_options.gatt_fallback,
+            adapter: None,
+            last_scan: None,
+            last_error: None,
+            devices: Vec::new(),
+            scans: 0,
+            scan_errors: 0,
+            advertisements_decoded: 0,
+            parse_failures: 0,
+            fleet: None,
+        });
+        Self {
+            interval,
+            profile: PowerProfile::default(),
+            absent: Some(AbsentPolicy::default()),
+            scan_options,
+            calibration: None,
+            low_threshold: None,
+            fleet: None,
+            tx,
+            diagnostics,
+            switch: ScanSwitch(Arc::new(watch::channel(false).0)),
+            connected: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn with_scan_options(mut self, scan_options: ScanOptions) -> Self {
+        self.diagnostics
+            .send_modify(|d| d.gatt_fallback = scan_options.gatt_fallback);
+        self.scan_options = scan_options;
+        self
+    }
+
+    /// Take [`ScanOptions::calibration`] from `calibration` before every
+    /// scan, so it can change while the monitor runs
+    pub fn with_calibration(mut self, calibration: watch::Receiver<Vec<Calibration>>) -> Self {
+        self.calibration = Some(calibration);
+        self
+    }
+
+    /// Scan as often as the profile allows while a component is at or below
+    /// the level in `low_threshold` and not charging, read before every wait
+    /// so it can change while the monitor runs. Without it, that level is
+    /// [`DEFAULT_LOW_BATTERY`].
+    pub fn with_low_threshold(mut self, low_threshold: watch::Receiver<u8>) -> Self {
+        self.low_threshold = Some(low_threshold);
+        self
+    }
+
+    /// Keep track of every pair in range in [`Diagnostics::fleet`], named
+    /// by `names` and kept per `retention`, rather than only the one most
+    /// likely the user's. Scans then always listen for their full length.
+    pub fn with_fleet(
+        mut self,
+        names: watch::Receiver<Vec<DeviceName>>,
+        retention: Retention,
+    ) -> Self {
+        self.diagnostics
+            .send_modify(|d| d.fleet = Some(Fleet::new(retention)));
+        self.fleet = Some(names);
+        self
+    }
+
+    pub fn with_power_profile(mut self, profile: PowerProfile) -> Self {
+        self.diagnostics.send_modify(|d| d.profile = profile);
+        self.profile = profile;
+        self
+    }
+
+    /// `None` to keep scanning as usual however long the AirPods are away
+    pub fn with_absent_policy(mut self, absent: Option<AbsentPolicy>) -> Self {
+        self.absent = absent;
+        self
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Option<Reading>> {
+        self.tx.subscribe()
+    }
+
+    pub fn switch(&self) -> ScanSwitch {
+        self.switch.clone()
+    }
+
+    /// Updated after every scan
+    pub fn diagnostics(&self) -> watch::Receiver<Diagnostics> {
+        self.diagnostics.subscribe()
+    }
+
+    pub async fn run(mut self) {
+        // Made-up AirPods need no Bluetooth stack
+        let simulated = self.scan_options.simulate.is_some();
+        if !simulated {
+            tokio::spawn(wake_on_connect(self.connected.clone()));
+        }
+        let mut paused = self.switch.0.subscribe();
+        let mut charging_changed_at: Option<Instant> = None;
+        // Starting up counts as a sighting, so the daemon scans normally at
+        // first
+        let mut last_sighting = Instant::now();
+        let mut backoff: Option<Duration> = None;
+        loop {
+            if *paused.borrow_and_update() {
+                tracing::info!("Scanning paused");
+                // The sender lives in `self`, so this can't fail
+                let _ = paused.wait_for(|paused| !paused).await;
+                tracing::info!("Scanning resumed");
+            }
+
+            // Only look the adapter up again when it may have changed
+            let lookup = {
+                let d = self.diagnostics.borrow();
+                d.adapter.is_none() || d.last_error.is_some()
+            };
+            if lookup && !simulated {
+                let adapter = adapter_info().await.ok().flatten();
+                self.diagnostics.send_modify(|d| d.adapter = adapter);
+            }
+
+            if let Some(calibration) = &self.calibration {
+                self.scan_options.calibration = calibration.borrow().clone();
+            }
+            let mut advertisements = Vec::new();
+            let observe =
+                |advertisement: &Advertisement| advertisements.push(advertisement.clone());
+            let result = match &self.fleet {
+                Some(names) => scan_all(&self.scan_options, observe)
+                    .await
+                    .map(|sightings| {
+                        let names = names.borrow().clone();
+                        self.diagnostics.send_modify(|d| {
+                            if let Some(fleet) = &mut d.fleet {
+                                fleet.update(&sightings, &names, SystemTime::now());
+                            }
+                        });
+                        sightings.into_iter().next()
+                    }),
+                None => scan_with(&self.scan_options, observe).await,
+            };
+            self.record(
+                &advertisements,
+                result.as_ref().err().map(|e| e.to_string()),
+            );
+            match result {
+                Ok(sighting) => {
+                    if sighting.is_some() {
+                        last_sighting = Instant::now();
+                        backoff = None;
+                        self.diagnostics
+                            .send_modify(|d| d.last_sighting = Some(SystemTime::now()));
+                    }
+                    let previous = self.tx.borrow().as_ref().and_then(|r| r.status.clone());
+                    let current = sighting.as_ref().map(|s| &s.status);
+                    if charging_changed(previous.as_ref(), current) {
+                        charging_changed_at = Some(Instant::now());
+                    }
+                    self.publish(sighting)
+                }
+                Err(e) => tracing::warn!("Scan failed: {}", e),
+            }
+
+            let settling = charging_changed_at.is_some_and(|at| at.elapsed() < SETTLING);
+            let threshold = self
+                .low_threshold
+                .as_ref()
+                .map_or(DEFAULT_LOW_BATTERY, |threshold| *threshold.borrow());
+            let low = self
+                .tx
+                .borrow()
+                .as_ref()
+                .and_then(|r| r.status.as_ref())
+                .is_some_and(|status| is_low(status, threshold));
+            let mut wait = self.profile.wait(self.interval, settling || low);
+
+            match self.absent {
+                Some(absent) if last_sighting.elapsed() >= absent.after && absent.sleep => {
+                    self.sleep_until_connected().await;
+                    last_sighting = Instant::now();
+                    continue;
+                }
+                Some(absent) if last_sighting.elapsed() >= absent.after => {
+                    if backoff.is_none() {
+                        tracing::info!(
+                            "No AirPods for {}s, scanning less often",
+                            absent.after.as_secs()
+                        );
+                    }
+                    let next = backoff
+                        .map_or(wait * 2, |backoff| backoff * 2)
+                        .min(absent.max_wait)
+                        .max(wait);
+                    backoff = Some(next);
+                    wait = next;
+                }
+                _ => {}
+            }
+            let backing_off = backoff.is_some();
+            self.diagnostics.send_modify(|d| {
+                d.next_wait = wait;
+                d.backing_off = backing_off;
+            });
+            tokio::select! {
+                () = sleep(wait) => {}
+                () = self.connected.notified() => {
+                    tracing::debug!("A device connected, scanning now");
+                    last_sighting = Instant::now();
+                    backoff = None;
+                }
+            }
+        }
+    }
+
+    /// Wait until a device connects to this host. This only asks the
+    /// Bluetooth stack which devices are connected, which doesn't need the
+    /// radio.
+    async fn sleep_until_connected(&self) {
+        tracing::info!("No AirPods around, not scanning until a device connects");
+        self.diagnostics.send_modify(|d| {
+            d.sleeping = true;
+            d.backing_off = false;
+        });
+        let mut before = connected_devices().await.unwrap_or_default();
+        loop {
+            tokio::select! {
+                () = sleep(self.interval) => {}
+                () = self.connected.notified() => {
+                    tracing::info!("A device connected, scanning again");
+                    break;
+                }
+            }
+            match connected_devices().await {
+                Ok(now) if now.iter().any(|name| !before.contains(name)) => {
+                    tracing::info!(devices = ?now, "A device connected, scanning again");
+                    break;
+                }
+                Ok(now) => before = now,
+                Err(e) => tracing::debug!("Listing connected devices failed: {}", e),
+            }
+        }
+        self.diagnostics.send_modify(|d| d.sleeping = false);
+    }
+
+    fn record(&self, advertisements: &[Advertisement], error: Option<String>) {
+        let now = SystemTime::now();
+        self.diagnostics.send_modify(|d| {
+            d.last_scan = Some(now);
+            d.scans += 1;
+            if error.is_some() {
+                d.scan_errors += 1;
+            }
+            d.last_error = error;
+            for advertisement in advertisements {
+                if let Verdict::Rejected(_) = advertisement.verdict {
+                    d.parse_failures += 1;
+                } else {
+                    d.advertisements_decoded += 1;
+                }
+                let model = match &advertisement.verdict {
+                    Verdict::Accepted(status) => Some(status.model().to_string()),
+                    _ => None,
+                };
+                let device = Device {
+                    address: advertisement.address.clone(),
+                    vendor: advertisement.vendor,
+                    model,
+                    rssi: advertisement.rssi,
+                    connected: advertisement.connected,
+                    last_seen: now,
+                };
+                match d
+                    .devices
+                    .iter_mut()
+                    .find(|d| d.address == device.address && d.vendor == device.vendor)
+                {
+                    Some(known) => *known = device,
+                    None => d.devices.push(device),
+                }
+            }
+            d.devices.retain(|device| {
+                now.duration_since(device.last_seen)
+                    .is_ok_and(|age| age < DEVICE_EXPIRY)
+            });
+        });
+    }
+
+    fn publish(&self, sighting: Option<Sighting>) {
+        let observed_at = SystemTime::now();
+        let reading = match sighting {
+            Some(sighting) => Reading {
+                status: Some(sighting.status),
+                rssi: sighting.rssi,
+                connected: sighting.connected,
+                name: sighting.name,
+                confidence: sighting.confidence,
+                partial: sighting.partial,
+                listening_mode: sighting.listening_mode,
+                data: sighting.data,
+                warnings: sighting.warnings,
+                fingerprint: Some(sighting.fingerprint),
+                observed_at,
+            },
+            None => Reading {
+                status: None,
+                rssi: None,
+                connected: false,
+                name: None,
+                confidence: None,
+                partial: false,
+                listening_mode: None,
+                data: Vec::new(),
+                warnings: Vec::new(),
+                fingerprint: None,
+                observed_at,
+            },
+        };
+        log_transition(
+            self.tx.borrow().as_ref().and_then(|r| r.status.as_ref()),
+            reading.status.as_ref(),
+        );
+        self.tx.send_if_modified(|current| {
+            let changed = current.as_ref().map(|r| &r.status) != Some(&reading.status);
+            *current = Some(reading);
+            changed
+        });
+    }
+}
+
+/// Wake the monitor whenever a device connects, see [`watch_connections`]
+async fn wake_on_connect(connected: Arc<Notify>) {
+    loop {
+        match watch_connections(|| connected.notify_one()).await {
+            Ok(()) => tracing::debug!("Connection events ended"),
+            Err(e) => tracing::debug!("Listening for connections failed: {}", e),
+        }
+        sleep(RECONNECT).await;
+    }
+}
+
+/// Whether a component started or stopped charging between two scans that
+/// both found the AirPods
+fn charging_changed(previous: Option<&AirPodsStatus>, current: Option<&AirPodsStatus>) -> bool {
+    let (Some(previous), Some(current)) = (previous, current) else {
+        return false;
+    };
+    current.components().iter().any(|component| {
+        previous
+            .components()
+            .iter()
+            .any(|c| c.name == component.name && c.charging != component.charging)
+    })
+}
+
+/// Log the AirPods showing up, going away, and components starting or
+/// stopping to charge, with the details as fields for structured logs like
+/// the journal's
+fn log_transition(previous: Option<&AirPodsStatus>, current: Option<&AirPodsStatus>) {
+    match (previous, current) {
+        (None, Some(status)) => tracing::info!(
+            model = status.model(),
+            battery = status.battery(),
+            "{} found",
+            status.model()
+        ),
+        (Some(status), None) => {
+            tracing::info!(model = status.model(), "{} gone", status.model())
+        }
+        (Some(previous), Some(current)) => {
+            for component in current.components() {
+                let Some(before) = previous
+                    .components()
+                    .iter()
+                    .find(|c| c.name == component.name)
+                else {
+                    continue;
+                };
+                if before.charging != component.charging {
+                    tracing::info!(
+                        model = current.model(),
+                        component = component.name.as_str(),
+                        battery = component.battery,
+                        charging = component.charging,
+                        "{} {} charging at {}%",
+                        component.name,
+                        if component.charging {
+                            "started"
+                        } else {
+                            "stopped"
+                        },
+                        component.battery
+                    );
+                }
+            }
+        }
+        (None, None) => {}
+    }
+}
+
+/// A component is at or below `threshold` and not charging
+fn is_low(status: &AirPodsStatus, threshold: u8) -> bool {
+    status
+        .components()
+        .iter()
+        .any(|c| !c.charging && c.battery <= threshold)
+}
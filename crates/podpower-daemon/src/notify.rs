@@ -0,0 +1,292 @@
+//! Low-battery push notifications through ntfy, Gotify and Pushover, and
+//! on the desktop of this host.
+
+use crate::events::{
+    Baselines, DEFAULT_FAST_DRAIN, DEFAULT_LOW_BATTERY, Event, EventTracker, Snooze,
+};
+use crate::monitor::Reading;
+use podpower_core::{AirPodsStatus, Component};
+use serde::{Deserialize, Deserializer};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+type Readings = watch::Receiver<Option<Reading>>;
+
+const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The `[notifications]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Alert when a component that isn't charging drops to this level
+    #[serde(default = "default_low_battery")]
+    pub low_battery: u8,
+    /// Don't alert while the earbuds are in the closed case
+    #[serde(default)]
+    pub quiet_when_stored: bool,
+    /// Alert about a low component once per discharge, rather than again
+    /// whenever its level rises above the threshold and drops back
+    #[serde(default)]
+    pub once_per_discharge: bool,
+    /// Alert when a component finishes charging, to take it off the
+    /// charger
+    #[serde(default)]
+    pub charged: bool,
+    /// Least time between two alerts of the same kind about a component
+    #[serde(default)]
+    pub cooldown: Cooldowns,
+    /// Alert when a component drains this many times faster than usual, 0
+    /// to never alert
+    #[serde(default = "default_fast_drain")]
+    pub fast_drain: f64,
+    #[serde(default)]
+    pub targets: Vec<Target>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            low_battery: DEFAULT_LOW_BATTERY,
+            quiet_when_stored: false,
+            once_per_discharge: false,
+            charged: false,
+            cooldown: Cooldowns::default(),
+            fast_drain: DEFAULT_FAST_DRAIN,
+            targets: Vec::new(),
+        }
+    }
+}
+
+impl NotifyConfig {
+    fn tracker(&self) -> EventTracker {
+        EventTracker::new(self.low_battery)
+            .with_quiet_when_stored(self.quiet_when_stored)
+            .with_once_per_discharge(self.once_per_discharge)
+            .with_fast_drain(self.fast_drain)
+    }
+}
+
+/// The `[notifications.cooldown]` section: per kind of alert, a duration
+/// like `"1h"`. Alerts within it of the previous one about the same
+/// component are dropped.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cooldowns {
+    #[serde(default, deserialize_with = "duration")]
+    pub low: Duration,
+    #[serde(default, deserialize_with = "duration")]
+    pub fast_drain: Duration,
+    #[serde(default, deserialize_with = "duration")]
+    pub charged: Duration,
+}
+
+impl Cooldowns {
+    fn of(&self, event: &Event) -> Duration {
+        match event {
+            Event::Low { .. } => self.low,
+            Event::FastDrain { .. } => self.fast_drain,
+            Event::Charged { .. } => self.charged,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    humantime::parse_duration(&text).map_err(serde::de::Error::custom)
+}
+
+fn default_low_battery() -> u8 {
+    DEFAULT_LOW_BATTERY
+}
+
+fn default_fast_drain() -> f64 {
+    DEFAULT_FAST_DRAIN
+}
+
+/// Where to push alerts, selected by `service = "..."`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "service", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Target {
+    /// Topic URL, e.g. `https://ntfy.sh/my-airpods`
+    Ntfy { url: String, token: Option<String> },
+    /// Server URL and application token
+    Gotify { url: String, token: String },
+    /// Application token and user key
+    Pushover { token: String, user: String },
+    /// This host's notifications, see [`crate::desktop`]
+    Desktop,
+}
+
+/// Push an alert to every target when a component drops to the low-battery
+/// level. Each component alerts once, and again only after it has charged
+/// or risen above the level. Components draining unusually fast alert once
+/// per discharge, measured against `baselines` to begin with, see
+/// [`EventTracker`]. With `charged`, components that reach 100% on the
+/// charger alert too, once until they drop below 90%. Alerts within their
+/// [`Cooldowns`] of the previous one, and all of them while `snooze` is on,
+/// are dropped. Changes to `config` apply from the next status on.
+pub async fn serve_notify(
+    mut config: watch::Receiver<NotifyConfig>,
+    mut readings: Readings,
+    snooze: Snooze,
+    baselines: Baselines,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("podpower/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let mut tracker = config
+        .borrow_and_update()
+        .tracker()
+        .with_baselines(baselines);
+    // When each kind of alert was last sent about each component
+    let mut sent: HashMap<(&'static str, String), Instant> = HashMap::new();
+
+    while readings.changed().await.is_ok() {
+        if config.has_changed().unwrap_or(false) {
+            tracker.reconfigure(config.borrow_and_update().tracker());
+        }
+        let Some((status, fingerprint, observed_at)) =
+            readings.borrow_and_update().as_ref().map(|reading| {
+                (
+                    reading.status.clone(),
+                    reading.fingerprint.clone(),
+                    reading.observed_at,
+                )
+            })
+        else {
+            continue;
+        };
+
+        for event in tracker.update(status.as_ref(), fingerprint.as_ref(), observed_at) {
+            let Some(status) = &status else {
+                continue;
+            };
+            let Some(component) = event
+                .component()
+                .and_then(|name| status.components().iter().find(|c| c.name == name))
+            else {
+                continue;
+            };
+            let message = match event {
+                Event::Low { .. } => low_message(status, component),
+                Event::FastDrain { rate, usual, .. } => Message {
+                    title: format!("{} draining fast", status.model()),
+                    body: format!(
+                        "{} is losing {}% an hour, usually {}%. At {}% now",
+                        capitalize(&component.name),
+                        rate,
+                        usual,
+                        component.battery
+                    ),
+                },
+                Event::Charged { .. } if config.borrow().charged => Message {
+                    title: format!("{} fully charged", capitalize(&component.name)),
+                    body: format!("{} {} at 100%, unplug it", status.model(), component.name),
+                },
+                _ => continue,
+            };
+            if snooze.is_snoozed() {
+                tracing::debug!("Snoozed, dropping {}", message.title);
+                continue;
+            }
+            let cooldown = config.borrow().cooldown.of(&event);
+            let key = (event.name(), component.name.clone());
+            if sent.get(&key).is_some_and(|at| at.elapsed() < cooldown) {
+                tracing::debug!("Cooling down, dropping {}", message.title);
+                continue;
+            }
+            sent.insert(key, Instant::now());
+            let targets = config.borrow().targets.clone();
+            for target in &targets {
+                if let Err(e) = send(&client, target, &message).await {
+                    tracing::warn!("Notification via {} failed: {}", target.service(), e);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Message {
+    title: String,
+    body: String,
+}
+
+fn low_message(status: &AirPodsStatus, component: &Component) -> Message {
+    Message {
+        title: format!("{} battery low", status.model()),
+        body: format!(
+            "{} is at {}%",
+            capitalize(&component.name),
+            component.battery
+        ),
+    }
+}
+
+async fn send(
+    client: &reqwest::Client,
+    target: &Target,
+    message: &Message,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let request = match target {
+        Target::Ntfy { url, token } => {
+            let request = client
+                .post(url)
+                .header("Title", &message.title)
+                .header("Priority", "high")
+                .header("Tags", "battery")
+                .body(message.body.clone());
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        }
+        Target::Gotify { url, token } => client
+            .post(format!("{}/message", url.trim_end_matches('/')))
+            .header("X-Gotify-Key", token)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(
+                json!({ "title": message.title, "message": message.body, "priority": 8 })
+                    .to_string(),
+            ),
+        Target::Pushover { token, user } => client
+            .post(PUSHOVER_URL)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(
+                json!({
+                    "token": token,
+                    "user": user,
+                    "title": message.title,
+                    "message": message.body,
+                })
+                .to_string(),
+            ),
+        Target::Desktop => return crate::desktop::show(&message.title, &message.body).await,
+    };
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+impl Target {
+    fn service(&self) -> &'static str {
+        match self {
+            Target::Ntfy { .. } => "ntfy",
+            Target::Gotify { .. } => "Gotify",
+            Target::Pushover { .. } => "Pushover",
+            Target::Desktop => "desktop",
+        }
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
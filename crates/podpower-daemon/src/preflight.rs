@@ -0,0 +1,121 @@
+//! Checks before a scan for what the OS needs to let podpower use
+//! Bluetooth, so that a blocked radio or a missing permission is reported as
+//! what to do about it rather than whatever btleplug makes of it.
+//!
+//! Scans go through BlueZ on Linux, CoreBluetooth on macOS and WinRT on
+//! Windows, none of which needs root or `CAP_NET_ADMIN`. macOS and Windows
+//! can't be asked for their permissions without prompting, so there only
+//! [`permission_hint`] helps once the OS refused.
+
+use crate::scan::ScanError;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+
+/// Fail early for what would otherwise fail the scan with a less helpful
+/// error: on Linux a radio blocked with rfkill or no system bus to reach
+/// BlueZ on
+pub fn check() -> Result<(), ScanError> {
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(hardware) = rfkill_block(Path::new("/sys/class/rfkill")) {
+            return Err(ScanError::Blocked { hardware });
+        }
+        if !system_bus_available() {
+            return Err(ScanError::Unavailable(
+                "no D-Bus system bus to reach BlueZ on".into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether every Bluetooth radio under `dir` (`/sys/class/rfkill`) is
+/// blocked, and if so whether by a hardware switch rather than software.
+/// `None` when one isn't, or there are none to tell.
+#[cfg(target_os = "linux")]
+pub fn rfkill_block(dir: &Path) -> Option<bool> {
+    let read = |path: &Path, name: &str| {
+        std::fs::read_to_string(path.join(name))
+            .map(|text| text.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut hardware = false;
+    let mut radios = 0;
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if read(&path, "type") != "bluetooth" {
+            continue;
+        }
+        radios += 1;
+        let hard = read(&path, "hard") == "1";
+        if !hard && read(&path, "soft") != "1" {
+            return None;
+        }
+        hardware |= hard;
+    }
+    (radios > 0).then_some(hardware)
+}
+
+#[cfg(target_os = "linux")]
+fn system_bus_available() -> bool {
+    std::env::var_os("DBUS_SYSTEM_BUS_ADDRESS").is_some()
+        || [
+            "/run/dbus/system_bus_socket",
+            "/var/run/dbus/system_bus_socket",
+        ]
+        .iter()
+        .any(|path| Path::new(path).exists())
+}
+
+/// What to do about [`ScanError::PermissionDenied`] on this platform
+pub fn permission_hint() -> String {
+    #[cfg(target_os = "linux")]
+    {
+        linux_permission_hint(
+            &std::fs::read_to_string("/proc/self/status").unwrap_or_default(),
+            &std::fs::read_to_string("/etc/group").unwrap_or_default(),
+        )
+    }
+    #[cfg(target_os = "macos")]
+    {
+        "Grant Bluetooth permission to your terminal (or to podpower, for the daemon) in System Settings → Privacy & Security → Bluetooth, then restart it".to_string()
+    }
+    #[cfg(windows)]
+    {
+        "Let apps use Bluetooth in Settings → Privacy & security → Radios, and check that Bluetooth is on in Settings → Bluetooth & devices".to_string()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    {
+        "Check that your user may use Bluetooth".to_string()
+    }
+}
+
+/// The Linux hint for a process whose `/proc/self/status` is `status` on a
+/// system whose `/etc/group` is `groups`: joining the `bluetooth` group
+/// where BlueZ's D-Bus policy has one, otherwise the policy itself
+#[cfg(target_os = "linux")]
+pub fn linux_permission_hint(status: &str, groups: &str) -> String {
+    let bluetooth_gid = groups.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != "bluetooth" {
+            return None;
+        }
+        fields.nth(1)?.parse::<u32>().ok()
+    });
+    let own_gids: Vec<u32> = status
+        .lines()
+        .find_map(|line| line.strip_prefix("Groups:"))
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|gid| gid.parse().ok())
+        .collect();
+    let hint = match bluetooth_gid {
+        Some(gid) if !own_gids.contains(&gid) => {
+            "Add your user to the `bluetooth` group (sudo usermod -aG bluetooth $USER), then log out and back in"
+        }
+        _ => {
+            "BlueZ's D-Bus policy in /etc/dbus-1/system.d/bluetooth.conf doesn't let your user in; in a Flatpak, allow talking to org.bluez on the system bus"
+        }
+    };
+    hint.to_string()
+}
@@ -0,0 +1,1022 @@
+use crate::calibration::{Calibration, calibrate};
+use crate::fingerprint::{Fingerprint, KnownPairs, confidence, merge};
+use crate::gatt::read_battery_service;
+use crate::simulate::{Scenario, Simulator};
+use btleplug::api::bleuuid::BleUuid;
+use btleplug::api::{
+    BDAddr, Central, CentralEvent, CentralState, Manager as _, PeripheralProperties, ScanFilter,
+};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use podpower_core::payload::ScanStats;
+use podpower_core::{
+    AdvertisementData, AirPodsStatus, AppleDecoder, Decoded, ListeningMode, ParseError,
+    VendorDecoder, Warning, default_decoders,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+use tracing::{debug, instrument, trace, warn};
+
+pub const SCAN_TIMEOUT_SECS: u64 = 3;
+const POLL_INTERVAL_MS: u64 = 100; // Check for new devices every 100ms
+const MIN_RSSI_THRESHOLD: i16 = -60; // Only consider devices with RSSI > -60 dBm (strong signal = nearby/connected)
+
+/// How often to retry acquiring the adapter and starting the scan. BlueZ
+/// and WinRT fail these transiently for a few seconds after resume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub retry: RetryPolicy,
+    /// Decoders to try on every advertisement, in order
+    pub decoders: Vec<Arc<dyn VendorDecoder>>,
+    /// When no advertisement matched, read the GATT Battery Service of
+    /// connected devices instead, see [`crate::gatt`]
+    pub gatt_fallback: bool,
+    pub filter: SightingFilter,
+    /// Where to remember the pairs seen connected to this host, see
+    /// [`KnownPairs`]. Without it, [`Sighting::confidence`] can't count
+    /// earlier sightings.
+    pub known_pairs: Option<PathBuf>,
+    /// When nothing passed, settle for the best advertisement that didn't:
+    /// one with a weak signal, or one only [`VendorDecoder::decode_partial`]
+    /// could read. See [`Sighting::partial`].
+    pub partial: bool,
+    /// Ask connected AirPods for their noise control, see
+    /// [`Scanner::listening_mode`]
+    pub listening_mode: bool,
+    /// Offsets applied to the levels of the devices found, see
+    /// [`crate::calibration`]
+    pub calibration: Vec<Calibration>,
+    /// Scan made-up AirPods instead of the OS Bluetooth stack, see
+    /// [`crate::simulate`]
+    pub simulate: Option<Scenario>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            retry: RetryPolicy::default(),
+            decoders: default_decoders(),
+            gatt_fallback: false,
+            filter: SightingFilter::default(),
+            known_pairs: None,
+            partial: false,
+            listening_mode: false,
+            calibration: Vec::new(),
+            simulate: None,
+        }
+    }
+}
+
+/// Why a scan could not be started
+#[derive(Debug)]
+pub enum ScanError {
+    /// There is no Bluetooth adapter (or the OS does not expose one)
+    NoAdapter,
+    /// The adapter exists but is powered off
+    PoweredOff,
+    /// Blocked with rfkill, by a hardware switch when `hardware`, see
+    /// [`crate::preflight`]
+    Blocked {
+        hardware: bool,
+    },
+    /// The OS Bluetooth service can't be reached, e.g. BlueZ isn't running
+    Unavailable(String),
+    /// The OS refused access to Bluetooth
+    PermissionDenied(String),
+    /// Another scan holds the adapter
+    ScanInProgress,
+    Bluetooth(btleplug::Error),
+}
+
+impl ScanError {
+    /// Stable identifier for scripts, e.g. the `code` of JSON errors
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScanError::NoAdapter => "no_adapter",
+            ScanError::PoweredOff => "bluetooth_off",
+            ScanError::Blocked { .. } => "bluetooth_blocked",
+            ScanError::Unavailable(_) => "bluetooth_unavailable",
+            ScanError::PermissionDenied(_) => "permission_denied",
+            ScanError::ScanInProgress => "scan_in_progress",
+            ScanError::Bluetooth(_) => "bluetooth_error",
+        }
+    }
+
+    /// Whether retrying could help, e.g. an adapter that hasn't reappeared
+    /// after resume yet
+    fn is_transient(&self) -> bool {
+        !matches!(
+            self,
+            ScanError::PermissionDenied(_) | ScanError::ScanInProgress | ScanError::Blocked { .. }
+        )
+    }
+
+    fn from_btleplug(e: btleplug::Error) -> Self {
+        let message = e.to_string();
+        if matches!(e, btleplug::Error::PermissionDenied)
+            || [
+                "AccessDenied",
+                "NotPermitted",
+                "NotAuthorized",
+                "Permission denied",
+                // WinRT's E_ACCESSDENIED, e.g. apps aren't allowed radios
+                "Access is denied",
+            ]
+            .iter()
+            .any(|needle| message.contains(needle))
+        {
+            ScanError::PermissionDenied(message)
+        } else if message.contains("org.bluez.Error.NotReady") {
+            ScanError::PoweredOff
+        } else if message.contains("ServiceUnknown") && message.contains("org.bluez") {
+            ScanError::Unavailable("BlueZ isn't running".into())
+        } else if message.contains("already in progress") {
+            ScanError::ScanInProgress
+        } else {
+            ScanError::Bluetooth(e)
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::NoAdapter => write!(f, "No Bluetooth adapters found"),
+            ScanError::PoweredOff => {
+                write!(f, "Bluetooth is turned off. Turn it on and try again")
+            }
+            ScanError::Blocked { hardware: false } => {
+                write!(f, "Bluetooth is blocked. Try: rfkill unblock bluetooth")
+            }
+            ScanError::Blocked { hardware: true } => write!(
+                f,
+                "Bluetooth is switched off by a hardware switch or key. Turn it on and try again"
+            ),
+            ScanError::Unavailable(detail) => write!(
+                f,
+                "Bluetooth isn't available ({}). Try: sudo systemctl start bluetooth",
+                detail
+            ),
+            ScanError::PermissionDenied(detail) => write!(
+                f,
+                "Not allowed to use Bluetooth ({}). {}",
+                detail,
+                crate::preflight::permission_hint()
+            ),
+            ScanError::ScanInProgress => write!(
+                f,
+                "Bluetooth scan already in progress. Try: sudo systemctl restart bluetooth"
+            ),
+            ScanError::Bluetooth(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScanError::Bluetooth(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The Bluetooth stack a scan runs on: [`Bluetooth`], or a stand-in for
+/// [`scan_on`] to drive
+pub trait Scanner: Send + Sync {
+    type Device: Device;
+
+    /// Start discovery. Failures that [`ScanError`] deems transient are
+    /// retried per [`ScanOptions::retry`].
+    fn start_scan(&self) -> impl Future<Output = Result<(), ScanError>> + Send;
+
+    /// Every device discovered so far
+    fn devices(&self) -> impl Future<Output = Result<Vec<Self::Device>, btleplug::Error>> + Send;
+
+    /// Stop discovery. The future doesn't borrow the scanner, so a scan
+    /// that is dropped midway can still stop in the background.
+    fn stop_scan(&self) -> impl Future<Output = Result<(), btleplug::Error>> + Send + 'static;
+
+    /// See [`ScanOptions::gatt_fallback`]. Finds nothing unless implemented.
+    fn read_battery_service(
+        &self,
+    ) -> impl Future<Output = Result<Option<Sighting>, Box<dyn std::error::Error + Send + Sync>>> + Send
+    {
+        async { Ok(None) }
+    }
+
+    /// The noise control of the connected pair `sighting` is of. Unknown
+    /// unless implemented.
+    fn listening_mode(
+        &self,
+        sighting: &Sighting,
+    ) -> impl Future<
+        Output = Result<Option<ListeningMode>, Box<dyn std::error::Error + Send + Sync>>,
+    > + Send {
+        let _ = sighting;
+        async { Ok(None) }
+    }
+}
+
+/// A device discovered by a [`Scanner`]
+pub trait Device: Send + Sync {
+    /// Its latest advertisement, `None` before the first one
+    fn properties(
+        &self,
+    ) -> impl Future<Output = Result<Option<PeripheralProperties>, btleplug::Error>> + Send;
+
+    fn is_connected(&self) -> impl Future<Output = Result<bool, btleplug::Error>> + Send;
+}
+
+impl Device for Peripheral {
+    async fn properties(&self) -> Result<Option<PeripheralProperties>, btleplug::Error> {
+        btleplug::api::Peripheral::properties(self).await
+    }
+
+    async fn is_connected(&self) -> Result<bool, btleplug::Error> {
+        btleplug::api::Peripheral::is_connected(self).await
+    }
+}
+
+/// The OS Bluetooth stack, scanning on its first adapter
+#[derive(Default)]
+pub struct Bluetooth {
+    /// The adapter the scan started on
+    adapter: Mutex<Option<Adapter>>,
+}
+
+impl Bluetooth {
+    fn adapter(&self) -> Option<Adapter> {
+        self.adapter.lock().unwrap().clone()
+    }
+}
+
+impl Scanner for Bluetooth {
+    type Device = Peripheral;
+
+    async fn start_scan(&self) -> Result<(), ScanError> {
+        let adapter = try_start_scan().await?;
+        *self.adapter.lock().unwrap() = Some(adapter);
+        Ok(())
+    }
+
+    async fn devices(&self) -> Result<Vec<Peripheral>, btleplug::Error> {
+        match self.adapter() {
+            Some(adapter) => adapter.peripherals().await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn stop_scan(&self) -> impl Future<Output = Result<(), btleplug::Error>> + Send + 'static {
+        let adapter = self.adapter();
+        async move {
+            match adapter {
+                Some(adapter) => adapter.stop_scan().await,
+                None => Ok(()),
+            }
+        }
+    }
+
+    async fn read_battery_service(
+        &self,
+    ) -> Result<Option<Sighting>, Box<dyn std::error::Error + Send + Sync>> {
+        match self.adapter() {
+            Some(adapter) => read_battery_service(&adapter).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Over an AAP channel to the connected device of the sighting's
+    /// [`Sighting::name`], see [`crate::aap`]
+    #[cfg(all(feature = "aap", target_os = "linux"))]
+    async fn listening_mode(
+        &self,
+        sighting: &Sighting,
+    ) -> Result<Option<ListeningMode>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(adapter) = self.adapter() else {
+            return Ok(None);
+        };
+        let Some(paired) = &sighting.name else {
+            return Ok(None);
+        };
+        match find_connected(adapter.peripherals().await?, |name| name == paired).await? {
+            Some((_, properties)) => Ok(crate::aap::read_listening_mode(properties.address).await?),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A decoded AirPods advertisement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sighting {
+    pub status: AirPodsStatus,
+    /// The advertising address, which AirPods rotate every few minutes
+    pub address: String,
+    /// Signal strength of the advertisement in dBm, when reported
+    pub rssi: Option<i16>,
+    /// Whether the device is connected to this host, see [`scan_with`]
+    pub connected: bool,
+    /// The name the device was paired under, e.g. "Viktor's AirPods Pro",
+    /// when it is connected to this host, or for AirPods, of the connected
+    /// device whose name says their model, when theirs is the strongest
+    /// signal of that model
+    pub name: Option<String>,
+    pub fingerprint: Fingerprint,
+    /// How likely this is the user's own pair, see
+    /// [`crate::fingerprint::confidence`]. `None` when the scan was pinned
+    /// to an address.
+    pub confidence: Option<u8>,
+    /// A degraded result from an advertisement that failed validation, see
+    /// [`ScanOptions::partial`]
+    pub partial: bool,
+    /// Noise control of a connected pair, with
+    /// [`ScanOptions::listening_mode`]
+    pub listening_mode: Option<ListeningMode>,
+    /// The bytes `status` was decoded from: the advertisement's manufacturer
+    /// or service data, or the GATT Battery Level
+    pub data: Vec<u8>,
+    /// What the decoder had to guess, see [`Decoded::warnings`]
+    pub warnings: Vec<String>,
+}
+
+/// Which pairs a scan is after. Fields left `None` match any pair.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SightingFilter {
+    /// [`AirPodsStatus::model`], ignoring case
+    pub model: Option<String>,
+    /// Advertising address, ignoring case. AirPods rotate theirs every few
+    /// minutes, so this mostly pins other vendors' earbuds.
+    pub address: Option<String>,
+}
+
+impl SightingFilter {
+    pub fn matches(&self, status: &AirPodsStatus, address: &str) -> bool {
+        self.model
+            .as_ref()
+            .is_none_or(|model| model.eq_ignore_ascii_case(status.model()))
+            && self
+                .address
+                .as_ref()
+                .is_none_or(|wanted| wanted.eq_ignore_ascii_case(address))
+    }
+}
+
+pub async fn scan_for_airpods()
+-> Result<Option<AirPodsStatus>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(scan_for_sighting().await?.map(|s| s.status))
+}
+
+/// Like [`scan_for_airpods`], but also returns the advertisement's RSSI
+pub async fn scan_for_sighting()
+-> Result<Option<Sighting>, Box<dyn std::error::Error + Send + Sync>> {
+    scan_with(&ScanOptions::default(), |_| {}).await
+}
+
+/// A supported vendor's advertisement seen during a scan, reported by
+/// [`scan_with`]
+#[derive(Debug, Clone)]
+pub struct Advertisement {
+    pub address: String,
+    pub rssi: Option<i16>,
+    /// [`VendorDecoder::name`] of the decoder that looked at it
+    pub vendor: &'static str,
+    /// The vendor's manufacturer or service data
+    pub data: Vec<u8>,
+    /// Whether the device is connected to this host, see [`scan_with`]
+    pub connected: bool,
+    pub verdict: Verdict,
+}
+
+/// What the scan made of an [`Advertisement`]
+#[derive(Debug, Clone)]
+pub enum Verdict {
+    Accepted(AirPodsStatus),
+    /// Below [`MIN_RSSI_THRESHOLD`], probably someone else's AirPods
+    WeakSignal(AirPodsStatus),
+    /// Decoded, but not the model or address [`ScanOptions::filter`] asks for
+    Filtered(AirPodsStatus),
+    Rejected(ParseError),
+}
+
+/// Counts the [`Advertisement`]s a scan reports into [`ScanStats`], e.g.
+/// from [`scan_with`]'s `observe`
+#[derive(Debug, Default)]
+pub struct ScanTally {
+    advertisements: u32,
+    apple_advertisements: u32,
+    /// Whether any advertisement of the address was accepted
+    devices: HashMap<String, bool>,
+}
+
+impl ScanTally {
+    pub fn observe(&mut self, advertisement: &Advertisement) {
+        self.advertisements += 1;
+        if advertisement.vendor == AppleDecoder::NAME {
+            self.apple_advertisements += 1;
+        }
+        let accepted = matches!(advertisement.verdict, Verdict::Accepted(_));
+        *self
+            .devices
+            .entry(advertisement.address.clone())
+            .or_default() |= accepted;
+    }
+
+    /// The counts so far, for a scan that ran for `duration`
+    pub fn stats(&self, duration: Duration) -> ScanStats {
+        ScanStats {
+            duration_ms: duration.as_millis() as u64,
+            advertisements: self.advertisements,
+            apple_advertisements: self.apple_advertisements,
+            devices_considered: self.devices.len() as u32,
+            devices_rejected: self.devices.values().filter(|accepted| !**accepted).count() as u32,
+        }
+    }
+}
+
+/// Like [`scan_for_sighting`], calling `observe` once for every distinct
+/// advertisement (per address and payload) of the enabled vendors seen along
+/// the way
+///
+/// Several pairs can be in range, e.g. a family member's, so a pair connected
+/// to this host is preferred over the others. An advertisement counts as
+/// connected when its own peripheral is. AirPods advertise from a random
+/// address rather than the one they are connected with, so for them the
+/// name of a connected device, e.g. "Alex's AirPods Pro", goes to the
+/// strongest pair of the model it names (see [`Sighting::name`]), which is
+/// preferred next. Then the strongest signal wins. While any device is
+/// connected, the scan keeps looking for a connected pair until it times
+/// out. Only BlueZ reports connections btleplug didn't make itself, so
+/// elsewhere the signal strength decides.
+///
+/// Failures to start the scan are returned as a [`ScanError`].
+pub async fn scan_with(
+    options: &ScanOptions,
+    observe: impl FnMut(&Advertisement) + Send,
+) -> Result<Option<Sighting>, Box<dyn std::error::Error + Send + Sync>> {
+    match options.simulate {
+        Some(scenario) => scan_on(&Simulator::new(scenario), options, observe).await,
+        None => scan_on(&Bluetooth::default(), options, observe).await,
+    }
+}
+
+/// Like [`scan_with`], on `scanner` instead of the OS Bluetooth stack
+#[instrument(level = "debug", name = "scan", skip_all)]
+pub async fn scan_on(
+    scanner: &impl Scanner,
+    options: &ScanOptions,
+    observe: impl FnMut(&Advertisement) + Send,
+) -> Result<Option<Sighting>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut pass = collect(scanner, options, observe, true).await?;
+    pass.sightings.truncate(1);
+    read_listening_modes(scanner, options, &mut pass.sightings).await;
+    if let Some(sighting) = pass.sightings.into_iter().next() {
+        return Ok(Some(sighting));
+    }
+    if options.gatt_fallback
+        && let Some(sighting) = scanner
+            .read_battery_service()
+            .await?
+            .filter(|s| options.filter.matches(&s.status, &s.address))
+    {
+        return Ok(Some(calibrate(sighting, &options.calibration)));
+    }
+    Ok(pass.partial)
+}
+
+/// Like [`scan_with`], but listens for the whole scan and returns every pair
+/// in range, most likely the user's first
+pub async fn scan_all(
+    options: &ScanOptions,
+    observe: impl FnMut(&Advertisement) + Send,
+) -> Result<Vec<Sighting>, Box<dyn std::error::Error + Send + Sync>> {
+    match options.simulate {
+        Some(scenario) => scan_all_on(&Simulator::new(scenario), options, observe).await,
+        None => scan_all_on(&Bluetooth::default(), options, observe).await,
+    }
+}
+
+/// Like [`scan_all`], on `scanner` instead of the OS Bluetooth stack
+#[instrument(level = "debug", name = "scan", skip_all)]
+pub async fn scan_all_on(
+    scanner: &impl Scanner,
+    options: &ScanOptions,
+    observe: impl FnMut(&Advertisement) + Send,
+) -> Result<Vec<Sighting>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut pass = collect(scanner, options, observe, false).await?;
+    if !pass.sightings.is_empty() {
+        read_listening_modes(scanner, options, &mut pass.sightings).await;
+        return Ok(pass.sightings);
+    }
+    if options.gatt_fallback
+        && let Some(sighting) = scanner
+            .read_battery_service()
+            .await?
+            .filter(|s| options.filter.matches(&s.status, &s.address))
+    {
+        return Ok(vec![calibrate(sighting, &options.calibration)]);
+    }
+    Ok(pass.partial.into_iter().collect())
+}
+
+/// Fill in [`Sighting::listening_mode`] of the connected pairs, with
+/// [`ScanOptions::listening_mode`]
+async fn read_listening_modes(
+    scanner: &impl Scanner,
+    options: &ScanOptions,
+    sightings: &mut [Sighting],
+) {
+    if !options.listening_mode {
+        return;
+    }
+    for sighting in sightings.iter_mut().filter(|s| s.name.is_some()) {
+        match scanner.listening_mode(sighting).await {
+            Ok(listening_mode) => sighting.listening_mode = listening_mode,
+            Err(e) => debug!("could not read the listening mode: {}", e),
+        }
+    }
+}
+
+/// What one scan found
+struct Pass {
+    /// The latest accepted sighting per pair, connected ones first, then by
+    /// signal strength
+    sightings: Vec<Sighting>,
+    /// With [`ScanOptions::partial`], the strongest advertisement that
+    /// failed validation, when none passed
+    partial: Option<Sighting>,
+}
+
+/// Listen for advertisements until the scan times out, or with `early`,
+/// until the first sighting [`scan_with`] would pick for sure
+async fn collect(
+    scanner: &impl Scanner,
+    options: &ScanOptions,
+    mut observe: impl FnMut(&Advertisement) + Send,
+    early: bool,
+) -> Result<Pass, Box<dyn std::error::Error + Send + Sync>> {
+    let scan = start_scan(scanner, &options.retry).await?;
+
+    // Poll for AirPods up to SCAN_TIMEOUT_SECS seconds
+    let start = Instant::now();
+    let timeout = Duration::from_secs(SCAN_TIMEOUT_SECS);
+    let poll_interval = Duration::from_millis(POLL_INTERVAL_MS);
+    let mut seen: HashMap<(BDAddr, &str), Vec<u8>> = HashMap::new();
+    let mut connections = Connections::of(scanner.devices().await?).await?;
+    let mut sightings: Vec<Sighting> = Vec::new();
+    let mut partial: Option<Sighting> = None;
+
+    'scan: while start.elapsed() < timeout {
+        let peripherals = scanner.devices().await?;
+
+        for peripheral in peripherals {
+            // One misbehaving device nearby mustn't hide the others
+            let props = match peripheral.properties().await {
+                Ok(Some(props)) => props,
+                Ok(None) => continue,
+                Err(e) => {
+                    debug!("skipping a device whose properties failed: {}", e);
+                    continue;
+                }
+            };
+            let address = props.address;
+            let service_data: HashMap<u16, Vec<u8>> = props
+                .service_data
+                .iter()
+                .filter_map(|(uuid, data)| Some((uuid.to_ble_u16()?, data.clone())))
+                .collect();
+            let advertisement = AdvertisementData {
+                manufacturer_data: &props.manufacturer_data,
+                service_data: &service_data,
+                rssi: props.rssi,
+            };
+            for decoder in &options.decoders {
+                let Some(Decoded {
+                    data,
+                    status,
+                    warnings,
+                }) = decoder.decode(&advertisement)
+                else {
+                    continue;
+                };
+                let vendor = decoder.name();
+                let connected =
+                    status.is_ok() && connections.is_connected(&peripheral, address).await;
+                let name = props.local_name.clone().filter(|_| connected);
+                let verdict = match status {
+                    Ok(status) if !options.filter.matches(&status, &address.to_string()) => {
+                        Verdict::Filtered(status)
+                    }
+                    // Check RSSI - only consider devices with strong signal (likely connected/nearby)
+                    // Connected AirPods typically have RSSI between -30 and -60 dBm
+                    // Distant/disconnected ones are usually below -70 dBm
+                    Ok(status) if props.rssi.is_some_and(|rssi| rssi < MIN_RSSI_THRESHOLD) => {
+                        Verdict::WeakSignal(status)
+                    }
+                    Ok(status) => Verdict::Accepted(status),
+                    Err(e) => Verdict::Rejected(e),
+                };
+                match &verdict {
+                    Verdict::Accepted(status) => {
+                        debug!(%address, vendor, rssi = ?props.rssi, connected, model = status.model(), "found earbuds");
+                        for warning in &warnings {
+                            debug!(%address, vendor, "decoded anyway: {}", warning);
+                        }
+                    }
+                    Verdict::WeakSignal(_) => {
+                        debug!(%address, vendor, rssi = ?props.rssi, "rejected: signal too weak")
+                    }
+                    Verdict::Filtered(status) => {
+                        debug!(%address, vendor, model = status.model(), "rejected: filtered out")
+                    }
+                    Verdict::Rejected(e) => trace!(%address, vendor, "rejected: {}", e),
+                }
+                if seen.get(&(address, vendor)).map(Vec::as_slice) != Some(data) {
+                    seen.insert((address, vendor), data.to_vec());
+                    observe(&Advertisement {
+                        address: address.to_string(),
+                        rssi: props.rssi,
+                        vendor,
+                        data: data.to_vec(),
+                        connected,
+                        verdict: verdict.clone(),
+                    });
+                }
+
+                let status = match verdict {
+                    Verdict::Accepted(status) => status,
+                    Verdict::WeakSignal(status) if options.partial => {
+                        keep_strongest(
+                            &mut partial,
+                            Sighting::degraded(
+                                vendor, status, data, &warnings, address, props.rssi,
+                            ),
+                        );
+                        continue;
+                    }
+                    Verdict::Rejected(_) if options.partial => {
+                        if let Some((status, warnings)) =
+                            decoder.decode_partial(data).filter(|(status, _)| {
+                                options.filter.matches(status, &address.to_string())
+                            })
+                        {
+                            debug!(%address, vendor, model = status.model(), "partially decoded");
+                            keep_strongest(
+                                &mut partial,
+                                Sighting::degraded(
+                                    vendor, status, data, &warnings, address, props.rssi,
+                                ),
+                            );
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let sighting = Sighting {
+                    fingerprint: Fingerprint::of(vendor, &status, data),
+                    status,
+                    address: address.to_string(),
+                    rssi: props.rssi,
+                    connected,
+                    name,
+                    confidence: None,
+                    partial: false,
+                    listening_mode: None,
+                    data: data.to_vec(),
+                    warnings: warnings.iter().map(ToString::to_string).collect(),
+                };
+                // Nothing can beat a connected pair, or anything at all when
+                // no device is connected to tell pairs apart
+                if early && (connected || connections.names.is_empty()) {
+                    sightings = vec![sighting];
+                    break 'scan;
+                }
+                merge(&mut sightings, sighting);
+            }
+        }
+
+        sleep(poll_interval).await;
+    }
+
+    scan.stop().await?;
+    let mut partial = partial.filter(|_| sightings.is_empty());
+    claim_names(&mut sightings, &connections.names);
+    if options.filter.address.is_none() {
+        score(&mut sightings, options.known_pairs.as_deref());
+        score(partial.as_mut_slice(), options.known_pairs.as_deref());
+    }
+    // Stable, so equally likely pairs keep the order they were seen in
+    sightings.sort_by_key(|s| (!s.connected, s.name.is_none(), std::cmp::Reverse(s.rssi)));
+    // Only now, so fingerprints and merging see the levels as advertised
+    let sightings: Vec<Sighting> = sightings
+        .into_iter()
+        .map(|sighting| calibrate(sighting, &options.calibration))
+        .collect();
+    let partial = partial.map(|sighting| calibrate(sighting, &options.calibration));
+    match sightings.first() {
+        Some(sighting) if !sighting.connected && !connections.names.is_empty() => {
+            debug!("scan finished without finding AirPods connected to this host")
+        }
+        Some(_) => {}
+        None if partial.is_some() => {
+            debug!("scan finished with only a partial result")
+        }
+        None => debug!("scan finished without finding AirPods"),
+    }
+    Ok(Pass { sightings, partial })
+}
+
+impl Sighting {
+    /// An advertisement that failed validation, see [`ScanOptions::partial`]
+    fn degraded(
+        vendor: &'static str,
+        status: AirPodsStatus,
+        data: &[u8],
+        warnings: &[Warning],
+        address: BDAddr,
+        rssi: Option<i16>,
+    ) -> Self {
+        Sighting {
+            fingerprint: Fingerprint::of(vendor, &status, data),
+            status,
+            address: address.to_string(),
+            rssi,
+            connected: false,
+            name: None,
+            confidence: None,
+            partial: true,
+            listening_mode: None,
+            data: data.to_vec(),
+            warnings: warnings.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Keep whichever of `best` and `candidate` has the stronger signal
+fn keep_strongest(best: &mut Option<Sighting>, candidate: Sighting) {
+    if best.as_ref().is_none_or(|best| candidate.rssi >= best.rssi) {
+        *best = Some(candidate);
+    }
+}
+
+/// Fill in [`Sighting::confidence`], remembering the connected pairs in the
+/// [`KnownPairs`] at `known_pairs`
+fn score(sightings: &mut [Sighting], known_pairs: Option<&std::path::Path>) {
+    let mut known = match known_pairs.map(KnownPairs::load).transpose() {
+        Ok(known) => known.unwrap_or_default(),
+        Err(e) => {
+            debug!("could not read the known pairs: {}", e);
+            KnownPairs::default()
+        }
+    };
+    let pairs_in_range = sightings.len();
+    for sighting in sightings.iter_mut() {
+        sighting.confidence = Some(confidence(sighting, &known, pairs_in_range));
+        if sighting.connected
+            && known_pairs.is_some()
+            && let Err(e) = known.remember(&sighting.fingerprint)
+        {
+            debug!("could not remember a known pair: {}", e);
+        }
+    }
+}
+
+/// What a scan knows about the devices connected to this host
+struct Connections {
+    /// Names of the connected devices, e.g. "Alex's AirPods Pro"
+    names: Vec<String>,
+    /// Whether the peripheral at each address is connected, asked once per
+    /// scan as every answer is a round trip to the Bluetooth stack
+    known: HashMap<BDAddr, bool>,
+}
+
+impl Connections {
+    async fn of(devices: Vec<impl Device>) -> Result<Self, btleplug::Error> {
+        let mut connections = Self {
+            names: Vec::new(),
+            known: HashMap::new(),
+        };
+        for peripheral in devices {
+            let connected = peripheral.is_connected().await.unwrap_or(false);
+            match peripheral.properties().await {
+                Ok(Some(properties)) => {
+                    connections.known.insert(properties.address, connected);
+                    if connected {
+                        connections.names.extend(properties.local_name);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => debug!("skipping a device whose properties failed: {}", e),
+            }
+        }
+        debug!(names = ?connections.names, "connected devices");
+        Ok(connections)
+    }
+
+    /// Whether `peripheral`, advertising from `address`, is connected
+    async fn is_connected(&mut self, peripheral: &impl Device, address: BDAddr) -> bool {
+        if let Some(connected) = self.known.get(&address) {
+            return *connected;
+        }
+        let connected = peripheral.is_connected().await.unwrap_or(false);
+        self.known.insert(address, connected);
+        connected
+    }
+}
+
+/// Give each connected device's name to the strongest sighting of the
+/// model the name says most closely, unless a sighting is connected under
+/// it already. Names carry no generation: "Alex's AirPods Pro" names
+/// AirPods Pro 2 too, and AirPods 2 only when no AirPods Pro are around.
+fn claim_names(sightings: &mut [Sighting], names: &[String]) {
+    for name in names {
+        if sightings.iter().any(|s| s.name.as_ref() == Some(name)) {
+            continue;
+        }
+        let best = sightings
+            .iter_mut()
+            .filter(|s| s.name.is_none())
+            .filter_map(|s| Some((named_family(name, s.status.model())?.len(), s.rssi, s)))
+            .max_by_key(|(family, rssi, _)| (*family, *rssi));
+        if let Some((_, _, sighting)) = best {
+            sighting.name = Some(name.clone());
+        }
+    }
+}
+
+/// The family of `model`, i.e. without its generation, when the device
+/// name `name` says it
+fn named_family<'a>(name: &str, model: &'a str) -> Option<&'a str> {
+    let family = model
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .trim_end();
+    (!family.is_empty() && name.contains(family)).then_some(family)
+}
+
+/// Start scanning on `scanner`, retrying per `retry`
+async fn start_scan<'a, S: Scanner>(
+    scanner: &'a S,
+    retry: &RetryPolicy,
+) -> Result<ScanGuard<'a, S>, ScanError> {
+    let mut backoff = retry.initial_backoff;
+    let mut attempt = 1;
+    loop {
+        match scanner.start_scan().await {
+            Ok(()) => {
+                return Ok(ScanGuard {
+                    scanner,
+                    stopped: false,
+                });
+            }
+            Err(e) if !e.is_transient() || attempt >= retry.attempts => return Err(e),
+            Err(e) => {
+                warn!(
+                    "Starting the scan failed (attempt {} of {}), retrying in {:?}: {}",
+                    attempt, retry.attempts, backoff, e
+                );
+                sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// A running scan, stopped when dropped so a scan that fails or is cancelled
+/// midway doesn't leave discovery running
+struct ScanGuard<'a, S: Scanner> {
+    scanner: &'a S,
+    stopped: bool,
+}
+
+impl<S: Scanner> ScanGuard<'_, S> {
+    async fn stop(mut self) -> Result<(), btleplug::Error> {
+        self.stopped = true;
+        self.scanner.stop_scan().await
+    }
+}
+
+impl<S: Scanner> Drop for ScanGuard<'_, S> {
+    fn drop(&mut self) {
+        if self.stopped {
+            return;
+        }
+        // Drop can't wait, so stop in the background
+        let stop = self.scanner.stop_scan();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    if let Err(e) = stop.await {
+                        warn!("Stopping the scan failed: {}", e);
+                    }
+                });
+            }
+            Err(_) => warn!("Scan left running: no runtime to stop it on"),
+        }
+    }
+}
+
+/// Names of the devices connected to this host, looked up without scanning
+pub async fn connected_devices() -> Result<Vec<String>, btleplug::Error> {
+    let manager = Manager::new().await?;
+    match manager.adapters().await?.into_iter().next() {
+        Some(adapter) => Ok(Connections::of(adapter.peripherals().await?).await?.names),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Call `on_connect` every time a device connects to this host, until the
+/// Bluetooth stack goes away. BlueZ reports every connection; CoreBluetooth
+/// and WinRT only the ones btleplug made itself.
+pub async fn watch_connections(mut on_connect: impl FnMut() + Send) -> Result<(), ScanError> {
+    let manager = Manager::new().await.map_err(ScanError::from_btleplug)?;
+    let adapter = manager
+        .adapters()
+        .await
+        .map_err(ScanError::from_btleplug)?
+        .into_iter()
+        .next()
+        .ok_or(ScanError::NoAdapter)?;
+    let mut events = adapter.events().await.map_err(ScanError::from_btleplug)?;
+    while let Some(event) = std::future::poll_fn(|cx| events.as_mut().poll_next(cx)).await {
+        if let CentralEvent::DeviceConnected(id) = event {
+            debug!(?id, "device connected");
+            on_connect();
+        }
+    }
+    Ok(())
+}
+
+/// Description of the adapter scans run on, e.g. `hci0 (usb:v1D6Bp0246d0540)`
+/// on BlueZ, `None` when there is no adapter
+pub async fn adapter_info() -> Result<Option<String>, btleplug::Error> {
+    let manager = Manager::new().await?;
+    match manager.adapters().await?.into_iter().next() {
+        Some(adapter) => adapter.adapter_info().await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The name and properties of the first connected device among `devices`
+/// whose name `matches`
+#[cfg(all(feature = "aap", target_os = "linux"))]
+pub(crate) async fn find_connected(
+    devices: Vec<impl Device>,
+    matches: impl Fn(&str) -> bool,
+) -> Result<Option<(String, PeripheralProperties)>, btleplug::Error> {
+    for device in devices {
+        if !device.is_connected().await.unwrap_or(false) {
+            continue;
+        }
+        let Some(properties) = device.properties().await? else {
+            continue;
+        };
+        if let Some(name) = properties.local_name.clone().filter(|name| matches(name)) {
+            return Ok(Some((name, properties)));
+        }
+    }
+    Ok(None)
+}
+
+/// Acquire the first adapter and start scanning on it
+async fn try_start_scan() -> Result<Adapter, ScanError> {
+    crate::preflight::check()?;
+    let manager = Manager::new().await.map_err(ScanError::from_btleplug)?;
+    let adapters = manager.adapters().await.map_err(ScanError::from_btleplug)?;
+
+    // Adapters can take a moment to reappear after resume
+    let adapter = adapters.into_iter().next().ok_or(ScanError::NoAdapter)?;
+    let info = adapter.adapter_info().await.unwrap_or_default();
+    debug!(adapter = %info, "starting scan");
+
+    // Some platforms report Unknown until asked to scan, so only trust an
+    // explicit PoweredOff
+    if adapter.adapter_state().await.ok() == Some(CentralState::PoweredOff) {
+        return Err(ScanError::PoweredOff);
+    }
+    adapter
+        .start_scan(ScanFilter::default())
+        .await
+        .map_err(ScanError::from_btleplug)?;
+    Ok(adapter)
+}
@@ -0,0 +1,155 @@
+//! Made-up AirPods for `--simulate`, to build widgets and try alert rules on
+//! machines without AirPods or Bluetooth. The [`Simulator`] stands in for
+//! the Bluetooth stack and advertises real proximity pairing messages, so
+//! everything from decoding on runs as it would for real AirPods.
+//!
+//! The levels follow the wall clock, moving one 10% step a minute through a
+//! ten minute cycle, so one-off runs a minute apart see them change too.
+
+use crate::scan::{Device, ScanError, Scanner};
+use btleplug::api::{BDAddr, PeripheralProperties};
+use podpower_core::{AIRPODS_DATA_LENGTH, APPLE_MANUFACTURER_ID, PROXIMITY_PAIRING_TYPE};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long each step of a [`Scenario`] lasts
+pub const STEP: Duration = Duration::from_secs(60);
+/// Steps in a cycle, after which a [`Scenario`] starts over
+const STEPS: u64 = 10;
+
+/// The name the simulated pair is connected under
+pub const NAME: &str = "Simulated AirPods Pro";
+
+const AIRPODS_PRO: [u8; 2] = [0x0e, 0x20];
+/// No model has it yet
+const UNKNOWN_MODEL: [u8; 2] = [0x30, 0x20];
+// Status bytes all leave bit 5 clear, which puts the left pod in the high
+// nibbles
+const IN_EARS: u8 = 0x0a;
+const LEFT_IN_EAR: u8 = 0x08;
+const IN_CASE: u8 = 0x04;
+const CHARGING_LEFT: u8 = 0x10;
+const CHARGING_RIGHT: u8 = 0x20;
+const CHARGING_CASE: u8 = 0x40;
+const LID_CLOSED: u8 = 0x08;
+const PLAYING_MUSIC: u8 = 0x05;
+const DISCONNECTED: u8 = 0x0f;
+
+/// What the simulated AirPods go through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scenario {
+    /// In both ears, draining from 100% to 15%, the case at 65%
+    #[default]
+    Draining,
+    /// In the closed case on a charger, charging from 15% until they are
+    /// full
+    Charging,
+    /// Only the left pod in range, draining, with the case
+    OnePodMissing,
+    /// Like [`Scenario::Draining`], but advertising a model id podpower
+    /// doesn't know yet, as a newer model would
+    UnknownModel,
+}
+
+impl Scenario {
+    /// The manufacturer data the AirPods advertise at `at`
+    pub fn frame(self, at: SystemTime) -> Vec<u8> {
+        let secs = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let step = (secs / STEP.as_secs() % STEPS) as u8;
+        let draining = 10 - step;
+        let mut frame = vec![0; AIRPODS_DATA_LENGTH];
+        frame[0] = PROXIMITY_PAIRING_TYPE;
+        frame[1] = (AIRPODS_DATA_LENGTH - 2) as u8;
+        frame[2] = 0x01;
+        frame[3..5].copy_from_slice(&AIRPODS_PRO);
+        match self {
+            Scenario::Draining => {
+                frame[5] = IN_EARS;
+                frame[6] = (draining << 4) | draining;
+                frame[7] = 6;
+                frame[10] = PLAYING_MUSIC;
+            }
+            Scenario::Charging => {
+                let level = (step + 1).min(10);
+                let pods = if level < 10 {
+                    CHARGING_LEFT | CHARGING_RIGHT
+                } else {
+                    0
+                };
+                frame[5] = IN_CASE;
+                frame[6] = (level << 4) | level;
+                frame[7] = pods | CHARGING_CASE | (5 + step / 2);
+                frame[8] = LID_CLOSED;
+            }
+            Scenario::OnePodMissing => {
+                frame[5] = LEFT_IN_EAR;
+                frame[6] = (draining << 4) | DISCONNECTED;
+                frame[7] = 6;
+                frame[10] = PLAYING_MUSIC;
+            }
+            Scenario::UnknownModel => {
+                frame[3..5].copy_from_slice(&UNKNOWN_MODEL);
+                frame[5] = IN_EARS;
+                frame[6] = (draining << 4) | draining;
+                frame[7] = 6;
+                frame[10] = PLAYING_MUSIC;
+            }
+        }
+        frame
+    }
+}
+
+/// A Bluetooth stack with one pair of AirPods in [`Scenario`], connected
+/// to this host as [`NAME`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Simulator {
+    pub scenario: Scenario,
+}
+
+impl Simulator {
+    pub fn new(scenario: Scenario) -> Self {
+        Self { scenario }
+    }
+}
+
+/// The [`Simulator`]'s AirPods
+pub struct SimulatedDevice {
+    properties: PeripheralProperties,
+}
+
+impl Device for SimulatedDevice {
+    async fn properties(&self) -> Result<Option<PeripheralProperties>, btleplug::Error> {
+        Ok(Some(self.properties.clone()))
+    }
+
+    async fn is_connected(&self) -> Result<bool, btleplug::Error> {
+        Ok(true)
+    }
+}
+
+impl Scanner for Simulator {
+    type Device = SimulatedDevice;
+
+    async fn start_scan(&self) -> Result<(), ScanError> {
+        Ok(())
+    }
+
+    async fn devices(&self) -> Result<Vec<SimulatedDevice>, btleplug::Error> {
+        Ok(vec![SimulatedDevice {
+            properties: PeripheralProperties {
+                address: BDAddr::from([0x5e, 0x00, 0x00, 0x00, 0x00, 0x01]),
+                local_name: Some(NAME.to_string()),
+                rssi: Some(-45),
+                manufacturer_data: HashMap::from([(
+                    APPLE_MANUFACTURER_ID,
+                    self.scenario.frame(SystemTime::now()),
+                )]),
+                ..Default::default()
+            },
+        }])
+    }
+
+    fn stop_scan(&self) -> impl Future<Output = Result<(), btleplug::Error>> + Send + 'static {
+        std::future::ready(Ok(()))
+    }
+}
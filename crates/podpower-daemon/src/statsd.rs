@@ -0,0 +1,127 @@
+//! Periodic gauge pushes to StatsD (UDP) and Graphite (plaintext over TCP).
+
+use crate::control::SinkHealth;
+use crate::monitor::Reading;
+use podpower_core::AirPodsStatus;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket, lookup_host};
+use tokio::sync::watch;
+use tokio::time::{MissedTickBehavior, interval};
+
+type Readings = watch::Receiver<Option<Reading>>;
+
+/// Prefix of every metric name, e.g. `podpower.left.battery`
+pub const METRIC_PREFIX: &str = "podpower";
+
+/// Send `podpower.<component>.battery` and `.charging` gauges to the StatsD
+/// server at `target` (`host:port`) every `period` while AirPods are found
+pub async fn serve_statsd(
+    target: &str,
+    readings: Readings,
+    period: Duration,
+    health: SinkHealth,
+) -> io::Result<()> {
+    let addr = resolve(target).await?;
+    let local: SocketAddr = match addr {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await?;
+
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let Some(status) = current_status(&readings) else {
+            continue;
+        };
+        let packet: String = gauges(&status)
+            .map(|(name, value)| format!("{}:{}|g\n", name, value))
+            .collect();
+        match socket.send(packet.as_bytes()).await {
+            Ok(_) => health.succeeded(),
+            Err(e) => {
+                tracing::warn!("StatsD push failed: {}", e);
+                health.failed(e);
+            }
+        }
+    }
+}
+
+/// Send the same gauges to the Graphite plaintext listener at `target`
+/// (`host:port`) every `period`, reconnecting for each push so a restarted
+/// Graphite doesn't need the daemon restarted too
+pub async fn serve_graphite(
+    target: &str,
+    readings: Readings,
+    period: Duration,
+    health: SinkHealth,
+) -> io::Result<()> {
+    // Fail early on an unresolvable address; later failures are only logged.
+    resolve(target).await?;
+
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let Some((status, observed_at)) = readings.borrow().as_ref().and_then(|reading| {
+            let timestamp = reading
+                .observed_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            Some((reading.status.clone()?, timestamp))
+        }) else {
+            continue;
+        };
+        let payload: String = gauges(&status)
+            .map(|(name, value)| format!("{} {} {}\n", name, value, observed_at))
+            .collect();
+        let result = async {
+            let mut stream = TcpStream::connect(target).await?;
+            stream.write_all(payload.as_bytes()).await?;
+            stream.shutdown().await
+        }
+        .await;
+        match result {
+            Ok(()) => health.succeeded(),
+            Err(e) => {
+                tracing::warn!("Graphite push failed: {}", e);
+                health.failed(e);
+            }
+        }
+    }
+}
+
+fn current_status(readings: &Readings) -> Option<AirPodsStatus> {
+    readings.borrow().as_ref()?.status.clone()
+}
+
+/// `(metric name, value)` pairs for every component
+fn gauges(status: &AirPodsStatus) -> impl Iterator<Item = (String, u8)> + '_ {
+    status.components().iter().flat_map(|component| {
+        [
+            (
+                format!("{}.{}.battery", METRIC_PREFIX, component.name),
+                component.battery,
+            ),
+            (
+                format!("{}.{}.charging", METRIC_PREFIX, component.name),
+                u8::from(component.charging),
+            ),
+        ]
+    })
+}
+
+async fn resolve(target: &str) -> io::Result<SocketAddr> {
+    lookup_host(target).await?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("could not resolve {}", target),
+        )
+    })
+}
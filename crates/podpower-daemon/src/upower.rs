@@ -0,0 +1,194 @@
+//! Feed AirPods batteries into UPower through BlueZ.
+//!
+//! UPower has no API for registering external devices, but it mirrors every
+//! BlueZ `org.bluez.Battery1` interface into a native power device. BlueZ in
+//! turn lets external processes supply those batteries through the
+//! `org.bluez.BatteryProviderManager1` API, which is what this module
+//! implements: the AirPods' top-level level is attached to the matching
+//! connected BlueZ device, and from there shows up in GNOME/KDE's power panel
+//! next to the laptop battery.
+//!
+//! The battery provider API is still marked experimental by BlueZ, so
+//! `bluetoothd` must run with `--experimental` (or `Experimental = true` in
+//! `/etc/bluetooth/main.conf`).
+
+use crate::monitor::Reading;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::watch;
+use zbus::fdo::{ObjectManager, ObjectManagerProxy};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+use zbus::{Connection, interface, proxy};
+
+const PROVIDER_ROOT: &str = "/org/podpower/battery_provider";
+const BLUEZ_SERVICE: &str = "org.bluez";
+const BLUEZ_DEVICE_INTERFACE: &str = "org.bluez.Device1";
+/// Device ID profile vendor prefix for Apple (Bluetooth SIG company 0x004C)
+const APPLE_MODALIAS_PREFIX: &str = "bluetooth:v004C";
+
+struct BatteryProvider {
+    device: OwnedObjectPath,
+    percentage: u8,
+}
+
+#[interface(name = "org.bluez.BatteryProvider1")]
+impl BatteryProvider {
+    #[zbus(property)]
+    fn device(&self) -> OwnedObjectPath {
+        self.device.clone()
+    }
+
+    #[zbus(property)]
+    fn percentage(&self) -> u8 {
+        self.percentage
+    }
+
+    #[zbus(property)]
+    fn source(&self) -> String {
+        "podpower".into()
+    }
+}
+
+#[proxy(
+    interface = "org.bluez.BatteryProviderManager1",
+    default_service = "org.bluez"
+)]
+trait BatteryProviderManager {
+    fn register_battery_provider(&self, provider: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+/// A connected BlueZ device that looks like Apple headphones
+struct BluezDevice {
+    path: OwnedObjectPath,
+    adapter: OwnedObjectPath,
+}
+
+/// Publish the latest battery level as a BlueZ battery provider until
+/// `readings` closes.
+pub async fn serve_upower(mut readings: watch::Receiver<Option<Reading>>) -> zbus::Result<()> {
+    let conn = Connection::system().await?;
+    conn.object_server()
+        .at(PROVIDER_ROOT, ObjectManager)
+        .await?;
+
+    let mut registered_adapters: HashSet<OwnedObjectPath> = HashSet::new();
+    let mut exported: HashSet<OwnedObjectPath> = HashSet::new();
+
+    while readings.changed().await.is_ok() {
+        let level = readings
+            .borrow_and_update()
+            .as_ref()
+            .and_then(|r| r.status.as_ref()?.battery());
+
+        let devices = match level {
+            Some(_) => connected_airpods(&conn).await?,
+            None => Vec::new(),
+        };
+
+        let mut current = HashSet::new();
+        for device in &devices {
+            let path = provider_path(&device.path)?;
+            current.insert(path.clone());
+            set_percentage(&conn, &path, &device.path, level.unwrap_or_default()).await?;
+            exported.insert(path);
+
+            if registered_adapters.insert(device.adapter.clone()) {
+                BatteryProviderManagerProxy::builder(&conn)
+                    .path(device.adapter.clone())?
+                    .build()
+                    .await?
+                    .register_battery_provider(&ObjectPath::from_static_str_unchecked(
+                        PROVIDER_ROOT,
+                    ))
+                    .await?;
+            }
+        }
+
+        // Devices that disconnected (or AirPods that went out of range)
+        for stale in exported.difference(&current).cloned().collect::<Vec<_>>() {
+            conn.object_server()
+                .remove::<BatteryProvider, _>(&stale)
+                .await?;
+            exported.remove(&stale);
+        }
+    }
+    Ok(())
+}
+
+async fn set_percentage(
+    conn: &Connection,
+    path: &OwnedObjectPath,
+    device: &OwnedObjectPath,
+    percentage: u8,
+) -> zbus::Result<()> {
+    let server = conn.object_server();
+    match server.interface::<_, BatteryProvider>(path).await {
+        Ok(iface) => {
+            let mut provider = iface.get_mut().await;
+            if provider.percentage != percentage {
+                provider.percentage = percentage;
+                provider.percentage_changed(iface.signal_emitter()).await?;
+            }
+        }
+        Err(_) => {
+            let provider = BatteryProvider {
+                device: device.clone(),
+                percentage,
+            };
+            server.at(path, provider).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Provider object for a BlueZ device, e.g. `/org/bluez/hci0/dev_AA_BB_..`
+/// becomes `/org/podpower/battery_provider/dev_AA_BB_..`
+fn provider_path(device: &OwnedObjectPath) -> zbus::Result<OwnedObjectPath> {
+    let name = device.as_str().rsplit('/').next().unwrap_or_default();
+    Ok(OwnedObjectPath::try_from(format!(
+        "{}/{}",
+        PROVIDER_ROOT, name
+    ))?)
+}
+
+/// Connected BlueZ devices with an Apple Device ID and an audio icon.
+///
+/// The BLE advertisements use rotating random addresses, so the paired
+/// BR/EDR device can't be matched by address; instead this relies on
+/// the pairing being an Apple audio device that is connected right now.
+async fn connected_airpods(conn: &Connection) -> zbus::Result<Vec<BluezDevice>> {
+    let objects = ObjectManagerProxy::builder(conn)
+        .destination(BLUEZ_SERVICE)?
+        .path("/")?
+        .build()
+        .await?
+        .get_managed_objects()
+        .await?;
+
+    let devices = objects
+        .into_iter()
+        .filter_map(|(path, interfaces)| {
+            let props = interfaces
+                .into_iter()
+                .find(|(name, _)| name.as_str() == BLUEZ_DEVICE_INTERFACE)?
+                .1;
+            if !is_connected_apple_audio(&props) {
+                return None;
+            }
+            let adapter = OwnedObjectPath::try_from(props.get("Adapter")?.clone()).ok()?;
+            Some(BluezDevice { path, adapter })
+        })
+        .collect();
+    Ok(devices)
+}
+
+fn is_connected_apple_audio(props: &HashMap<String, OwnedValue>) -> bool {
+    let string = |key: &str| props.get(key).and_then(|v| <&str>::try_from(v).ok());
+    let connected = props
+        .get("Connected")
+        .and_then(|v| bool::try_from(v).ok())
+        .unwrap_or(false);
+
+    connected
+        && string("Modalias").is_some_and(|m| m.starts_with(APPLE_MODALIAS_PREFIX))
+        && string("Icon").is_some_and(|i| i.starts_with("audio-"))
+}
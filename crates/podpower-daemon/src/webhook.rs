@@ -0,0 +1,95 @@
+//! POST the status to a URL every time it changes.
+
+use crate::control::{Response, SinkHealth};
+use crate::monitor::Reading;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::sleep;
+
+type Readings = watch::Receiver<Option<Reading>>;
+
+/// Header carrying `sha256=<hex HMAC of the body>` when a secret is set
+pub const SIGNATURE_HEADER: &str = "X-Podpower-Signature";
+/// Deliveries are retried this many times before giving up on a change
+pub const MAX_RETRIES: u32 = 4;
+/// Delay before the first retry, doubled for each further one
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// POST a JSON [`Response`] to `url` on every status change, the same
+/// payload the query socket returns. Failed deliveries are retried with
+/// exponential backoff and then logged; only a bad client setup is an error.
+pub async fn serve_webhook(
+    url: String,
+    secret: Option<String>,
+    mut readings: Readings,
+    health: SinkHealth,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("podpower/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+
+    while readings.changed().await.is_ok() {
+        let Some(body) = readings
+            .borrow_and_update()
+            .as_ref()
+            .map(|reading| serde_json::to_vec(&Response::from(reading)))
+            .transpose()?
+        else {
+            continue;
+        };
+
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 0..=MAX_RETRIES {
+            match deliver(&client, &url, secret.as_deref(), &body).await {
+                Ok(()) => {
+                    health.succeeded();
+                    break;
+                }
+                Err(e) if attempt == MAX_RETRIES => {
+                    tracing::warn!("Webhook delivery to {} failed, giving up: {}", url, e);
+                    health.failed(e);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Webhook delivery to {} failed, retrying in {}s: {}",
+                        url,
+                        backoff.as_secs(),
+                        e
+                    );
+                    sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    secret: Option<&str>,
+    body: &[u8],
+) -> Result<(), reqwest::Error> {
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_vec());
+    if let Some(secret) = secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret, body));
+    }
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// `sha256=<hex>`, as in GitHub's `X-Hub-Signature-256`
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
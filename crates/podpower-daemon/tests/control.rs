@@ -0,0 +1,60 @@
+//! The query socket's answers, over a real socket.
+#![cfg(unix)]
+
+use podpower_daemon::Monitor;
+use podpower_daemon::control::{Introspection, Response, bind, request, serve_control};
+use podpower_daemon::events::{MAX_SNOOZE, Snooze};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// A query socket of its own for `name`, and the snooze it sets
+async fn serve(name: &str) -> (PathBuf, Snooze) {
+    let path = std::env::temp_dir().join(format!(
+        "podpower-control-{}-{}.sock",
+        std::process::id(),
+        name
+    ));
+    let _ = std::fs::remove_file(&path);
+    let monitor = Monitor::new(Duration::from_secs(60));
+    let snooze = Snooze::default();
+    let introspection = Introspection {
+        diagnostics: monitor.diagnostics(),
+        sinks: watch::channel(Vec::new()).1,
+        switch: monitor.switch(),
+        snooze: snooze.clone(),
+    };
+    let listener = bind(&path).await.unwrap();
+    tokio::spawn(serve_control(
+        listener,
+        watch::channel(None).1,
+        introspection,
+    ));
+    (path, snooze)
+}
+
+#[tokio::test]
+async fn snooze_sets_and_ends_the_snooze() {
+    let (path, snooze) = serve("snooze").await;
+
+    let response = request(&path, "snooze 60").await.unwrap();
+    assert!(matches!(response, Response::Snoozed { until: Some(_) }));
+    assert!(snooze.is_snoozed());
+    let response = request(&path, "snooze 0").await.unwrap();
+    assert!(matches!(response, Response::Snoozed { until: None }));
+    assert!(!snooze.is_snoozed());
+}
+
+#[tokio::test]
+async fn overlong_snooze_is_an_error_not_a_crash() {
+    let (path, snooze) = serve("overlong").await;
+
+    for secs in [u64::MAX, MAX_SNOOZE.as_secs() + 1] {
+        let response = request(&path, &format!("snooze {}", secs)).await.unwrap();
+        assert!(matches!(response, Response::Error { .. }), "{:?}", response);
+    }
+    assert!(!snooze.is_snoozed());
+    // Still answering
+    let response = request(&path, "snooze 60").await.unwrap();
+    assert!(matches!(response, Response::Snoozed { until: Some(_) }));
+}
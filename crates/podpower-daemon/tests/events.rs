@@ -0,0 +1,264 @@
+//! Worn/removed, low-battery, charged and fast-drain transitions of the
+//! event tracker.
+
+use podpower_core::{AirPodsStatus, Component, EarDetection};
+use podpower_daemon::events::{Baselines, Event, EventTracker};
+use podpower_daemon::fingerprint::Fingerprint;
+use std::time::{Duration, SystemTime};
+
+fn airpods(left: bool, right: bool) -> AirPodsStatus {
+    let pod = |name: &str| Component {
+        name: name.to_string(),
+        battery: 80,
+        charging: false,
+        precision: 10,
+        raw: None,
+    };
+    AirPodsStatus::InEar {
+        model: "AirPods Pro".to_string(),
+        battery: Some(80),
+        components: vec![pod("left"), pod("right")],
+        connection_state: None,
+        stored: Some(false),
+        in_ear: Some(EarDetection { left, right }),
+    }
+}
+
+fn worn_or_removed(tracker: &mut EventTracker, status: Option<&AirPodsStatus>) -> Vec<Event> {
+    tracker
+        .update(status, None, SystemTime::now())
+        .into_iter()
+        .filter(|event| matches!(event, Event::Worn { .. } | Event::Removed { .. }))
+        .collect()
+}
+
+fn worn(component: &str) -> Event {
+    Event::Worn {
+        component: component.to_string(),
+    }
+}
+
+fn removed(component: &str) -> Event {
+    Event::Removed {
+        component: component.to_string(),
+    }
+}
+
+#[test]
+fn reports_each_pod_going_in_and_out() {
+    let mut tracker = EventTracker::new(20);
+    assert_eq!(
+        worn_or_removed(&mut tracker, Some(&airpods(false, false))),
+        []
+    );
+    assert_eq!(
+        worn_or_removed(&mut tracker, Some(&airpods(true, false))),
+        [worn("left")]
+    );
+    assert_eq!(
+        worn_or_removed(&mut tracker, Some(&airpods(true, false))),
+        []
+    );
+    assert_eq!(
+        worn_or_removed(&mut tracker, Some(&airpods(false, true))),
+        [worn("right"), removed("left")]
+    );
+}
+
+#[test]
+fn found_already_worn_reports_nothing() {
+    let mut tracker = EventTracker::new(20);
+    assert_eq!(
+        worn_or_removed(&mut tracker, Some(&airpods(true, true))),
+        []
+    );
+    assert_eq!(worn_or_removed(&mut tracker, None), []);
+    assert_eq!(
+        worn_or_removed(&mut tracker, Some(&airpods(true, true))),
+        []
+    );
+}
+
+fn at_level(battery: u8, charging: bool) -> AirPodsStatus {
+    AirPodsStatus::OverEar {
+        model: "AirPods Max".to_string(),
+        battery,
+        components: vec![Component {
+            name: "headphones".to_string(),
+            battery,
+            charging,
+            precision: 10,
+            raw: None,
+        }],
+        on_head: true,
+        plugged_in: charging,
+        connection_state: None,
+    }
+}
+
+fn count(
+    tracker: &mut EventTracker,
+    levels: &[(u8, bool)],
+    counted: impl Fn(&Event) -> bool,
+) -> usize {
+    levels
+        .iter()
+        .map(|(battery, charging)| {
+            tracker
+                .update(
+                    Some(&at_level(*battery, *charging)),
+                    None,
+                    SystemTime::now(),
+                )
+                .iter()
+                .filter(|event| counted(event))
+                .count()
+        })
+        .sum()
+}
+
+fn lows(tracker: &mut EventTracker, levels: &[(u8, bool)]) -> usize {
+    count(tracker, levels, |event| matches!(event, Event::Low { .. }))
+}
+
+fn charged(tracker: &mut EventTracker, levels: &[(u8, bool)]) -> usize {
+    count(tracker, levels, |event| {
+        matches!(event, Event::Charged { .. })
+    })
+}
+
+#[test]
+fn flickering_level_reports_low_again() {
+    let mut tracker = EventTracker::new(20);
+    assert_eq!(
+        lows(&mut tracker, &[(20, false), (30, false), (20, false)]),
+        2
+    );
+}
+
+#[test]
+fn once_per_discharge_waits_for_a_charge() {
+    let mut tracker = EventTracker::new(20).with_once_per_discharge(true);
+    assert_eq!(
+        lows(&mut tracker, &[(20, false), (30, false), (20, false)]),
+        1
+    );
+    assert_eq!(lows(&mut tracker, &[(30, true), (20, false)]), 1);
+}
+
+#[test]
+fn charging_to_full_reports_charged_once() {
+    let mut tracker = EventTracker::new(20);
+    assert_eq!(
+        charged(&mut tracker, &[(90, true), (100, true), (100, true)]),
+        1
+    );
+    // Dropping out of charging as it fills up still counts
+    let mut tracker = EventTracker::new(20);
+    assert_eq!(charged(&mut tracker, &[(90, true), (100, false)]), 1);
+}
+
+#[test]
+fn found_full_off_the_charger_reports_nothing() {
+    let mut tracker = EventTracker::new(20);
+    assert_eq!(charged(&mut tracker, &[(100, false), (100, false)]), 0);
+}
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// Levels from 100% down to 60% a `step` apart, then back on the charger
+/// at `*at`, moved past the discharge, counting `FastDrain` events
+fn discharge(
+    tracker: &mut EventTracker,
+    pair: &Fingerprint,
+    at: &mut SystemTime,
+    step: Duration,
+) -> usize {
+    let mut fast = 0;
+    for (battery, charging) in [
+        (100, false),
+        (90, false),
+        (80, false),
+        (70, false),
+        (60, false),
+        (60, true),
+    ] {
+        fast += tracker
+            .update(Some(&at_level(battery, charging)), Some(pair), *at)
+            .iter()
+            .filter(|event| matches!(event, Event::FastDrain { .. }))
+            .count();
+        *at += step;
+    }
+    fast
+}
+
+fn pair(color: u8) -> Fingerprint {
+    Fingerprint {
+        vendor: "apple".to_string(),
+        model: "AirPods Max".to_string(),
+        color: Some(color),
+    }
+}
+
+#[test]
+fn pairs_in_turn_report_low_and_charged_once_each() {
+    let mut tracker = EventTracker::new(20);
+    let mut events = Vec::new();
+    for _ in 0..3 {
+        for (pair, battery, charging) in [(pair(1), 20, false), (pair(2), 100, true)] {
+            events.extend(tracker.update(
+                Some(&at_level(battery, charging)),
+                Some(&pair),
+                SystemTime::now(),
+            ));
+        }
+    }
+    let lows = events
+        .iter()
+        .filter(|event| matches!(event, Event::Low { .. }))
+        .count();
+    let charged = events
+        .iter()
+        .filter(|event| matches!(event, Event::Charged { .. }))
+        .count();
+    assert_eq!((lows, charged), (1, 1));
+}
+
+#[test]
+fn fast_drain_waits_for_a_baseline() {
+    let mut tracker = EventTracker::new(20);
+    let mut at = SystemTime::UNIX_EPOCH;
+    assert_eq!(discharge(&mut tracker, &pair(1), &mut at, HOUR), 0);
+    assert_eq!(discharge(&mut tracker, &pair(1), &mut at, HOUR / 6), 0);
+    assert_eq!(discharge(&mut tracker, &pair(1), &mut at, HOUR / 6), 1);
+}
+
+#[test]
+fn fast_drain_compares_each_pair_with_itself() {
+    let mut tracker = EventTracker::new(20);
+    let mut at = SystemTime::UNIX_EPOCH;
+    assert_eq!(discharge(&mut tracker, &pair(1), &mut at, HOUR), 0);
+    assert_eq!(discharge(&mut tracker, &pair(1), &mut at, HOUR), 0);
+    assert_eq!(discharge(&mut tracker, &pair(2), &mut at, HOUR / 6), 0);
+    assert_eq!(discharge(&mut tracker, &pair(1), &mut at, HOUR / 6), 1);
+}
+
+#[test]
+fn fast_drain_starts_from_recorded_baselines() {
+    let mut baselines = Baselines::default();
+    let mut at = SystemTime::UNIX_EPOCH;
+    for _ in 0..2 {
+        for (level, charging) in [(100, false), (80, false), (60, false), (60, true)] {
+            baselines.record("AirPods Max", "headphones", level, charging, at);
+            at += HOUR;
+        }
+    }
+    assert_eq!(
+        baselines.usual("AirPods Max", "headphones"),
+        Some((20.0, 2))
+    );
+
+    let mut tracker = EventTracker::new(20).with_baselines(baselines);
+    assert_eq!(discharge(&mut tracker, &pair(1), &mut at, HOUR / 6), 1);
+}
@@ -0,0 +1,117 @@
+//! Folding scans into the fleet of a `--fleet` daemon.
+
+use podpower_core::{AirPodsStatus, Component};
+use podpower_daemon::Sighting;
+use podpower_daemon::fingerprint::Fingerprint;
+use podpower_daemon::fleet::{DeviceName, Fleet, FleetDevice, Retention};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn sighting(color: u8, level: u8, name: Option<&str>) -> Sighting {
+    let pod = |name: &str| Component {
+        name: name.to_string(),
+        battery: level,
+        charging: false,
+        precision: 10,
+        raw: None,
+    };
+    Sighting {
+        status: AirPodsStatus::InEar {
+            model: "AirPods Pro".to_string(),
+            battery: Some(level),
+            components: vec![pod("left"), pod("right")],
+            connection_state: None,
+            stored: None,
+            in_ear: None,
+        },
+        address: format!("00:00:00:00:00:{:02X}", level),
+        rssi: Some(-50),
+        connected: name.is_some(),
+        name: name.map(str::to_string),
+        fingerprint: Fingerprint {
+            vendor: "apple".to_string(),
+            model: "AirPods Pro".to_string(),
+            color: Some(color),
+        },
+        confidence: None,
+        partial: false,
+        listening_mode: None,
+        data: Vec::new(),
+        warnings: Vec::new(),
+    }
+}
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn levels(fleet: &[FleetDevice]) -> Vec<Option<u8>> {
+    fleet.iter().map(|device| device.status.battery()).collect()
+}
+
+#[test]
+fn identical_pairs_are_told_apart_by_their_levels() {
+    let mut fleet = Fleet::default();
+    fleet.update(&[sighting(1, 80, None), sighting(1, 30, None)], &[], at(0));
+    // Both drained a little, seen the other way round
+    fleet.update(&[sighting(1, 20, None), sighting(1, 70, None)], &[], at(60));
+    assert_eq!(levels(&fleet.devices), [Some(70), Some(20)]);
+}
+
+#[test]
+fn pairs_out_of_range_keep_their_last_levels() {
+    let mut fleet = Fleet::default();
+    fleet.update(&[sighting(1, 80, None), sighting(2, 30, None)], &[], at(0));
+    fleet.update(&[sighting(2, 20, None)], &[], at(60));
+    assert_eq!(levels(&fleet.devices), [Some(80), Some(20)]);
+    assert_eq!(fleet.devices[0].last_seen, at(0));
+}
+
+#[test]
+fn configured_names_win_over_paired_ones() {
+    let names = [DeviceName {
+        name: "Shelf pair".to_string(),
+        model: "airpods pro".to_string(),
+        color: Some(2),
+    }];
+    let mut fleet = Fleet::default();
+    fleet.update(
+        &[
+            sighting(1, 80, Some("Maya's AirPods")),
+            sighting(2, 30, None),
+        ],
+        &names,
+        at(0),
+    );
+    // No longer connected, but still known by the name it was paired under
+    fleet.update(&[sighting(1, 70, None)], &names, at(60));
+    let names: Vec<_> = fleet
+        .devices
+        .iter()
+        .map(|device| device.name.as_deref())
+        .collect();
+    assert_eq!(names, [Some("Maya's AirPods"), Some("Shelf pair")]);
+    assert!(!fleet.devices[0].connected);
+}
+
+#[test]
+fn pairs_go_stale_then_are_forgotten() {
+    let mut fleet = Fleet::new(Retention {
+        stale_after: Duration::from_secs(60 * 60),
+        forget_after: Duration::from_secs(24 * 60 * 60),
+    });
+    fleet.update(&[sighting(1, 80, None), sighting(2, 30, None)], &[], at(0));
+    let hour = 60 * 60;
+    fleet.update(&[sighting(2, 20, None)], &[], at(hour));
+    let stale = |fleet: &Fleet, now| {
+        fleet
+            .devices
+            .iter()
+            .map(|device| device.is_stale(&fleet.retention, at(now)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(stale(&fleet, hour), [true, false]);
+
+    fleet.update(&[sighting(2, 10, None)], &[], at(24 * hour));
+    assert_eq!(levels(&fleet.devices), [Some(10)]);
+    assert_eq!(stale(&fleet, 24 * hour), [false]);
+}
@@ -0,0 +1,54 @@
+//! What the preflight makes of a Linux system's rfkill state and groups.
+#![cfg(target_os = "linux")]
+
+use podpower_daemon::preflight::{linux_permission_hint, rfkill_block};
+use std::path::{Path, PathBuf};
+
+/// A fake `/sys/class/rfkill` with one radio per `(type, soft, hard)`
+fn rfkill(name: &str, radios: &[(&str, u8, u8)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("podpower-rfkill-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (i, (kind, soft, hard)) in radios.iter().enumerate() {
+        let radio = dir.join(format!("rfkill{}", i));
+        std::fs::create_dir_all(&radio).unwrap();
+        std::fs::write(radio.join("type"), format!("{}\n", kind)).unwrap();
+        std::fs::write(radio.join("soft"), format!("{}\n", soft)).unwrap();
+        std::fs::write(radio.join("hard"), format!("{}\n", hard)).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn only_blocked_bluetooth_radios_count() {
+    assert_eq!(
+        rfkill_block(&rfkill("soft", &[("wlan", 0, 0), ("bluetooth", 1, 0)])),
+        Some(false)
+    );
+    assert_eq!(
+        rfkill_block(&rfkill("hard", &[("bluetooth", 0, 1)])),
+        Some(true)
+    );
+    assert_eq!(
+        rfkill_block(&rfkill("wlan", &[("wlan", 1, 0), ("bluetooth", 0, 0)])),
+        None
+    );
+    // One usable radio is enough
+    assert_eq!(
+        rfkill_block(&rfkill("one", &[("bluetooth", 1, 0), ("bluetooth", 0, 0)])),
+        None
+    );
+    assert_eq!(rfkill_block(&rfkill("none", &[])), None);
+    assert_eq!(rfkill_block(Path::new("/nonexistent/rfkill")), None);
+}
+
+#[test]
+fn users_outside_the_bluetooth_group_are_told_to_join_it() {
+    let groups = "root:x:0:\nbluetooth:x:112:alex\nalex:x:1000:\n";
+    let outside = linux_permission_hint("Name:\tpodpower\nGroups:\t4 27 1000 \n", groups);
+    let inside = linux_permission_hint("Name:\tpodpower\nGroups:\t112 1000\n", groups);
+    let no_group = linux_permission_hint("Groups:\t1000\n", "root:x:0:\n");
+
+    assert!(outside.contains("usermod -aG bluetooth"));
+    assert!(inside.contains("D-Bus policy"));
+    assert!(no_group.contains("D-Bus policy"));
+}
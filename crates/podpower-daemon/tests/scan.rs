@@ -0,0 +1,520 @@
+//! Scans against a simulated Bluetooth stack: which pair gets picked, how
+//! long the scan listens, and that discovery is stopped again. Time is
+//! paused, so the scan timeout passes instantly.
+
+use btleplug::api::{BDAddr, PeripheralProperties};
+use podpower_core::APPLE_MANUFACTURER_ID;
+use podpower_daemon::calibration::Calibration;
+use podpower_daemon::scan::SCAN_TIMEOUT_SECS;
+use podpower_daemon::{
+    Device, RetryPolicy, ScanError, ScanOptions, ScanTally, Scanner, scan_all_on, scan_on,
+};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// AirPods Pro in the closed case
+const AIRPODS_PRO: &str =
+    "07 19 01 0e 20 55 99 77 08 00 00 c2 4b 8d 31 fa 06 e7 5c 93 20 b8 4e 17 6d a5 f0";
+/// AirPods Pro 2 in both ears, and another pair at other levels
+const AIRPODS_PRO_2: &str =
+    "07 19 01 14 20 2b 99 04 00 05 05 e1 73 09 b6 2a 58 cd 44 f0 1e 97 3b 62 a8 0d 7c";
+const OTHER_AIRPODS_PRO_2: &str =
+    "07 19 01 14 20 2b 55 03 00 05 05 e1 73 09 b6 2a 58 cd 44 f0 1e 97 3b 62 a8 0d 7c";
+/// AirPods 2 in both ears
+const AIRPODS_2: &str =
+    "07 19 01 0f 20 2b 98 04 00 05 05 e1 73 09 b6 2a 58 cd 44 f0 1e 97 3b 62 a8 0d 7c";
+
+/// AirPods 2 in both ears, the left pod's level out of range
+const AIRPODS_2_GARBLED: &str =
+    "07 19 01 0f 20 2b 9c 04 00 05 05 e1 73 09 b6 2a 58 cd 44 f0 1e 97 3b 62 a8 0d 7c";
+
+const TIMEOUT: Duration = Duration::from_secs(SCAN_TIMEOUT_SECS);
+
+fn bytes(hex: &str) -> Vec<u8> {
+    hex.split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+        .collect()
+}
+
+#[derive(Clone)]
+struct MockDevice {
+    properties: PeripheralProperties,
+    connected: bool,
+    /// When, after the scan started, the device is in range
+    visible: Range<Duration>,
+    /// Whether fetching its properties fails
+    flaky: bool,
+    /// How often it was asked whether it is connected
+    connection_checks: Arc<AtomicU32>,
+}
+
+impl MockDevice {
+    /// An AirPods advertisement, in range for the whole scan
+    fn airpods(address: &str, frame: &str, rssi: i16) -> Self {
+        Self {
+            properties: PeripheralProperties {
+                address: address.parse().unwrap(),
+                rssi: Some(rssi),
+                manufacturer_data: HashMap::from([(APPLE_MANUFACTURER_ID, bytes(frame))]),
+                ..Default::default()
+            },
+            connected: false,
+            visible: Duration::ZERO..Duration::MAX,
+            flaky: false,
+            connection_checks: Arc::default(),
+        }
+    }
+
+    /// An advertisement from a device connected to this host under `name`
+    fn paired(self, name: &str) -> Self {
+        Self {
+            properties: PeripheralProperties {
+                local_name: Some(name.to_string()),
+                ..self.properties
+            },
+            connected: true,
+            ..self
+        }
+    }
+
+    /// A connected device that doesn't advertise, like the AirPods' audio
+    /// connection under their public address
+    fn connected(name: &str) -> Self {
+        Self {
+            properties: PeripheralProperties {
+                address: BDAddr::from([0x10, 0x20, 0x30, 0x40, 0x50, 0x60]),
+                local_name: Some(name.to_string()),
+                ..Default::default()
+            },
+            connected: true,
+            visible: Duration::ZERO..Duration::MAX,
+            flaky: false,
+            connection_checks: Arc::default(),
+        }
+    }
+
+    fn visible(self, visible: Range<Duration>) -> Self {
+        Self { visible, ..self }
+    }
+
+    fn flaky(self) -> Self {
+        Self {
+            flaky: true,
+            ..self
+        }
+    }
+}
+
+impl Device for MockDevice {
+    async fn properties(&self) -> Result<Option<PeripheralProperties>, btleplug::Error> {
+        if self.flaky {
+            return Err(btleplug::Error::DeviceNotFound);
+        }
+        Ok(Some(self.properties.clone()))
+    }
+
+    async fn is_connected(&self) -> Result<bool, btleplug::Error> {
+        self.connection_checks.fetch_add(1, Ordering::SeqCst);
+        Ok(self.connected)
+    }
+}
+
+/// A Bluetooth stack with `devices` around it
+#[derive(Default)]
+struct MockScanner {
+    devices: Vec<MockDevice>,
+    /// Why starting the scan fails, every time
+    start_error: Option<fn() -> ScanError>,
+    /// When, after the scan started, the adapter goes away
+    lost_after: Option<Duration>,
+    started_at: Mutex<Option<Instant>>,
+    start_attempts: AtomicU32,
+    stops: Arc<AtomicU32>,
+}
+
+impl MockScanner {
+    fn with_devices(devices: Vec<MockDevice>) -> Self {
+        Self {
+            devices,
+            ..Default::default()
+        }
+    }
+
+    fn failing(start_error: fn() -> ScanError) -> Self {
+        Self {
+            start_error: Some(start_error),
+            ..Default::default()
+        }
+    }
+
+    /// How often the scan was stopped, once the background stop of a
+    /// dropped scan had a chance to run
+    async fn stops(&self) -> u32 {
+        tokio::task::yield_now().await;
+        self.stops.load(Ordering::SeqCst)
+    }
+}
+
+impl Scanner for MockScanner {
+    type Device = MockDevice;
+
+    async fn start_scan(&self) -> Result<(), ScanError> {
+        self.start_attempts.fetch_add(1, Ordering::SeqCst);
+        if let Some(error) = self.start_error {
+            return Err(error());
+        }
+        *self.started_at.lock().unwrap() = Some(Instant::now());
+        Ok(())
+    }
+
+    async fn devices(&self) -> Result<Vec<MockDevice>, btleplug::Error> {
+        let Some(started_at) = *self.started_at.lock().unwrap() else {
+            return Ok(Vec::new());
+        };
+        let elapsed = started_at.elapsed();
+        if self
+            .lost_after
+            .is_some_and(|lost_after| elapsed >= lost_after)
+        {
+            return Err(btleplug::Error::RuntimeError("adapter removed".into()));
+        }
+        Ok(self
+            .devices
+            .iter()
+            .filter(|device| device.visible.contains(&elapsed))
+            .cloned()
+            .collect())
+    }
+
+    fn stop_scan(&self) -> impl Future<Output = Result<(), btleplug::Error>> + Send + 'static {
+        let stops = self.stops.clone();
+        async move {
+            stops.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+}
+
+fn options() -> ScanOptions {
+    ScanOptions {
+        retry: RetryPolicy {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn no_adapter_is_retried_then_reported() {
+    let scanner = MockScanner::failing(|| ScanError::NoAdapter);
+    let e = scan_on(&scanner, &options(), |_| {}).await.unwrap_err();
+
+    assert!(matches!(
+        e.downcast_ref::<ScanError>(),
+        Some(ScanError::NoAdapter)
+    ));
+    assert_eq!(scanner.start_attempts.load(Ordering::SeqCst), 3);
+    // Nothing was started, so there's nothing to stop
+    assert_eq!(scanner.stops().await, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn permission_denied_is_not_retried() {
+    let scanner = MockScanner::failing(|| ScanError::PermissionDenied("AccessDenied".into()));
+    let e = scan_on(&scanner, &options(), |_| {}).await.unwrap_err();
+
+    assert!(matches!(
+        e.downcast_ref::<ScanError>(),
+        Some(ScanError::PermissionDenied(_))
+    ));
+    assert_eq!(scanner.start_attempts.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn silent_adapter_times_out_with_nothing() {
+    let scanner = MockScanner::default();
+    let start = Instant::now();
+    let sighting = scan_on(&scanner, &options(), |_| {}).await.unwrap();
+
+    assert_eq!(sighting, None);
+    assert!(start.elapsed() >= TIMEOUT);
+    assert!(start.elapsed() < TIMEOUT + Duration::from_secs(1));
+    assert_eq!(scanner.stops().await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn late_advertisement_is_picked_up_before_the_timeout() {
+    let appears = Duration::from_secs(2);
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_PRO, -45).visible(appears..Duration::MAX),
+    ]);
+    let start = Instant::now();
+    let sighting = scan_on(&scanner, &options(), |_| {})
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(sighting.status.model(), "AirPods Pro");
+    assert!(start.elapsed() >= appears);
+    assert!(start.elapsed() < TIMEOUT);
+    assert_eq!(scanner.stops().await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn advertisement_after_the_timeout_is_missed() {
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_PRO, -45)
+            .visible(TIMEOUT + Duration::from_secs(1)..Duration::MAX),
+    ]);
+    let sighting = scan_on(&scanner, &options(), |_| {}).await.unwrap();
+
+    assert_eq!(sighting, None);
+    assert_eq!(scanner.stops().await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn connected_pair_wins_over_a_stronger_one() {
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_2, -35),
+        MockDevice::airpods("6A:11:C4:00:00:02", AIRPODS_PRO, -55).paired("Sam's AirPods Pro"),
+    ]);
+    let start = Instant::now();
+    let sighting = scan_on(&scanner, &options(), |_| {})
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(sighting.status.model(), "AirPods Pro");
+    assert!(sighting.connected);
+    assert_eq!(sighting.name.as_deref(), Some("Sam's AirPods Pro"));
+    // Nothing can beat a connected pair, so the scan ends right away
+    assert!(start.elapsed() < TIMEOUT);
+    assert_eq!(scanner.stops().await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn pair_named_by_a_connection_wins_over_a_stronger_one() {
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_2, -35),
+        MockDevice::airpods("6A:11:C4:00:00:02", AIRPODS_PRO, -55),
+        MockDevice::connected("Sam's AirPods Pro"),
+    ]);
+    let start = Instant::now();
+    let sighting = scan_on(&scanner, &options(), |_| {})
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(sighting.status.model(), "AirPods Pro");
+    assert_eq!(sighting.name.as_deref(), Some("Sam's AirPods Pro"));
+    // AirPods advertise from another address than they are connected with
+    assert!(!sighting.connected);
+    // The connected pair might still turn up
+    assert!(start.elapsed() >= TIMEOUT);
+}
+
+#[tokio::test(start_paused = true)]
+async fn connection_names_only_the_strongest_pair_of_its_model() {
+    let checks = Arc::new(AtomicU32::new(0));
+    let devices = vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_PRO_2, -50),
+        // A housemate's, and one across the street
+        MockDevice::airpods("6A:11:C4:00:00:02", OTHER_AIRPODS_PRO_2, -60),
+        MockDevice::airpods("7B:22:D5:00:00:03", AIRPODS_PRO_2, -85),
+        // The default name has no generation
+        MockDevice::connected("Alex's AirPods Pro"),
+    ];
+    let scanner = MockScanner::with_devices(
+        devices
+            .into_iter()
+            .map(|device| MockDevice {
+                connection_checks: checks.clone(),
+                ..device
+            })
+            .collect(),
+    );
+    let sightings = scan_all_on(&scanner, &options(), |_| {}).await.unwrap();
+
+    let found: Vec<_> = sightings
+        .iter()
+        .map(|s| (s.address.as_str(), s.name.as_deref(), s.connected))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("5E:90:1B:00:00:01", Some("Alex's AirPods Pro"), false),
+            ("6A:11:C4:00:00:02", None, false),
+        ]
+    );
+    assert!(sightings[0].confidence > sightings[1].confidence);
+    // Once per device for the whole scan, not on every poll
+    assert_eq!(checks.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test(start_paused = true)]
+async fn connected_pair_is_calibrated_by_its_name() {
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("6A:11:C4:00:00:02", AIRPODS_PRO, -55),
+        MockDevice::connected("Sam's AirPods Pro"),
+    ]);
+    let options = ScanOptions {
+        calibration: vec![
+            Calibration {
+                model: Some("AirPods 2".into()),
+                left: 5,
+                ..Default::default()
+            },
+            Calibration {
+                name: Some("Sam's AirPods Pro".into()),
+                left: -10,
+                case: 10,
+                ..Default::default()
+            },
+        ],
+        ..options()
+    };
+    let sighting = scan_on(&scanner, &options, |_| {}).await.unwrap().unwrap();
+
+    assert_eq!(sighting.name.as_deref(), Some("Sam's AirPods Pro"));
+    let levels: Vec<u8> = sighting
+        .status
+        .components()
+        .iter()
+        .map(|c| c.battery)
+        .collect();
+    assert_eq!(levels, [85, 95, 85]);
+    assert_eq!(sighting.status.battery(), Some(85));
+}
+
+#[tokio::test(start_paused = true)]
+async fn every_pair_in_range_is_listed_strongest_first() {
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_2, -55),
+        MockDevice::airpods("6A:11:C4:00:00:02", AIRPODS_PRO, -40),
+        // Someone else's, across the room
+        MockDevice::airpods("7B:22:D5:00:00:03", AIRPODS_PRO, -85),
+    ]);
+    let start = Instant::now();
+    let sightings = scan_all_on(&scanner, &options(), |_| {}).await.unwrap();
+
+    let found: Vec<_> = sightings
+        .iter()
+        .map(|s| (s.status.model(), s.address.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("AirPods Pro", "6A:11:C4:00:00:02"),
+            ("AirPods 2", "5E:90:1B:00:00:01"),
+        ]
+    );
+    assert!(sightings.iter().all(|s| s.confidence.is_some()));
+    // Listing every pair takes the whole scan
+    assert!(start.elapsed() >= TIMEOUT);
+    assert_eq!(scanner.stops().await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn tally_counts_what_was_turned_down() {
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_2, -55),
+        MockDevice::airpods("7B:22:D5:00:00:03", AIRPODS_PRO, -85),
+    ]);
+    let mut tally = ScanTally::default();
+    scan_all_on(&scanner, &options(), |advertisement| {
+        tally.observe(advertisement)
+    })
+    .await
+    .unwrap();
+
+    let stats = tally.stats(TIMEOUT);
+    assert_eq!(stats.duration_ms, 3000);
+    assert_eq!(stats.advertisements, 2);
+    assert_eq!(stats.apple_advertisements, 2);
+    assert_eq!(stats.devices_considered, 2);
+    // The one across the room has too weak a signal
+    assert_eq!(stats.devices_rejected, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn partial_sighting_keeps_its_warnings() {
+    let scanner = MockScanner::with_devices(vec![MockDevice::airpods(
+        "7B:22:D5:00:00:03",
+        AIRPODS_2_GARBLED,
+        -85,
+    )]);
+    let options = ScanOptions {
+        partial: true,
+        ..options()
+    };
+    let sighting = scan_on(&scanner, &options, |_| {}).await.unwrap().unwrap();
+
+    assert!(sighting.partial);
+    assert_eq!(sighting.warnings.len(), 1, "{:?}", sighting.warnings);
+}
+
+#[tokio::test(start_paused = true)]
+async fn address_rotation_mid_scan_is_one_pair() {
+    let rotation = Duration::from_secs(1);
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_PRO, -45)
+            .visible(Duration::ZERO..rotation),
+        MockDevice::airpods("4C:3D:88:00:00:07", AIRPODS_PRO, -46).visible(rotation..Duration::MAX),
+    ]);
+    let mut addresses = Vec::new();
+    let sightings = scan_all_on(&scanner, &options(), |advertisement| {
+        addresses.push(advertisement.address.clone())
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(addresses, ["5E:90:1B:00:00:01", "4C:3D:88:00:00:07"]);
+    assert_eq!(sightings.len(), 1);
+    assert_eq!(sightings[0].address, "4C:3D:88:00:00:07");
+    assert_eq!(scanner.stops().await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn failing_peripheral_is_skipped() {
+    let scanner = MockScanner::with_devices(vec![
+        MockDevice::airpods("5E:90:1B:00:00:01", AIRPODS_2, -40).flaky(),
+        MockDevice::airpods("6A:11:C4:00:00:02", AIRPODS_PRO, -45),
+        MockDevice::connected("Sam's AirPods Pro").flaky(),
+    ]);
+    let sightings = scan_all_on(&scanner, &options(), |_| {}).await.unwrap();
+
+    let found: Vec<_> = sightings.iter().map(|s| s.status.model()).collect();
+    assert_eq!(found, ["AirPods Pro"]);
+    assert_eq!(scanner.stops().await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn adapter_lost_mid_scan_stops_the_scan() {
+    let scanner = MockScanner {
+        lost_after: Some(Duration::from_secs(1)),
+        ..Default::default()
+    };
+    let e = scan_all_on(&scanner, &options(), |_| {}).await.unwrap_err();
+
+    assert!(matches!(
+        e.downcast_ref::<btleplug::Error>(),
+        Some(btleplug::Error::RuntimeError(_))
+    ));
+    assert_eq!(scanner.stops().await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn cancelled_scan_stops_the_scan() {
+    let scanner = MockScanner::default();
+    let options = options();
+    let scan = scan_all_on(&scanner, &options, |_| {});
+    let cancelled = tokio::time::timeout(Duration::from_secs(1), scan).await;
+
+    assert!(cancelled.is_err());
+    assert_eq!(scanner.stops().await, 1);
+}
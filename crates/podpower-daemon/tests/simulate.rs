@@ -0,0 +1,82 @@
+//! The made-up AirPods of `--simulate`, decoded as real ones would be.
+
+use podpower_core::{AirPodsStatus, decode_airpods_data};
+use podpower_daemon::simulate::{NAME, STEP, Scenario, Simulator};
+use podpower_daemon::{ScanOptions, scan_on};
+use std::time::UNIX_EPOCH;
+
+/// The status `scenario` advertises `steps` into its cycle
+fn status(scenario: Scenario, steps: u32) -> AirPodsStatus {
+    let at = UNIX_EPOCH + STEP * steps;
+    decode_airpods_data(&scenario.frame(at)).unwrap()
+}
+
+fn levels(status: &AirPodsStatus) -> Vec<(String, u8, bool)> {
+    status
+        .components()
+        .iter()
+        .map(|c| (c.name.clone(), c.battery, c.charging))
+        .collect()
+}
+
+fn level(name: &str, battery: u8, charging: bool) -> (String, u8, bool) {
+    (name.to_string(), battery, charging)
+}
+
+#[test]
+fn draining_runs_down_and_starts_over() {
+    let full = status(Scenario::Draining, 0);
+    assert_eq!(full.model(), "AirPods Pro");
+    assert_eq!(
+        levels(&full),
+        [
+            level("left", 100, false),
+            level("right", 100, false),
+            level("case", 65, false)
+        ]
+    );
+    assert_eq!(status(Scenario::Draining, 8).battery(), Some(25));
+    assert_eq!(status(Scenario::Draining, 10), full);
+}
+
+#[test]
+fn charging_stops_once_full() {
+    assert_eq!(
+        levels(&status(Scenario::Charging, 0)),
+        [
+            level("left", 15, true),
+            level("right", 15, true),
+            level("case", 55, true)
+        ]
+    );
+    let full = status(Scenario::Charging, 9);
+    assert_eq!(full.battery(), Some(100));
+    assert!(
+        full.components()
+            .iter()
+            .filter(|c| c.name != "case")
+            .all(|c| !c.charging)
+    );
+}
+
+#[test]
+fn one_pod_missing_leaves_out_the_right_pod() {
+    let status = status(Scenario::OnePodMissing, 3);
+    assert_eq!(status.missing(), ["right"]);
+    assert_eq!(status.battery(), Some(75));
+}
+
+#[tokio::test]
+async fn scans_find_the_simulated_pair_connected() {
+    let sighting = scan_on(
+        &Simulator::new(Scenario::Draining),
+        &ScanOptions::default(),
+        |_| {},
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert!(sighting.connected);
+    assert_eq!(sighting.name.as_deref(), Some(NAME));
+    assert_eq!(sighting.status.model(), "AirPods Pro");
+}
@@ -0,0 +1,29 @@
+//! Arbitrary bytes through every decoder: nothing may panic, and whatever
+//! decodes has to report percentages.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use podpower_core::{AirPodsStatus, decode_airpods_data, decode_airpods_data_lenient};
+
+fn check(status: &AirPodsStatus) {
+    for component in status.components() {
+        assert!(component.battery <= 100, "{:?}", status);
+    }
+    assert!(status.battery().is_none_or(|battery| battery <= 100));
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(status) = decode_airpods_data(data) {
+        check(&status);
+    }
+    if let Some(status) = decode_airpods_data_lenient(data) {
+        check(&status);
+    }
+    if let Ok(status) = podpower_core::decode_galaxy_buds_data(data) {
+        check(&status);
+    }
+    if let Ok(status) = podpower_core::decode_fast_pair_data(data) {
+        check(&status);
+    }
+});
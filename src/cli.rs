@@ -0,0 +1,729 @@
+use crate::config::Config;
+use crate::exit::UsageError;
+use crate::logging::LogFormat;
+use crate::output::i18n::Lang;
+use crate::output::style::{ColorChoice, Icons};
+use crate::output::{Format, RenderOptions};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use podpower_core::{AppleDecoder, EncryptionKey, InvalidKey, default_decoders};
+use podpower_daemon::calibration::Calibration;
+use podpower_daemon::fingerprint::KnownPairs;
+use podpower_daemon::fleet::Retention;
+use podpower_daemon::simulate::Scenario;
+use podpower_daemon::{AbsentPolicy, RetryPolicy, ScanOptions, SightingFilter};
+#[cfg(any(feature = "http", feature = "grpc"))]
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Check AirPods battery status from their BLE advertisements
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[command(flatten)]
+    pub scan: ScanArgs,
+}
+
+/// How scans talk to the Bluetooth stack
+#[derive(Debug, Args)]
+pub struct ScanArgs {
+    /// Attempts at acquiring the adapter and starting a scan before giving up
+    #[arg(long, value_name = "N", default_value_t = RetryPolicy::default().attempts, value_parser = clap::value_parser!(u32).range(1..), global = true)]
+    pub scan_retries: u32,
+
+    /// Milliseconds before the first retry, doubled for each further one
+    #[arg(long, value_name = "MS", default_value_t = RetryPolicy::default().initial_backoff.as_millis() as u64, global = true)]
+    pub scan_retry_backoff: u64,
+
+    /// File with the AirPods' 16-byte proximity encryption key, as hex or raw
+    /// bytes, to decode exact battery levels instead of 10% steps
+    #[arg(long, value_name = "PATH", global = true)]
+    pub key_file: Option<PathBuf>,
+
+    /// Only look for this vendor's earbuds (repeatable) [default: all]
+    #[arg(
+        long = "vendor",
+        value_name = "VENDOR",
+        value_parser = clap::builder::PossibleValuesParser::new(
+            default_decoders().iter().map(|decoder| decoder.name())
+        ),
+        global = true
+    )]
+    pub vendors: Vec<String>,
+
+    /// If no earbuds advertise their battery, read it from the standard GATT Battery Service of a connected device
+    #[arg(long, global = true)]
+    pub gatt: bool,
+
+    /// If nothing passes, settle for a weak signal or a malformed advertisement, flagged `partial` in JSON
+    #[arg(long, global = true)]
+    pub partial: bool,
+
+    /// Turn down advertisements of unknown models or unexpected lengths instead of decoding them with `warnings`
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Ask connected AirPods for their noise control (ANC/transparency/off), shown as `listening_mode` in JSON
+    #[cfg(all(feature = "aap", target_os = "linux"))]
+    #[arg(long, global = true)]
+    pub listening_mode: bool,
+
+    /// Only accept this model, e.g. "AirPods Pro"
+    #[arg(long, value_name = "MODEL", global = true)]
+    pub model: Option<String>,
+
+    /// Only accept earbuds advertising from this address (AirPods change theirs every few minutes)
+    #[arg(long, value_name = "ADDR", global = true)]
+    pub address: Option<String>,
+
+    /// Print every pair in range, most likely yours first, instead of only the most likely one
+    #[arg(long)]
+    pub all: bool,
+
+    /// Add what the scan saw (duration, advertisements, devices turned down) as `scan` in JSON, including when nothing is found
+    #[arg(long, conflicts_with = "all")]
+    pub scan_stats: bool,
+
+    /// Make up AirPods going through this scenario instead of scanning, to try widgets and alerts without Bluetooth
+    #[arg(long, value_name = "SCENARIO", value_enum, num_args = 0..=1, default_missing_value = "draining", global = true)]
+    pub simulate: Option<Simulate>,
+}
+
+impl ScanArgs {
+    pub fn scan_options(&self) -> Result<ScanOptions, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(address) = self.address.as_deref().filter(|a| !is_address(a)) {
+            return Err(UsageError(format!(
+                "--address {} isn't a Bluetooth address like AA:BB:CC:DD:EE:FF",
+                address
+            ))
+            .into());
+        }
+        let key = match &self.key_file {
+            Some(path) => Some(read_key(path).map_err(|e| format!("{}: {}", path.display(), e))?),
+            None => None,
+        };
+        let decoders = default_decoders()
+            .into_iter()
+            .filter(|decoder| {
+                self.vendors.is_empty() || self.vendors.iter().any(|v| v == decoder.name())
+            })
+            .map(|decoder| {
+                if decoder.name() != AppleDecoder::NAME || (key.is_none() && !self.strict) {
+                    return decoder;
+                }
+                let apple = match &key {
+                    Some(key) => AppleDecoder::with_key(key.clone()),
+                    None => AppleDecoder::default(),
+                };
+                Arc::new(apple.with_strict(self.strict))
+            })
+            .collect();
+        #[cfg(all(feature = "aap", target_os = "linux"))]
+        let listening_mode = self.listening_mode;
+        #[cfg(not(all(feature = "aap", target_os = "linux")))]
+        let listening_mode = false;
+        Ok(ScanOptions {
+            retry: RetryPolicy {
+                attempts: self.scan_retries,
+                initial_backoff: Duration::from_millis(self.scan_retry_backoff),
+            },
+            decoders,
+            gatt_fallback: self.gatt,
+            filter: SightingFilter {
+                model: self.model.clone(),
+                address: self.address.clone(),
+            },
+            // Made-up pairs mustn't count as the user's
+            known_pairs: KnownPairs::default_path().filter(|_| self.simulate.is_none()),
+            partial: self.partial,
+            listening_mode,
+            calibration: calibration(),
+            simulate: self.simulate.map(Into::into),
+        })
+    }
+}
+
+/// Six hex bytes separated by colons, the way advertising addresses are shown
+fn is_address(address: &str) -> bool {
+    let bytes: Vec<&str> = address.split(':').collect();
+    bytes.len() == 6
+        && bytes
+            .iter()
+            .all(|byte| byte.len() == 2 && byte.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// The `[[calibration]]` entries of the default config file. A file that
+/// fails to parse only costs the calibration here; the daemon reports it.
+fn calibration() -> Vec<Calibration> {
+    match Config::load(None) {
+        Ok(config) => config.calibration,
+        Err(e) => {
+            tracing::warn!("Not calibrating: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// A key file holds either the 16 raw bytes or their (at least 32) hex
+/// digits, so the length tells them apart
+fn read_key(path: &Path) -> Result<EncryptionKey, Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = std::fs::read(path)?;
+    match <[u8; 16]>::try_from(bytes.as_slice()) {
+        Ok(raw) => Ok(EncryptionKey::new(raw)),
+        Err(_) => Ok(String::from_utf8(bytes)
+            .map_err(|_| InvalidKey)?
+            .parse::<EncryptionKey>()?),
+    }
+}
+
+/// Diagnostic logging on stderr
+#[derive(Debug, Args)]
+pub struct LogArgs {
+    /// Log filter, e.g. `debug` or `podpower_daemon=trace` [default: $RUST_LOG or info]
+    #[arg(long, value_name = "FILTER", global = true)]
+    pub log_level: Option<String>,
+
+    /// Log line format [default: journald when run by systemd, otherwise text]
+    #[arg(long, value_enum, global = true)]
+    pub log_format: Option<LogFormat>,
+
+    /// Print every advertisement of a supported vendor seen while scanning, and why it was accepted or rejected
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+}
+
+/// Options controlling how a status is printed
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t, global = true)]
+    pub format: Format,
+
+    /// Color battery levels (green/yellow/red) in text output
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub color: ColorChoice,
+
+    /// Prefix text output lines with battery/charging glyphs
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub icons: Icons,
+
+    /// Render each component as a gauge in text output (e.g. `L ▰▰▰▰▱ 78%`)
+    #[arg(
+        long,
+        value_name = "WIDTH",
+        num_args = 0..=1,
+        default_missing_value = "5",
+        value_parser = clap::value_parser!(u8).range(1..=50),
+        global = true
+    )]
+    pub bars: Option<u8>,
+
+    /// Only print battery lines: no model line, warnings or "not found" message
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Show disconnected components ("Left: disconnected", `missing` in JSON) instead of leaving them out
+    #[arg(long, global = true)]
+    pub show_missing: bool,
+
+    /// How the 0-10 readings of AirPods map to battery levels
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub round: Round,
+
+    /// Include the advertisement bytes the status was decoded from, as hex: `raw_data` in JSON, a last line in plain text
+    #[arg(long, global = true)]
+    pub raw: bool,
+
+    /// Exit with 0 when no AirPods were found, printing `{"type": "none"}` in JSON and nothing in text formats
+    #[arg(long, global = true)]
+    pub empty_ok: bool,
+
+    /// Write the output to this file instead of stdout, replacing it atomically (e.g. for node_exporter's textfile collector)
+    #[arg(long, value_name = "PATH", global = true)]
+    pub out: Option<PathBuf>,
+
+    /// Language of text output [default: from LC_ALL, LC_MESSAGES or LANG]
+    #[arg(long, value_enum, global = true)]
+    pub lang: Option<Lang>,
+
+    /// Emit JSON in the shape of this schema version, for consumers pinned to an older one
+    #[cfg(feature = "json")]
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = podpower_core::payload::SCHEMA_VERSION,
+        value_parser = clap::value_parser!(u32).range(
+            i64::from(podpower_core::payload::MIN_SCHEMA_VERSION)..=i64::from(podpower_core::payload::SCHEMA_VERSION)
+        ),
+        global = true
+    )]
+    pub output_version: u32,
+
+    /// Print JSON on a single line instead of pretty-printed (for NDJSON and logs)
+    #[cfg(feature = "json")]
+    #[arg(long, global = true)]
+    pub compact: bool,
+
+    /// Print only these JSON fields, as one flat object: payload fields like `model`, a component's level like `left`, or whether it charges like `charging_case`
+    #[cfg(feature = "json")]
+    #[arg(long, value_name = "FIELD,...", value_delimiter = ',', global = true)]
+    pub fields: Option<Vec<String>>,
+}
+
+impl OutputArgs {
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            color: self.color.enabled(),
+            icons: self.icons,
+            bars: self.bars,
+            lang: self.lang.unwrap_or_else(Lang::detect),
+            quiet: self.quiet,
+            show_missing: self.show_missing,
+            round: self.round.into(),
+            raw: self.raw,
+            #[cfg(feature = "json")]
+            output_version: Some(self.output_version),
+            #[cfg(feature = "json")]
+            compact: self.compact,
+            #[cfg(feature = "json")]
+            fields: self.fields.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Keep scanning in the background and serve the latest status
+    #[command(visible_alias = "serve")]
+    Daemon(Box<DaemonArgs>),
+
+    /// Print the latest status from a running daemon instead of scanning
+    #[cfg(feature = "json")]
+    Query(QueryArgs),
+
+    /// Show the latest levels of every pair a daemon running with --fleet heard from
+    #[cfg(feature = "json")]
+    Fleet(FleetArgs),
+
+    /// Hold back a running daemon's notifications for a while
+    #[cfg(feature = "notify")]
+    Snooze(SnoozeArgs),
+
+    /// Print (or with --write, save) a status-bar module that shows the battery
+    Init(InitArgs),
+
+    /// Print Home Assistant sensor definitions (YAML) that read a running daemon
+    #[cfg(feature = "json")]
+    HassConfig(HassConfigArgs),
+
+    /// Print the JSON Schema of the JSON output
+    #[cfg(feature = "schema")]
+    Schema {
+        #[arg(value_enum, default_value_t)]
+        kind: crate::schema::SchemaKind,
+    },
+
+    /// Switch the noise control of AirPods connected to this machine (--model picks among several)
+    #[cfg(all(feature = "aap", target_os = "linux"))]
+    SetMode {
+        #[arg(value_enum)]
+        mode: ListeningMode,
+    },
+
+    /// Battery history recorded by `podpower daemon --history`
+    #[cfg(feature = "history")]
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+
+    /// Charge cycles and battery wear, estimated from the battery history
+    #[cfg(feature = "history")]
+    Health(HealthArgs),
+
+    /// Show the battery status as a system tray icon
+    #[cfg(all(feature = "tray", target_os = "linux"))]
+    Tray {
+        /// Seconds to wait between scans
+        #[arg(long, value_name = "SECS", default_value_t = podpower_daemon::monitor::DEFAULT_INTERVAL_SECS)]
+        interval: u64,
+    },
+
+    /// Live terminal dashboard with gauges and a battery history
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Seconds to wait between scans
+        #[arg(long, value_name = "SECS", default_value_t = podpower_daemon::monitor::DEFAULT_INTERVAL_SECS)]
+        interval: u64,
+    },
+}
+
+#[derive(Debug, Clone, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct DaemonArgs {
+    #[cfg(feature = "json")]
+    #[command(subcommand)]
+    pub command: Option<DaemonCommand>,
+
+    /// Serve the REST API on this address (e.g. 127.0.0.1:9876)
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "ADDR")]
+    pub http: Option<SocketAddr>,
+
+    /// Serve the gRPC API on this address (e.g. 127.0.0.1:9877)
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDR")]
+    pub grpc: Option<SocketAddr>,
+
+    /// Register org.podpower.Battery on the D-Bus session bus
+    #[cfg(feature = "dbus")]
+    #[arg(long)]
+    pub dbus: bool,
+
+    /// Report the battery to UPower through BlueZ's battery provider API
+    #[cfg(feature = "upower")]
+    #[arg(long)]
+    pub upower: bool,
+
+    /// POST the JSON status to this URL whenever it changes
+    #[cfg(feature = "webhook")]
+    #[arg(long, value_name = "URL")]
+    pub webhook: Option<String>,
+
+    /// Sign webhook bodies with this HMAC-SHA256 key (X-Podpower-Signature header)
+    #[cfg(feature = "webhook")]
+    #[arg(
+        long,
+        value_name = "SECRET",
+        env = "PODPOWER_WEBHOOK_SECRET",
+        requires = "webhook",
+        hide_env_values = true
+    )]
+    pub webhook_secret: Option<String>,
+
+    /// Keep the latest levels of every pair in range, not only the user's, for `podpower fleet`
+    #[arg(long)]
+    pub fleet: bool,
+
+    /// Seconds without hearing from a pair before `podpower fleet` marks its levels stale, 0 to never
+    #[arg(long, value_name = "SECS", requires = "fleet", default_value_t = Retention::default().stale_after.as_secs())]
+    pub stale_after: u64,
+
+    /// Seconds without hearing from a pair before dropping it from the fleet, 0 to never
+    #[arg(long, value_name = "SECS", requires = "fleet", default_value_t = Retention::default().forget_after.as_secs())]
+    pub forget_after: u64,
+
+    /// Also show low-battery alerts as desktop notifications on this host
+    #[cfg(feature = "notify")]
+    #[arg(long)]
+    pub notify: bool,
+
+    /// Push battery gauges to this StatsD server every interval (e.g. localhost:8125)
+    #[arg(long, value_name = "HOST:PORT")]
+    pub statsd: Option<String>,
+
+    /// Push battery gauges to this Graphite plaintext listener every interval (e.g. localhost:2003)
+    #[arg(long, value_name = "HOST:PORT")]
+    pub graphite: Option<String>,
+
+    /// Seconds to wait between scans
+    #[arg(long, value_name = "SECS", default_value_t = podpower_daemon::monitor::DEFAULT_INTERVAL_SECS)]
+    pub interval: u64,
+
+    /// How eagerly to scan: `low` waits three intervals, `balanced` scans continuously while the battery is low or charging just changed, `realtime` always does
+    #[arg(long, value_enum, default_value_t)]
+    pub power_profile: PowerProfile,
+
+    /// Seconds without finding AirPods before scanning less often, 0 to never back off
+    #[arg(long, value_name = "SECS", default_value_t = AbsentPolicy::default().after.as_secs())]
+    pub absent_after: u64,
+
+    /// Longest wait between scans while backing off, in seconds
+    #[arg(long, value_name = "SECS", default_value_t = AbsentPolicy::default().max_wait.as_secs())]
+    pub absent_max_interval: u64,
+
+    /// Instead of backing off, stop scanning until a Bluetooth device connects to this host
+    #[arg(long)]
+    pub sleep_when_absent: bool,
+
+    /// Record every status change to a SQLite database [default: $XDG_DATA_HOME/podpower/history.sqlite, %LOCALAPPDATA%\podpower\history.sqlite on Windows]
+    #[cfg(feature = "history")]
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    pub history: Option<Option<PathBuf>>,
+
+    /// Config file [default: $XDG_CONFIG_HOME/podpower/config.toml, %APPDATA%\podpower\config.toml on Windows]
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Query socket path [default: $XDG_RUNTIME_DIR/podpower.sock, \\.\pipe\podpower on Windows]
+    #[cfg(feature = "json")]
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// Write a systemd user service running the daemon with these options, then exit
+    #[cfg(target_os = "linux")]
+    #[arg(long)]
+    pub install_systemd: bool,
+
+    /// With --install-systemd, also write a socket unit so the daemon starts on the first query
+    #[cfg(all(feature = "json", target_os = "linux"))]
+    #[arg(long, requires = "install_systemd")]
+    pub socket_activation: bool,
+
+    /// Write and load a LaunchAgent running the daemon at login with these options, then exit
+    #[cfg(target_os = "macos")]
+    #[arg(long)]
+    pub install_launchd: bool,
+
+    /// Register the daemon to start at login with these options, then exit
+    #[cfg(windows)]
+    #[arg(long)]
+    pub install_startup: bool,
+
+    /// Stop and remove the installed service, then exit
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    #[arg(long)]
+    pub uninstall: bool,
+}
+
+impl DaemonArgs {
+    pub fn absent_policy(&self) -> Option<AbsentPolicy> {
+        (self.absent_after > 0).then(|| AbsentPolicy {
+            after: Duration::from_secs(self.absent_after),
+            max_wait: Duration::from_secs(self.absent_max_interval),
+            sleep: self.sleep_when_absent,
+        })
+    }
+
+    pub fn retention(&self) -> Retention {
+        Retention {
+            stale_after: Duration::from_secs(self.stale_after),
+            forget_after: Duration::from_secs(self.forget_after),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PowerProfile {
+    Low,
+    #[default]
+    Balanced,
+    Realtime,
+}
+
+impl From<PowerProfile> for podpower_daemon::PowerProfile {
+    fn from(profile: PowerProfile) -> Self {
+        match profile {
+            PowerProfile::Low => podpower_daemon::PowerProfile::Low,
+            PowerProfile::Balanced => podpower_daemon::PowerProfile::Balanced,
+            PowerProfile::Realtime => podpower_daemon::PowerProfile::Realtime,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Simulate {
+    /// In both ears, draining from 100% to 15% over ten minutes
+    Draining,
+    /// In the case on a charger, charging until full
+    Charging,
+    /// Only the left pod in range, draining
+    OnePodMissing,
+    /// Draining, but of a model podpower doesn't know yet
+    UnknownModel,
+}
+
+impl From<Simulate> for Scenario {
+    fn from(simulate: Simulate) -> Self {
+        match simulate {
+            Simulate::Draining => Scenario::Draining,
+            Simulate::Charging => Scenario::Charging,
+            Simulate::OnePodMissing => Scenario::OnePodMissing,
+            Simulate::UnknownModel => Scenario::UnknownModel,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Round {
+    /// The middle of the 10% step: 85% for 8 of 10
+    #[default]
+    #[value(name = "nearest-5")]
+    Nearest5,
+    /// The bottom of the step: 80% for 8 of 10
+    #[value(name = "nearest-10")]
+    Nearest10,
+    /// The reading itself, 0 to 10
+    Raw,
+}
+
+impl From<Round> for podpower_core::Rounding {
+    fn from(round: Round) -> Self {
+        match round {
+            Round::Nearest5 => podpower_core::Rounding::Nearest5,
+            Round::Nearest10 => podpower_core::Rounding::Nearest10,
+            Round::Raw => podpower_core::Rounding::Raw,
+        }
+    }
+}
+
+#[cfg(all(feature = "aap", target_os = "linux"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListeningMode {
+    /// Active noise cancellation
+    Anc,
+    Transparency,
+    Off,
+    /// AirPods Pro 2 only
+    Adaptive,
+}
+
+#[cfg(all(feature = "aap", target_os = "linux"))]
+impl From<ListeningMode> for podpower_core::ListeningMode {
+    fn from(mode: ListeningMode) -> Self {
+        match mode {
+            ListeningMode::Anc => podpower_core::ListeningMode::Anc,
+            ListeningMode::Transparency => podpower_core::ListeningMode::Transparency,
+            ListeningMode::Off => podpower_core::ListeningMode::Off,
+            ListeningMode::Adaptive => podpower_core::ListeningMode::Adaptive,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Args)]
+pub struct QueryArgs {
+    /// Query socket path [default: $XDG_RUNTIME_DIR/podpower.sock, \\.\pipe\podpower on Windows]
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+}
+
+#[cfg(feature = "notify")]
+#[derive(Debug, Clone, Args)]
+pub struct SnoozeArgs {
+    /// How long, e.g. `1h` or `30m`; `0s` ends the snooze
+    #[arg(value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub duration: Duration,
+
+    #[command(flatten)]
+    pub query: QueryArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct InitArgs {
+    /// Status bar to configure
+    #[arg(value_enum)]
+    pub bar: Bar,
+
+    /// Seconds between refreshes [default: 30, for tmux its own status-interval]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: Option<u64>,
+
+    /// Write the snippet to a file the bar's config includes instead of printing it [default: next to the bar's config, e.g. ~/.config/waybar/podpower.jsonc]
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    pub write: Option<Option<PathBuf>>,
+}
+
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Args)]
+pub struct HassConfigArgs {
+    /// Base URL of the daemon's HTTP server (`podpower daemon --http`), as Home Assistant reaches it
+    #[arg(long, value_name = "URL", default_value = "http://127.0.0.1:9876")]
+    pub url: String,
+
+    /// Run `podpower query --format hass` on the Home Assistant host instead of reading the HTTP server
+    #[arg(long)]
+    pub command_line: bool,
+
+    /// Seconds between updates
+    #[arg(long, value_name = "SECS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Bar {
+    Waybar,
+    Polybar,
+    Sketchybar,
+    Tmux,
+}
+
+/// Commands for a daemon that is already running
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Subcommand)]
+pub enum DaemonCommand {
+    /// Show the running daemon's uptime, adapter, scan mode, devices and sinks
+    Status(DaemonStatusArgs),
+    /// Stop scanning until `podpower daemon resume`, keeping the latest status
+    Pause(QueryArgs),
+    /// Start scanning again after `podpower daemon pause`
+    Resume(QueryArgs),
+}
+
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Args)]
+pub struct FleetArgs {
+    /// Print the devices as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub query: QueryArgs,
+}
+
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Args)]
+pub struct DaemonStatusArgs {
+    /// Print the status as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub query: QueryArgs,
+}
+
+#[cfg(feature = "history")]
+#[derive(Debug, Subcommand)]
+pub enum HistoryCommand {
+    /// Write the recorded battery levels as CSV
+    Export(ExportArgs),
+}
+
+#[cfg(feature = "history")]
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// CSV file to write, `-` for stdout
+    #[arg(long, value_name = "PATH")]
+    pub csv: PathBuf,
+
+    /// Only export the last DURATION, e.g. `7d` or `12h` [default: everything]
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub since: Option<Duration>,
+
+    /// History database [default: $XDG_DATA_HOME/podpower/history.sqlite, %LOCALAPPDATA%\podpower\history.sqlite on Windows]
+    #[arg(long, value_name = "PATH")]
+    pub file: Option<PathBuf>,
+}
+
+#[cfg(feature = "history")]
+#[derive(Debug, Args)]
+pub struct HealthArgs {
+    /// Print the estimates as JSON
+    #[cfg(feature = "json")]
+    #[arg(long)]
+    pub json: bool,
+
+    /// History database [default: $XDG_DATA_HOME/podpower/history.sqlite, %LOCALAPPDATA%\podpower\history.sqlite on Windows]
+    #[arg(long, value_name = "PATH")]
+    pub file: Option<PathBuf>,
+}
@@ -0,0 +1,120 @@
+//! Optional TOML configuration file for the daemon. One-shot scans only
+//! read its `[[calibration]]` entries.
+
+use crate::cli::DaemonArgs;
+use serde::Deserialize;
+#[cfg(any(feature = "http", feature = "grpc"))]
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = "config.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Outputs to feed, next to the ones enabled by flags
+    pub sinks: SinksConfig,
+    /// Shell commands run on status transitions
+    pub hooks: Option<podpower_daemon::hooks::HooksConfig>,
+    /// Low-battery push notifications
+    #[cfg(feature = "notify")]
+    pub notifications: Option<podpower_daemon::notify::NotifyConfig>,
+    /// Per-device offsets for batteries that misreport
+    pub calibration: Vec<podpower_daemon::calibration::Calibration>,
+    /// Names of the pairs `--fleet` keeps track of
+    pub devices: Vec<podpower_daemon::fleet::DeviceName>,
+}
+
+/// The `[sinks]` section: the daemon's flags, for setups that feed several
+/// consumers at once
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SinksConfig {
+    #[cfg(feature = "http")]
+    pub http: Option<SocketAddr>,
+    #[cfg(feature = "grpc")]
+    pub grpc: Option<SocketAddr>,
+    #[cfg(feature = "dbus")]
+    pub dbus: bool,
+    #[cfg(feature = "upower")]
+    pub upower: bool,
+    #[cfg(feature = "webhook")]
+    pub webhook: Option<String>,
+    #[cfg(feature = "webhook")]
+    pub webhook_secret: Option<String>,
+    pub statsd: Option<String>,
+    pub graphite: Option<String>,
+    /// Record the battery history to the default location, or `history_file`
+    #[cfg(feature = "history")]
+    pub history: bool,
+    #[cfg(feature = "history")]
+    pub history_file: Option<PathBuf>,
+}
+
+impl SinksConfig {
+    /// Enable the configured sinks in `args`. A flag given on the command
+    /// line wins over the same setting here.
+    pub fn apply(self, args: &mut DaemonArgs) {
+        #[cfg(feature = "http")]
+        {
+            args.http = args.http.or(self.http);
+        }
+        #[cfg(feature = "grpc")]
+        {
+            args.grpc = args.grpc.or(self.grpc);
+        }
+        #[cfg(feature = "dbus")]
+        {
+            args.dbus |= self.dbus;
+        }
+        #[cfg(feature = "upower")]
+        {
+            args.upower |= self.upower;
+        }
+        #[cfg(feature = "webhook")]
+        {
+            args.webhook = args.webhook.take().or(self.webhook);
+            args.webhook_secret = args.webhook_secret.take().or(self.webhook_secret);
+        }
+        args.statsd = args.statsd.take().or(self.statsd);
+        args.graphite = args.graphite.take().or(self.graphite);
+        #[cfg(feature = "history")]
+        if args.history.is_none() && (self.history || self.history_file.is_some()) {
+            args.history = Some(self.history_file);
+        }
+    }
+}
+
+impl Config {
+    /// Read `path`, or the default location when `None`. A missing default
+    /// file means an empty config; a missing explicit one is an error.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default());
+            }
+            Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+        };
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+}
+
+/// `$XDG_CONFIG_HOME/podpower/config.toml` (falling back to `~/.config`) on
+/// Unix, `%APPDATA%\podpower\config.toml` on Windows
+pub fn default_path() -> Option<PathBuf> {
+    #[cfg(unix)]
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    #[cfg(windows)]
+    let dir = std::env::var_os("APPDATA").map(PathBuf::from);
+    Some(dir?.join("podpower").join(FILE_NAME))
+}
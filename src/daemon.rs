@@ -0,0 +1,743 @@
+use crate::cli::DaemonArgs;
+#[cfg(feature = "notify")]
+use crate::cli::SnoozeArgs;
+#[cfg(feature = "json")]
+use crate::cli::{DaemonCommand, DaemonStatusArgs, FleetArgs, QueryArgs};
+use crate::config::Config;
+#[cfg(feature = "json")]
+use podpower_daemon::control::{self, Introspection, Response};
+use podpower_daemon::control::{RunningSink, SinkHealth};
+use podpower_daemon::events::{Baselines, Snooze};
+use podpower_daemon::monitor::Diagnostics;
+use podpower_daemon::{Monitor, Reading, ScanOptions};
+#[cfg(any(feature = "http", feature = "grpc"))]
+use std::net::SocketAddr;
+#[cfg(feature = "history")]
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tokio::task::{AbortHandle, JoinSet};
+
+type Listeners = JoinSet<Result<(), String>>;
+
+pub async fn run(
+    args: DaemonArgs,
+    scan_options: ScanOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    #[cfg(feature = "json")]
+    match &args.command {
+        Some(DaemonCommand::Status(args)) => return status(args.clone()).await,
+        Some(DaemonCommand::Pause(args)) => return switch(args.clone(), "pause").await,
+        Some(DaemonCommand::Resume(args)) => return switch(args.clone(), "resume").await,
+        None => {}
+    }
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    if args.uninstall {
+        return crate::service::uninstall();
+    }
+    #[cfg(target_os = "linux")]
+    if args.install_systemd {
+        return crate::service::systemd::install(&args);
+    }
+    #[cfg(target_os = "macos")]
+    if args.install_launchd {
+        return crate::service::launchd::install();
+    }
+    #[cfg(windows)]
+    if args.install_startup {
+        return crate::service::startup::install();
+    }
+    #[cfg(windows)]
+    crate::service::startup::detach_console();
+
+    let config = Config::load(args.config.as_deref())?;
+    let interval = Duration::from_secs(args.interval);
+    let (calibration, calibration_config) = watch::channel(config.calibration.clone());
+    let (device_names, names_config) = watch::channel(config.devices.clone());
+    let (low_threshold, low_threshold_config) = watch::channel(low_battery(&args, &config));
+    let mut monitor = Monitor::new(interval)
+        .with_scan_options(scan_options)
+        .with_calibration(calibration_config)
+        .with_low_threshold(low_threshold_config)
+        .with_power_profile(args.power_profile.into())
+        .with_absent_policy(args.absent_policy());
+    if args.fleet {
+        monitor = monitor.with_fleet(names_config, args.retention());
+    }
+    let readings = monitor.subscribe();
+    let diagnostics = monitor.diagnostics();
+    let mut listeners = Listeners::new();
+    let mut reload = Reload::new()?;
+    // Only the query socket and notifications look at these
+    #[cfg_attr(not(feature = "json"), allow(unused_variables))]
+    let snooze = Snooze::default();
+    #[cfg_attr(not(feature = "json"), allow(unused_variables))]
+    let (running, sink_status) = watch::channel(Vec::new());
+
+    #[cfg(feature = "json")]
+    {
+        let path = args
+            .socket
+            .clone()
+            .unwrap_or_else(control::default_socket_path);
+        let listener = control::bind(&path).await?;
+        let readings = readings.clone();
+        let introspection = Introspection {
+            diagnostics: diagnostics.clone(),
+            sinks: sink_status,
+            switch: monitor.switch(),
+            snooze: snooze.clone(),
+        };
+        listeners.spawn(async move {
+            control::serve_control(listener, readings, introspection)
+                .await
+                .map_err(|e| format!("Query socket: {}", e))
+        });
+    }
+
+    // Hooks and notifications keep running across reloads so they remember
+    // what they already reported; only their settings are swapped
+    let (hooks, hooks_config) = watch::channel(config.hooks.clone().unwrap_or_default());
+    let baselines = baselines(&args, &config);
+    {
+        let readings = readings.clone();
+        let baselines = baselines.clone();
+        listeners.spawn(async move {
+            podpower_daemon::hooks::serve_hooks(hooks_config, readings, baselines)
+                .await
+                .map_err(|e| format!("Hooks: {}", e))
+        });
+    }
+
+    #[cfg(feature = "notify")]
+    let notifications = {
+        let (notifications, notify_config) =
+            watch::channel(notify_config(&args, config.notifications.clone()));
+        log_notifications(&notify_config.borrow());
+        let readings = readings.clone();
+        listeners.spawn(async move {
+            podpower_daemon::notify::serve_notify(notify_config, readings, snooze, baselines)
+                .await
+                .map_err(|e| format!("Notifications: {}", e))
+        });
+        notifications
+    };
+
+    let mut sinks: Vec<(Sink, AbortHandle, RunningSink)> = Vec::new();
+    for sink in Sink::configured(&args, &config)? {
+        let (handle, status) = sink.spawn(&mut listeners, &readings, &diagnostics, interval);
+        sinks.push((sink, handle, status));
+    }
+    running.send_replace(sinks.iter().map(|(_, _, status)| status.clone()).collect());
+
+    tokio::spawn(monitor.run());
+
+    loop {
+        tokio::select! {
+            // Listeners run until they fail; the first failure ends the process.
+            Some(result) = listeners.join_next() => match result {
+                Ok(result) => result?,
+                Err(e) if e.is_cancelled() => {}
+                Err(e) => return Err(e.into()),
+            },
+            () = reload.requested() => {
+                let config = match Config::load(args.config.as_deref()) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        tracing::warn!("Keeping the current config: {}", e);
+                        continue;
+                    }
+                };
+                let wanted = match Sink::configured(&args, &config) {
+                    Ok(wanted) => wanted,
+                    Err(e) => {
+                        tracing::warn!("Keeping the current config: {}", e);
+                        continue;
+                    }
+                };
+                tracing::info!("Reloaded the config");
+                sinks.retain(|(sink, handle, _)| {
+                    let keep = wanted.contains(sink);
+                    if !keep {
+                        tracing::info!("Stopping {}", sink.name());
+                        handle.abort();
+                    }
+                    keep
+                });
+                for sink in wanted {
+                    if !sinks.iter().any(|(running, _, _)| *running == sink) {
+                        let (handle, status) = sink.spawn(&mut listeners, &readings, &diagnostics, interval);
+                        sinks.push((sink, handle, status));
+                    }
+                }
+                running.send_replace(sinks.iter().map(|(_, _, status)| status.clone()).collect());
+                low_threshold.send_replace(low_battery(&args, &config));
+                hooks.send_replace(config.hooks.unwrap_or_default());
+                calibration.send_replace(config.calibration);
+                device_names.send_replace(config.devices);
+                #[cfg(feature = "notify")]
+                {
+                    let config = notify_config(&args, config.notifications);
+                    log_notifications(&config);
+                    notifications.send_replace(config);
+                }
+            }
+        }
+    }
+}
+
+/// Send `command` to the running daemon
+#[cfg(feature = "json")]
+async fn request(
+    args: QueryArgs,
+    command: &str,
+) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+    let path = args.socket.unwrap_or_else(control::default_socket_path);
+    match control::request(&path, command).await {
+        Ok(Response::Error { message }) => Err(message.into()),
+        Ok(response) => Ok(response),
+        Err(e) => Err(format!(
+            "could not reach the daemon at {} ({}). Is `podpower daemon` running?",
+            path.display(),
+            e
+        )
+        .into()),
+    }
+}
+
+/// `podpower daemon pause` and `podpower daemon resume`
+#[cfg(feature = "json")]
+async fn switch(
+    args: QueryArgs,
+    command: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Response::Scanning { paused, changed } = request(args, command).await? else {
+        return Err("unexpected answer from the daemon".into());
+    };
+    match (paused, changed) {
+        (true, true) => println!("Scanning paused"),
+        (true, false) => println!("Scanning was already paused"),
+        (false, true) => println!("Scanning resumed"),
+        (false, false) => println!("Scanning was not paused"),
+    }
+    Ok(())
+}
+
+/// `podpower snooze`
+#[cfg(feature = "notify")]
+pub async fn snooze(args: SnoozeArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let command = format!("snooze {}", args.duration.as_secs());
+    let Response::Snoozed { until } = request(args.query, &command).await? else {
+        return Err("unexpected answer from the daemon".into());
+    };
+    match until {
+        Some(_) => println!(
+            "Notifications snoozed for {}",
+            humantime::format_duration(args.duration)
+        ),
+        None => println!("Notifications resumed"),
+    }
+    Ok(())
+}
+
+/// `podpower fleet`
+#[cfg(feature = "json")]
+pub async fn fleet(args: FleetArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Response::Fleet(fleet) = request(args.query, "fleet").await? else {
+        return Err("unexpected answer from the daemon".into());
+    };
+    if !fleet.enabled {
+        return Err("the daemon isn't keeping track of a fleet, start it with --fleet".into());
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&fleet)?);
+        return Ok(());
+    }
+
+    if fleet.devices.is_empty() {
+        println!("No devices heard from yet");
+        return Ok(());
+    }
+    let now = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let rows: Vec<[String; 4]> = fleet
+        .devices
+        .iter()
+        .map(|device| {
+            let name = device.name.clone().unwrap_or_else(|| match device.color {
+                Some(color) => format!("(color {})", color),
+                None => "-".to_string(),
+            });
+            let levels = device
+                .status
+                .components()
+                .iter()
+                .map(|c| {
+                    let charging = if c.charging { "+" } else { "" };
+                    format!("{} {}%{}", c.name, c.battery, charging)
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+            let seen = if device.connected {
+                "connected".to_string()
+            } else {
+                let ago = elapsed(now.saturating_sub(device.last_seen_at) / 1000);
+                match device.stale {
+                    true => format!("{} ago (stale)", ago),
+                    false => format!("{} ago", ago),
+                }
+            };
+            [name, device.model.clone(), levels, seen]
+        })
+        .collect();
+    let header = ["NAME", "MODEL", "BATTERY", "LAST SEEN"].map(String::from);
+    let widths: Vec<usize> = (0..4)
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+/// `podpower daemon status`
+#[cfg(feature = "json")]
+async fn status(args: DaemonStatusArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Response::Daemon(status) = request(args.query, "daemon").await? else {
+        return Err("unexpected answer from the daemon".into());
+    };
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let ago = |at: u64| format!("{} ago", elapsed(now.saturating_sub(at) / 1000));
+    println!(
+        "podpower {} daemon, up {}",
+        status.version,
+        elapsed(status.uptime_secs)
+    );
+    println!(
+        "Adapter:    {}",
+        status.adapter.as_deref().unwrap_or("none found")
+    );
+    let next = if status.paused {
+        "paused (`podpower daemon resume` to continue)".to_string()
+    } else if status.scan.sleeping {
+        "no AirPods around, waiting for a device to connect".to_string()
+    } else if status.scan.backing_off {
+        format!(
+            "no AirPods around, backing off to {}s between scans",
+            status.scan.wait_secs
+        )
+    } else {
+        format!("next scan {}s after the last", status.scan.wait_secs)
+    };
+    let gatt = if status.scan.gatt_fallback {
+        ", GATT fallback"
+    } else {
+        ""
+    };
+    println!(
+        "Scanning:   {} profile{}, {}",
+        status.scan.profile, gatt, next
+    );
+    match (status.last_scan_at, &status.last_error) {
+        (Some(at), Some(error)) => println!("Last scan:  {}, failed: {}", ago(at), error),
+        (Some(at), None) => println!("Last scan:  {}", ago(at)),
+        (None, _) => println!("Last scan:  none yet"),
+    }
+    match status.last_sighting_at {
+        Some(at) => println!("AirPods:    last found {}", ago(at)),
+        None => println!("AirPods:    not found yet"),
+    }
+    if let Some(until) = status.snoozed_until {
+        println!(
+            "Alerts:     snoozed for another {}",
+            elapsed(until.saturating_sub(now) / 1000)
+        );
+    }
+
+    println!("Devices:");
+    if status.devices.is_empty() {
+        println!("  none heard from recently");
+    }
+    for device in &status.devices {
+        let rssi = device
+            .rssi
+            .map_or_else(|| "? dBm".to_string(), |rssi| format!("{} dBm", rssi));
+        let model = device.model.as_deref().unwrap_or("(rejected)");
+        let connected = if device.connected { ", connected" } else { "" };
+        println!(
+            "  {}  {:<8}  {}  {}{}, {}",
+            device.address,
+            device.vendor,
+            model,
+            rssi,
+            connected,
+            ago(device.last_seen_at)
+        );
+    }
+
+    println!("Sinks:");
+    if status.sinks.is_empty() {
+        println!("  none");
+    }
+    for sink in &status.sinks {
+        let target = sink
+            .target
+            .as_ref()
+            .map(|target| format!(" {}", target))
+            .unwrap_or_default();
+        let health = match (&sink.last_error, sink.last_error_at) {
+            (Some(error), Some(at)) => format!("last delivery failed {}: {}", ago(at), error),
+            _ => "ok".to_string(),
+        };
+        println!(
+            "  {}{}, up {}, {}",
+            sink.name,
+            target,
+            elapsed(now.saturating_sub(sink.running_since) / 1000),
+            health
+        );
+    }
+    Ok(())
+}
+
+/// `secs` as e.g. `45s`, `12m`, `3h 5m` or `2d 4h`
+#[cfg(feature = "json")]
+fn elapsed(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+/// The `[notifications]` section, with a desktop target added for `--notify`
+/// The level the monitor scans more often at: the higher of the hooks' and
+/// the notifications' `low_battery`, so neither reacts late
+#[cfg_attr(not(feature = "notify"), allow(unused_variables))]
+fn low_battery(args: &DaemonArgs, config: &Config) -> u8 {
+    let hooks = config.hooks.clone().unwrap_or_default().low_battery;
+    #[cfg(feature = "notify")]
+    return hooks.max(notify_config(args, config.notifications.clone()).low_battery);
+    #[cfg(not(feature = "notify"))]
+    hooks
+}
+
+#[cfg(feature = "notify")]
+fn notify_config(
+    args: &DaemonArgs,
+    config: Option<podpower_daemon::notify::NotifyConfig>,
+) -> podpower_daemon::notify::NotifyConfig {
+    use podpower_daemon::notify::Target;
+
+    let mut config = config.unwrap_or_default();
+    if args.notify && !config.targets.contains(&Target::Desktop) {
+        config.targets.push(Target::Desktop);
+    }
+    config
+}
+
+#[cfg(feature = "notify")]
+fn log_notifications(config: &podpower_daemon::notify::NotifyConfig) {
+    if !config.targets.is_empty() {
+        tracing::info!(
+            "Sending low-battery notifications to {} target(s)",
+            config.targets.len()
+        );
+    }
+}
+
+/// How far back the history is read for usual drain rates
+#[cfg(feature = "history")]
+const BASELINE_PERIOD: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Usual drain rates for fast-drain alerts to start from: those of the
+/// last [`BASELINE_PERIOD`] of the history, when one is recorded
+#[cfg_attr(not(feature = "history"), allow(unused_variables))]
+fn baselines(args: &DaemonArgs, config: &Config) -> Baselines {
+    #[cfg(feature = "history")]
+    for sink in Sink::configured(args, config).unwrap_or_default() {
+        if let Sink::History(path) = sink
+            && path.exists()
+        {
+            let since = SystemTime::now() - BASELINE_PERIOD;
+            match podpower_daemon::history::History::open(&path).and_then(|h| h.since(since)) {
+                Ok(samples) => return podpower_daemon::history::baselines(&samples),
+                Err(e) => tracing::warn!("Reading drain rates from {}: {}", path.display(), e),
+            }
+        }
+    }
+    Baselines::default()
+}
+
+/// An output fed from the readings, enabled by a flag or the `[sinks]`
+/// config section. On reload, only sinks whose settings changed restart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Sink {
+    #[cfg(feature = "http")]
+    Http(SocketAddr),
+    #[cfg(feature = "grpc")]
+    Grpc(SocketAddr),
+    #[cfg(feature = "dbus")]
+    Dbus,
+    #[cfg(feature = "upower")]
+    Upower,
+    #[cfg(feature = "webhook")]
+    Webhook {
+        url: String,
+        secret: Option<String>,
+    },
+    #[cfg(feature = "history")]
+    History(PathBuf),
+    Statsd(String),
+    Graphite(String),
+}
+
+impl Sink {
+    /// The sinks enabled by `args`, plus those in `config`
+    fn configured(
+        args: &DaemonArgs,
+        config: &Config,
+    ) -> Result<Vec<Sink>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut args = args.clone();
+        config.sinks.clone().apply(&mut args);
+        let mut sinks = Vec::new();
+        #[cfg(feature = "http")]
+        sinks.extend(args.http.map(Sink::Http));
+        #[cfg(feature = "grpc")]
+        sinks.extend(args.grpc.map(Sink::Grpc));
+        #[cfg(feature = "dbus")]
+        if args.dbus {
+            sinks.push(Sink::Dbus);
+        }
+        #[cfg(feature = "upower")]
+        if args.upower {
+            sinks.push(Sink::Upower);
+        }
+        #[cfg(feature = "webhook")]
+        if let Some(url) = args.webhook {
+            sinks.push(Sink::Webhook {
+                url,
+                secret: args.webhook_secret,
+            });
+        }
+        #[cfg(feature = "history")]
+        if let Some(path) = args.history {
+            sinks.push(Sink::History(crate::history::database_path(path)?));
+        }
+        sinks.extend(args.statsd.map(Sink::Statsd));
+        sinks.extend(args.graphite.map(Sink::Graphite));
+        Ok(sinks)
+    }
+
+    /// Prefix of the sink's errors and log lines
+    fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "http")]
+            Sink::Http(_) => "HTTP server",
+            #[cfg(feature = "grpc")]
+            Sink::Grpc(_) => "gRPC server",
+            #[cfg(feature = "dbus")]
+            Sink::Dbus => "D-Bus service",
+            #[cfg(feature = "upower")]
+            Sink::Upower => "UPower bridge",
+            #[cfg(feature = "webhook")]
+            Sink::Webhook { .. } => "Webhook",
+            #[cfg(feature = "history")]
+            Sink::History(_) => "History",
+            Sink::Statsd(_) => "StatsD",
+            Sink::Graphite(_) => "Graphite",
+        }
+    }
+
+    /// Where the sink delivers to, for `podpower daemon status`
+    fn target(&self) -> Option<String> {
+        match self {
+            #[cfg(feature = "http")]
+            Sink::Http(addr) => Some(addr.to_string()),
+            #[cfg(feature = "grpc")]
+            Sink::Grpc(addr) => Some(addr.to_string()),
+            #[cfg(feature = "dbus")]
+            Sink::Dbus => Some(podpower_daemon::dbus::BUS_NAME.to_string()),
+            #[cfg(feature = "upower")]
+            Sink::Upower => None,
+            #[cfg(feature = "webhook")]
+            Sink::Webhook { url, .. } => Some(url.clone()),
+            #[cfg(feature = "history")]
+            Sink::History(path) => Some(path.display().to_string()),
+            Sink::Statsd(target) | Sink::Graphite(target) => Some(target.clone()),
+        }
+    }
+
+    fn spawn(
+        &self,
+        listeners: &mut Listeners,
+        readings: &watch::Receiver<Option<Reading>>,
+        diagnostics: &watch::Receiver<Diagnostics>,
+        interval: Duration,
+    ) -> (AbortHandle, RunningSink) {
+        let status = RunningSink {
+            name: self.name().to_string(),
+            target: self.target(),
+            started_at: SystemTime::now(),
+            health: SinkHealth::default(),
+        };
+        (
+            self.start(
+                listeners,
+                readings,
+                diagnostics,
+                interval,
+                status.health.clone(),
+            ),
+            status,
+        )
+    }
+
+    #[cfg_attr(not(feature = "http"), allow(unused_variables))]
+    fn start(
+        &self,
+        listeners: &mut Listeners,
+        readings: &watch::Receiver<Option<Reading>>,
+        diagnostics: &watch::Receiver<Diagnostics>,
+        interval: Duration,
+        health: SinkHealth,
+    ) -> AbortHandle {
+        // Start from the latest reading rather than waiting for the next one
+        let mut readings = readings.clone();
+        readings.mark_changed();
+        let name = self.name();
+        match self.clone() {
+            #[cfg(feature = "http")]
+            Sink::Http(addr) => {
+                tracing::info!("Serving HTTP on http://{}", addr);
+                let diagnostics = diagnostics.clone();
+                listeners.spawn(async move {
+                    podpower_daemon::http::serve_http(addr, readings, diagnostics)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))
+                })
+            }
+            #[cfg(feature = "grpc")]
+            Sink::Grpc(addr) => {
+                tracing::info!("Serving gRPC on {}", addr);
+                listeners.spawn(async move {
+                    podpower_daemon::grpc::serve_grpc(addr, readings)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))
+                })
+            }
+            #[cfg(feature = "dbus")]
+            Sink::Dbus => {
+                tracing::info!(
+                    "Registering {} on the session bus",
+                    podpower_daemon::dbus::BUS_NAME
+                );
+                listeners.spawn(async move {
+                    podpower_daemon::dbus::serve_dbus(readings)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))
+                })
+            }
+            #[cfg(feature = "upower")]
+            Sink::Upower => {
+                tracing::info!("Registering as a BlueZ battery provider");
+                listeners.spawn(async move {
+                    podpower_daemon::upower::serve_upower(readings)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))
+                })
+            }
+            #[cfg(feature = "webhook")]
+            Sink::Webhook { url, secret } => {
+                tracing::info!("Posting status changes to {}", url);
+                listeners.spawn(async move {
+                    podpower_daemon::webhook::serve_webhook(url, secret, readings, health)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))
+                })
+            }
+            #[cfg(feature = "history")]
+            Sink::History(path) => {
+                tracing::info!("Recording battery history to {}", path.display());
+                listeners.spawn(async move {
+                    let history = async {
+                        if let Some(dir) = path.parent() {
+                            std::fs::create_dir_all(dir)?;
+                        }
+                        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+                            podpower_daemon::history::History::open(&path)?,
+                        )
+                    }
+                    .await
+                    .map_err(|e| format!("{}: {}: {}", name, path.display(), e))?;
+                    podpower_daemon::history::serve_history(history, readings)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))
+                })
+            }
+            Sink::Statsd(target) => {
+                tracing::info!("Pushing gauges to StatsD at {}", target);
+                listeners.spawn(async move {
+                    podpower_daemon::statsd::serve_statsd(&target, readings, interval, health)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))
+                })
+            }
+            Sink::Graphite(target) => {
+                tracing::info!("Pushing gauges to Graphite at {}", target);
+                listeners.spawn(async move {
+                    podpower_daemon::statsd::serve_graphite(&target, readings, interval, health)
+                        .await
+                        .map_err(|e| format!("{}: {}", name, e))
+                })
+            }
+        }
+    }
+}
+
+/// SIGHUP asks a running daemon to reload its config file. Windows has no
+/// equivalent, so there a restart is needed.
+struct Reload {
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+}
+
+impl Reload {
+    fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            hangup: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    async fn requested(&mut self) {
+        #[cfg(unix)]
+        if self.hangup.recv().await.is_some() {
+            return;
+        }
+        std::future::pending().await
+    }
+}
@@ -0,0 +1,54 @@
+//! Process exit statuses. Every exit goes through [`Exit`] so that the
+//! numbers scripts rely on are defined in one place.
+
+use podpower_daemon::ScanError;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum Exit {
+    Success = 0,
+    /// Anything without a more specific status below
+    Failure = 1,
+    /// The scan finished without finding AirPods
+    NotFound = 2,
+    /// No usable adapter: none at all, powered off, or busy with another scan
+    Adapter = 3,
+    /// The OS denied access to Bluetooth
+    Permission = 4,
+    /// Invalid command-line arguments
+    Usage = 5,
+}
+
+impl Exit {
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+/// Arguments that parsed but can't be used, e.g. together, which exit with
+/// [`Exit::Usage`] like the ones clap turns down
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+impl From<&ScanError> for Exit {
+    fn from(e: &ScanError) -> Self {
+        match e {
+            ScanError::NoAdapter
+            | ScanError::PoweredOff
+            | ScanError::Blocked { .. }
+            | ScanError::Unavailable(_)
+            | ScanError::ScanInProgress => Exit::Adapter,
+            ScanError::PermissionDenied(_) => Exit::Permission,
+            ScanError::Bluetooth(_) => Exit::Failure,
+        }
+    }
+}
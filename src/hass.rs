@@ -0,0 +1,109 @@
+//! Home Assistant sensor definitions for `podpower hass-config`.
+//!
+//! The sensors read [`HassPayload`](podpower_core::payload::HassPayload),
+//! either from a running daemon's `GET /hass` with the RESTful integration
+//! or from `podpower query --format hass` with the command_line one.
+
+use crate::cli::HassConfigArgs;
+use crate::init::{escape_quotes, shell_quote};
+
+/// The battery sensors, by key in the payload and name in Home Assistant.
+/// Those a device doesn't have stay unavailable.
+const LEVELS: [(&str, &str); 5] = [
+    ("battery", "AirPods battery"),
+    ("left", "AirPods left"),
+    ("right", "AirPods right"),
+    ("case", "AirPods case"),
+    ("headphones", "AirPods Max"),
+];
+
+pub fn run(args: HassConfigArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let yaml = if args.command_line {
+        let exe = shell_quote(&std::env::current_exe()?.to_string_lossy());
+        command_line(&format!("{} query --format hass", exe), args.interval)
+    } else {
+        rest(args.url.trim_end_matches('/'), args.interval)
+    };
+    print!("{}", yaml);
+    Ok(())
+}
+
+/// One request to the daemon's HTTP server feeds every sensor
+fn rest(url: &str, interval: u64) -> String {
+    let mut yaml = format!(
+        "# podpower AirPods battery, see `podpower hass-config --help`. Paste into\n\
+         # configuration.yaml; it reads a daemon running with `--http`.\n\
+         rest:\n  - resource: \"{}/hass\"\n    scan_interval: {}\n    sensor:\n",
+        escape_quotes(url),
+        interval
+    );
+    for (key, name) in LEVELS {
+        yaml += &level("      - ", "        ", key, name);
+    }
+    yaml += &device_name("      - ", "        ");
+    yaml += "    binary_sensor:\n";
+    for (key, name) in &LEVELS[1..] {
+        yaml += &charging("      - ", "        ", key, name);
+    }
+    yaml
+}
+
+/// Every sensor runs the command, which only asks the running daemon
+fn command_line(command: &str, interval: u64) -> String {
+    let command = escape_quotes(command);
+    let mut yaml = "# podpower AirPods battery, see `podpower hass-config --help`. Paste into\n\
+                    # configuration.yaml; it asks a running `podpower daemon`.\n\
+                    command_line:\n"
+        .to_string();
+    let run = |yaml: &mut String| {
+        *yaml += &format!(
+            "      command: \"{}\"\n      scan_interval: {}\n",
+            command, interval
+        );
+    };
+    for (key, name) in LEVELS {
+        yaml += &level("  - sensor:\n      ", "      ", key, name);
+        run(&mut yaml);
+    }
+    yaml += &device_name("  - sensor:\n      ", "      ");
+    run(&mut yaml);
+    for (key, name) in &LEVELS[1..] {
+        yaml += &charging("  - binary_sensor:\n      ", "      ", key, name);
+        yaml += "      payload_on: \"True\"\n      payload_off: \"False\"\n";
+        run(&mut yaml);
+    }
+    yaml
+}
+
+/// A battery percentage, unavailable while the payload has none
+fn level(first: &str, indent: &str, key: &str, name: &str) -> String {
+    format!(
+        "{first}name: \"{name}\"\n\
+         {indent}unique_id: podpower_{key}\n\
+         {indent}value_template: \"{{{{ value_json.{key} }}}}\"\n\
+         {indent}availability: \"{{{{ value_json.{key} is not none }}}}\"\n\
+         {indent}device_class: battery\n\
+         {indent}unit_of_measurement: \"%\"\n\
+         {indent}state_class: measurement\n"
+    )
+}
+
+/// What the AirPods were paired under
+fn device_name(first: &str, indent: &str) -> String {
+    format!(
+        "{first}name: \"AirPods name\"\n\
+         {indent}unique_id: podpower_name\n\
+         {indent}value_template: \"{{{{ value_json.name }}}}\"\n\
+         {indent}availability: \"{{{{ value_json.available }}}}\"\n"
+    )
+}
+
+fn charging(first: &str, indent: &str, key: &str, name: &str) -> String {
+    format!(
+        "{first}name: \"{name} charging\"\n\
+         {indent}unique_id: podpower_{key}_charging\n\
+         {indent}value_template: \"{{{{ value_json.{key}_charging }}}}\"\n\
+         {indent}availability: \"{{{{ value_json.{key}_charging is not none }}}}\"\n\
+         {indent}device_class: battery_charging\n"
+    )
+}
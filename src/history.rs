@@ -0,0 +1,128 @@
+//! `podpower history`: reading back what `podpower daemon --history` recorded.
+
+use crate::cli::{ExportArgs, HealthArgs, HistoryCommand};
+use podpower_daemon::health::ComponentHealth;
+use podpower_daemon::history::{self, History, Sample};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn run(command: HistoryCommand) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match command {
+        HistoryCommand::Export(args) => export(args),
+    }
+}
+
+/// Runtime losses at least this large (in percent) get called out
+const WEAR_WARNING: f64 = 20.0;
+
+fn export(args: ExportArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let since = match args.since {
+        Some(duration) => SystemTime::now()
+            .checked_sub(duration)
+            .unwrap_or(UNIX_EPOCH),
+        None => UNIX_EPOCH,
+    };
+    let samples = open(args.file)?.since(since)?;
+
+    let out: Box<dyn Write> = if args.csv.as_os_str() == "-" {
+        Box::new(std::io::stdout().lock())
+    } else {
+        let file = std::fs::File::create(&args.csv)
+            .map_err(|e| format!("{}: {}", args.csv.display(), e))?;
+        Box::new(file)
+    };
+    let mut out = std::io::BufWriter::new(out);
+    writeln!(out, "timestamp,model,component,level,charging")?;
+    for sample in &samples {
+        writeln!(out, "{}", row(sample))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// `podpower health`
+pub fn health(args: HealthArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let samples = open(args.file)?.since(UNIX_EPOCH)?;
+    let components = podpower_daemon::health::health(&samples);
+    #[cfg(feature = "json")]
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&components)?);
+        return Ok(());
+    }
+    if components.is_empty() {
+        println!("No battery history recorded yet");
+    }
+    for component in &components {
+        print_health(component);
+    }
+    Ok(())
+}
+
+fn print_health(health: &ComponentHealth) {
+    println!(
+        "{} {}: {} charge cycles ({:.1} equivalent)",
+        health.model, health.component, health.cycles, health.equivalent_cycles
+    );
+    for month in &health.runtime {
+        println!("  {}  {:.1} h per charge", month.month, month.hours);
+    }
+    if let (Some(trend), Some(first)) = (health.runtime_trend(), health.runtime.first()) {
+        let direction = if trend < 0.0 { "down" } else { "up" };
+        let warning = if trend <= -WEAR_WARNING {
+            ", the battery is wearing out"
+        } else {
+            ""
+        };
+        println!(
+            "  Runtime {} {:.0}% since {}{}",
+            direction,
+            trend.abs(),
+            first.month,
+            warning
+        );
+    }
+}
+
+/// The history database at `path` or the default location
+fn open(path: Option<PathBuf>) -> Result<History, Box<dyn std::error::Error + Send + Sync>> {
+    let path = database_path(path)?;
+    // Opening would create an empty database, which only hides the mistake
+    if !path.exists() {
+        return Err(format!(
+            "no history at {}. Record one with `podpower daemon --history`",
+            path.display()
+        )
+        .into());
+    }
+    Ok(History::open(&path)?)
+}
+
+/// `path`, or the default location when `None`
+pub fn database_path(
+    path: Option<PathBuf>,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    path.or_else(history::default_path)
+        .ok_or_else(|| "no default history location, $HOME is not set".into())
+}
+
+fn row(sample: &Sample) -> String {
+    format!(
+        "{},{},{},{},{}",
+        humantime::format_rfc3339_millis(sample.observed_at),
+        field(&sample.model),
+        field(&sample.component),
+        sample.level,
+        sample.charging
+    )
+}
+
+/// Quote fields that would otherwise break the row, e.g. a device named
+/// `Alex's AirPods, 2nd pair`
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
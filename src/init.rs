@@ -0,0 +1,147 @@
+//! Status-bar snippets for `podpower init`.
+//!
+//! Every snippet asks a running daemon (`podpower query`) rather than
+//! scanning, so a refresh costs milliseconds, and lives in a file of its own
+//! that the bar's main config includes.
+
+use crate::cli::{Bar, InitArgs};
+use std::path::PathBuf;
+
+/// Refresh interval of the snippets, unless `--interval` says otherwise
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+
+pub fn run(args: InitArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let exe = shell_quote(&std::env::current_exe()?.to_string_lossy());
+    let snippet = snippet(args.bar, &exe, args.interval);
+
+    let Some(path) = args.write else {
+        print!("{}", snippet);
+        return Ok(());
+    };
+    let path = match path {
+        Some(path) => path,
+        None => default_path(args.bar)?,
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, snippet)?;
+    #[cfg(unix)]
+    if args.bar == Bar::Sketchybar {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    eprintln!("Wrote {}", path.display());
+    eprintln!();
+    eprintln!("{}", include_hint(args.bar, &path.to_string_lossy()));
+    eprintln!();
+    eprintln!("It shows what a running `podpower daemon` last saw, so keep one running.");
+    Ok(())
+}
+
+fn snippet(bar: Bar, exe: &str, interval: Option<u64>) -> String {
+    let secs = interval.unwrap_or(DEFAULT_INTERVAL_SECS);
+    match bar {
+        // Waybar hides custom modules with empty output, which the prompt
+        // format prints while no AirPods are found
+        Bar::Waybar => format!(
+            r#"// podpower AirPods battery, see `podpower init waybar --help`
+{{
+    "custom/podpower": {{
+        "exec": "{exec}",
+        "interval": {secs},
+        "format": "🎧 {{}}",
+        "tooltip": false
+    }}
+}}
+"#,
+            exec = escape_quotes(&format!("{} query --format prompt", exe)),
+        ),
+        Bar::Polybar => format!(
+            r#"; podpower AirPods battery, see `podpower init polybar --help`
+[module/podpower]
+type = custom/script
+exec = {exe} query --format prompt
+interval = {secs}
+label = %output%
+"#
+        ),
+        // `#()` commands rerun with the status line, so the interval is
+        // tmux-wide and only set when asked for
+        Bar::Tmux => {
+            let mut conf =
+                "# podpower AirPods battery, see `podpower init tmux --help`\n".to_string();
+            if let Some(secs) = interval {
+                conf += &format!("set -g status-interval {}\n", secs);
+            }
+            let command = format!("{} query --format tmux", exe);
+            conf += &format!(
+                "set -ag status-right \" #({})\"\n",
+                escape_quotes(&command.replace('#', "##"))
+            );
+            conf
+        }
+        Bar::Sketchybar => format!(
+            r#"#!/bin/sh
+# podpower AirPods battery plugin for SketchyBar. Add the item in sketchybarrc:
+#   sketchybar --add item podpower right \
+#              --set podpower update_freq={secs} script="$PLUGIN_DIR/podpower.sh"
+eval "sketchybar --set $({exe} query --format sketchybar)"
+"#
+        ),
+    }
+}
+
+/// Where `--write` puts the snippet by default, next to the bar's own config
+fn default_path(bar: Bar) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME").ok_or("HOME is not set")?).join(".config"),
+    };
+    Ok(match bar {
+        Bar::Waybar => config.join("waybar").join("podpower.jsonc"),
+        Bar::Polybar => config.join("polybar").join("podpower.ini"),
+        Bar::Tmux => config.join("tmux").join("podpower.conf"),
+        Bar::Sketchybar => config
+            .join("sketchybar")
+            .join("plugins")
+            .join("podpower.sh"),
+    })
+}
+
+/// How to hook the written file into the bar's main config
+fn include_hint(bar: Bar, path: &str) -> String {
+    match bar {
+        Bar::Waybar => format!(
+            "Include it from your Waybar config and add the module to a bar:\n  \"include\": [\"{}\"],\n  \"modules-right\": [\"custom/podpower\", ...]",
+            escape_quotes(path)
+        ),
+        Bar::Polybar => format!(
+            "Include it from your Polybar config and add the module to a bar:\n  include-file = {}\n  modules-right = podpower ...",
+            path
+        ),
+        Bar::Tmux => format!(
+            "Source it from ~/.tmux.conf:\n  source-file {}",
+            shell_quote(path)
+        ),
+        Bar::Sketchybar => "Add the item to sketchybarrc as shown at the top of the plugin, then run `sketchybar --reload`".to_string(),
+    }
+}
+
+/// Quote `arg` for `sh -c`, which every bar runs its commands through
+pub(crate) fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+:@".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Escape `text` for a double-quoted JSON or tmux string
+pub(crate) fn escape_quotes(text: &str) -> String {
+    text.replace('\\', r"\\").replace('"', r#"\""#)
+}
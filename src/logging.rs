@@ -0,0 +1,114 @@
+//! Diagnostic logging to stderr via `tracing`, or to the systemd journal
+//! when run as a service.
+
+use clap::ValueEnum;
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+
+/// Used when neither `--log-level` nor `RUST_LOG` is set
+const DEFAULT_FILTER: &str = "info";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per event
+    #[cfg(feature = "json")]
+    Json,
+    /// Structured entries in the systemd journal, with the level as the
+    /// priority and event fields as `PODPOWER_*` fields
+    #[cfg(all(feature = "journald", target_os = "linux"))]
+    Journald,
+}
+
+/// Install the global subscriber. `level` takes precedence over `RUST_LOG`
+/// and accepts the same directives (e.g. `debug` or `podpower_daemon=trace`).
+/// Without a `format`, logs go to the journal when stderr already does, and
+/// are text otherwise.
+pub fn init(level: Option<&str>, format: Option<LogFormat>) {
+    let filter = match level {
+        Some(level) => EnvFilter::new(level),
+        None => {
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER))
+        }
+    };
+    let format = format.unwrap_or_else(default_format);
+    #[cfg(all(feature = "journald", target_os = "linux"))]
+    if format == LogFormat::Journald {
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::util::SubscriberInitExt;
+
+        match journald::layer() {
+            Ok(layer) => tracing_subscriber::registry()
+                .with(filter)
+                .with(layer)
+                .init(),
+            Err(e) => {
+                init_stderr(filter, LogFormat::Text);
+                tracing::warn!("Logging to stderr, the journal isn't available: {}", e);
+            }
+        }
+        return;
+    }
+    init_stderr(filter, format);
+}
+
+fn init_stderr(filter: EnvFilter, format: LogFormat) {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal());
+    match format {
+        #[cfg(feature = "json")]
+        LogFormat::Json => builder.json().init(),
+        _ => builder.with_target(false).init(),
+    }
+}
+
+#[cfg(all(feature = "journald", target_os = "linux"))]
+fn default_format() -> LogFormat {
+    if journald::is_stderr() {
+        LogFormat::Journald
+    } else {
+        LogFormat::Text
+    }
+}
+
+#[cfg(not(all(feature = "journald", target_os = "linux")))]
+fn default_format() -> LogFormat {
+    LogFormat::Text
+}
+
+#[cfg(all(feature = "journald", target_os = "linux"))]
+mod journald {
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::MetadataExt;
+
+    /// Whether stderr is connected to the journal, as for systemd services.
+    /// systemd says so in `$JOURNAL_STREAM` as the device and inode numbers
+    /// of the stream, which a redirected stderr no longer matches.
+    pub fn is_stderr() -> bool {
+        let Some(stream) = std::env::var_os("JOURNAL_STREAM") else {
+            return false;
+        };
+        let Some((device, inode)) = stream.to_str().and_then(|s| s.split_once(':')) else {
+            return false;
+        };
+        let Ok(metadata) = std::io::stderr()
+            .as_fd()
+            .try_clone_to_owned()
+            .and_then(|fd| std::fs::File::from(fd).metadata())
+        else {
+            return false;
+        };
+        device.parse() == Ok(metadata.dev()) && inode.parse() == Ok(metadata.ino())
+    }
+
+    /// Entries as `podpower`, failing when the journal's socket can't be
+    /// opened
+    pub fn layer() -> std::io::Result<tracing_journald::Layer> {
+        Ok(tracing_journald::layer()?
+            .with_syslog_identifier("podpower".to_string())
+            .with_field_prefix(Some("PODPOWER".to_string())))
+    }
+}
@@ -1,274 +1,374 @@
-use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter};
-use btleplug::platform::Manager;
-use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use tokio::time::sleep;
-
-const APPLE_MANUFACTURER_ID: u16 = 0x004c; // Apple Inc.
-const AIRPODS_DATA_LENGTH: usize = 27;
-const SCAN_TIMEOUT_SECS: u64 = 3;
-const POLL_INTERVAL_MS: u64 = 100; // Check for new devices every 100ms
-const MIN_RSSI_THRESHOLD: i16 = -60; // Only consider devices with RSSI > -60 dBm (strong signal = nearby/connected)
-
-// Byte positions in the 27-byte manufacturer data
-const BYTE_MODEL_HIGH: usize = 3;
-const BYTE_MODEL_LOW: usize = 4;
-const BYTE_FLIP: usize = 5;
-const BYTE_BATTERY_PODS: usize = 6;
-const BYTE_BATTERY_CASE_AND_CHARGING: usize = 7;
-
-// Bit masks
-const MASK_FLIP_BIT: u8 = 0x20;  // Bit 5
-const MASK_CHARGING_LEFT: u8 = 0x01;
-const MASK_CHARGING_RIGHT: u8 = 0x02;
-const MASK_CHARGING_CASE: u8 = 0x04;
-const BATTERY_DISCONNECTED: u8 = 15;
-
-/// A single component (earbud, case, or headphones) with its battery status
-#[derive(Debug, Serialize, Deserialize)]
-struct Component {
-    name: String,
-    battery: u8,
-    charging: bool,
-}
-
-/// Main AirPods status with unified component-based structure
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
-enum AirPodsStatus {
-    InEar {
-        model: String,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        battery: Option<u8>,
-        components: Vec<Component>,
-    },
-    OverEar {
-        model: String,
-        battery: u8,
-        components: Vec<Component>,
-    },
-}
+mod cli;
+mod config;
+mod daemon;
+mod exit;
+#[cfg(feature = "json")]
+mod hass;
+#[cfg(feature = "history")]
+mod history;
+mod init;
+mod logging;
+mod output;
+#[cfg(feature = "schema")]
+mod schema;
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+mod service;
+#[cfg(all(feature = "tray", target_os = "linux"))]
+mod tray;
+#[cfg(feature = "tui")]
+mod tui;
+
+use clap::Parser;
+use cli::{Cli, Command, OutputArgs};
+use exit::{Exit, UsageError};
+use output::i18n::{Lang, Text};
+use podpower_core::payload::ScanStats;
+use podpower_core::{AirPodsStatus, AppleDecoder};
+use podpower_daemon::{ScanError, ScanTally, scan_all, scan_with};
+#[cfg(feature = "json")]
+use std::time::{Duration, UNIX_EPOCH};
+use std::time::{Instant, SystemTime};
+
+/// How long the prompt format waits for the daemon before printing nothing
+#[cfg(feature = "json")]
+const PROMPT_TIMEOUT: Duration = Duration::from_millis(80);
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    match scan_for_airpods().await {
-        Ok(Some(status)) => {
-            println!("{}", serde_json::to_string_pretty(&status)?);
-            Ok(())
-        }
-        Ok(None) => {
-            eprintln!("AirPods not found");
-            std::process::exit(1);
-        }
+async fn main() {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
         Err(e) => {
+            let _ = e.print();
+            // `--help` and `--version` also arrive here
+            if e.use_stderr() {
+                Exit::Usage
+            } else {
+                Exit::Success
+            }
+            .exit()
+        }
+    };
+    // --quiet silences warnings, but an explicit --log-level still wins
+    let log_level = cli
+        .log
+        .log_level
+        .as_deref()
+        .or(cli.output.quiet.then_some("error"));
+    logging::init(log_level, cli.log.log_format);
+    let format = cli.output.format;
+    let render_options = cli.output.render_options();
+    let out = cli.output.out.clone();
+
+    if let Err(e) = run(cli).await {
+        let Some(e) = e.downcast_ref::<ScanError>() else {
             eprintln!("Error: {}", e);
-            std::process::exit(1);
+            if e.is::<UsageError>() {
+                Exit::Usage
+            } else {
+                Exit::Failure
+            }
+            .exit()
+        };
+        // Scripts tell these apart by exit status, or by `code` in formats
+        // with an error payload
+        match output::render_error(&e.to_string(), e.code(), None, format, &render_options) {
+            Ok(Some(bytes)) => {
+                let _ = output::write(&bytes, out.as_deref());
+            }
+            _ => eprintln!("Error: {}", e),
         }
+        Exit::from(e).exit()
     }
 }
 
-/// Extract the high nibble (4 bits) from a byte
-#[inline]
-fn high_nibble(byte: u8) -> u8 {
-    (byte >> 4) & 0x0f
-}
-
-/// Extract the low nibble (4 bits) from a byte
-#[inline]
-fn low_nibble(byte: u8) -> u8 {
-    byte & 0x0f
-}
-
-async fn scan_for_airpods() -> Result<Option<AirPodsStatus>, Box<dyn std::error::Error>> {
-    let manager = Manager::new().await?;
-    let adapters = manager.adapters().await?;
-
-    let adapter = adapters
-        .into_iter()
-        .next()
-        .ok_or("No Bluetooth adapters found")?;
-
-    // Start scan, providing helpful error message if already in progress
-    if let Err(e) = adapter.start_scan(ScanFilter::default()).await {
-        if e.to_string().contains("already in progress") {
-            return Err(
-                "Bluetooth scan already in progress. Try: sudo systemctl restart bluetooth".into(),
-            );
-        }
-        return Err(e.into());
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    #[cfg(feature = "json")]
+    if cli.output.fields.is_some() {
+        output::check_fields(cli.output.format)?;
     }
-
-    // Poll for AirPods up to SCAN_TIMEOUT_SECS seconds
-    let start = std::time::Instant::now();
-    let timeout = Duration::from_secs(SCAN_TIMEOUT_SECS);
-    let poll_interval = Duration::from_millis(POLL_INTERVAL_MS);
-
-    while start.elapsed() < timeout {
-        let peripherals = adapter.peripherals().await?;
-
-        for peripheral in peripherals {
-            let properties = peripheral.properties().await?;
-
-            if let Some(props) = properties
-                && let Some(data) = props.manufacturer_data.get(&APPLE_MANUFACTURER_ID)
-                && data.len() == AIRPODS_DATA_LENGTH
-            {
-                // Check RSSI - only consider devices with strong signal (likely connected/nearby)
-                // Connected AirPods typically have RSSI between -30 and -60 dBm
-                // Distant/disconnected ones are usually below -70 dBm
-                if let Some(rssi) = props.rssi {
-                    if rssi < MIN_RSSI_THRESHOLD {
-                        continue; // Skip weak signals
-                    }
+    match cli.command {
+        Some(Command::Daemon(args)) => daemon::run(*args, cli.scan.scan_options()?).await,
+        #[cfg(feature = "json")]
+        Some(Command::Query(args)) => query(args, &cli.output).await,
+        #[cfg(feature = "json")]
+        Some(Command::Fleet(args)) => daemon::fleet(args).await,
+        #[cfg(feature = "notify")]
+        Some(Command::Snooze(args)) => daemon::snooze(args).await,
+        Some(Command::Init(args)) => init::run(args),
+        #[cfg(feature = "json")]
+        Some(Command::HassConfig(args)) => hass::run(args),
+        #[cfg(feature = "history")]
+        Some(Command::History { command }) => history::run(command),
+        #[cfg(feature = "history")]
+        Some(Command::Health(args)) => history::health(args),
+        #[cfg(all(feature = "tray", target_os = "linux"))]
+        Some(Command::Tray { interval }) => {
+            tray::run(
+                std::time::Duration::from_secs(interval),
+                cli.scan.scan_options()?,
+            )
+            .await
+        }
+        #[cfg(feature = "tui")]
+        Some(Command::Tui { interval }) => {
+            tui::run(
+                std::time::Duration::from_secs(interval),
+                cli.scan.scan_options()?,
+            )
+            .await
+        }
+        #[cfg(feature = "schema")]
+        Some(Command::Schema { kind }) => schema::print(kind),
+        #[cfg(all(feature = "aap", target_os = "linux"))]
+        Some(Command::SetMode { mode }) => set_mode(mode.into(), cli.scan.model.as_deref()).await,
+        #[cfg(feature = "json")]
+        None if cli.output.format == output::Format::Prompt => prompt(&cli.output).await,
+        None if cli.scan.all => {
+            output::check_all(cli.output.format)?;
+            let options = cli.scan.scan_options()?;
+            let mut tally = ScanTally::default();
+            let started = Instant::now();
+            let sightings = scan_all(&options, |advertisement| {
+                tally.observe(advertisement);
+                if cli.log.verbose {
+                    print_advertisement(advertisement);
                 }
-
-                if let Some(status) = parse_airpods_data(data) {
-                    adapter.stop_scan().await?;
-                    return Ok(Some(status));
+            })
+            .await?;
+            if cli.log.verbose {
+                print_scan_stats(&tally.stats(started.elapsed()));
+            }
+            if sightings.is_empty() {
+                return print_status(None, Default::default(), SystemTime::now(), &cli.output);
+            }
+            let render_options = cli.output.render_options();
+            for sighting in &sightings {
+                for note in
+                    output::warning_notes(&sighting.warnings, cli.output.format, &render_options)
+                {
+                    eprintln!("{}", note);
                 }
             }
+            let bytes = output::render_all(
+                &sightings,
+                SystemTime::now(),
+                cli.output.format,
+                &render_options,
+            )?;
+            output::write(&bytes, cli.output.out.as_deref())?;
+            Ok(())
+        }
+        None => {
+            let options = cli.scan.scan_options()?;
+            let mut tally = ScanTally::default();
+            let started = Instant::now();
+            let sighting = scan_with(&options, |advertisement| {
+                tally.observe(advertisement);
+                if cli.log.verbose {
+                    print_advertisement(advertisement);
+                }
+            })
+            .await?;
+            let stats = tally.stats(started.elapsed());
+            if cli.log.verbose {
+                print_scan_stats(&stats);
+            }
+            let mut provenance: output::Provenance =
+                sighting.as_ref().map(Into::into).unwrap_or_default();
+            provenance.scan = cli.scan.scan_stats.then_some(stats);
+            print_status(
+                sighting.map(|s| s.status),
+                provenance,
+                SystemTime::now(),
+                &cli.output,
+            )
         }
-
-        sleep(poll_interval).await;
     }
-
-    adapter.stop_scan().await?;
-    Ok(None)
 }
 
-/// Parse AirPods manufacturer data from BLE advertisement
-///
-/// # BLE Packet Structure (27 bytes)
-/// Based on reverse engineering from OpenPods project:
-/// - Byte 3-4: Device model identifier
-/// - Byte 5: Flip bit (determines left/right orientation)
-/// - Byte 6: Left and right pod battery levels (4 bits each)
-/// - Byte 7: Case battery + charging status
-///   - High nibble (bits 4-7): Charging flags
-///   - Low nibble (bits 0-3): Case battery level
-fn parse_airpods_data(data: &[u8]) -> Option<AirPodsStatus> {
-    if data.len() != AIRPODS_DATA_LENGTH {
-        return None;
+#[cfg(feature = "json")]
+async fn query(
+    args: cli::QueryArgs,
+    output_args: &OutputArgs,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use podpower_daemon::control::{self, Response};
+
+    let path = args.socket.unwrap_or_else(control::default_socket_path);
+    match control::request(&path, "status").await {
+        Ok(Response::Status {
+            status,
+            observed_at,
+            name,
+            confidence,
+            partial,
+            listening_mode,
+            data,
+            warnings,
+            ..
+        }) => print_status(
+            status,
+            output::Provenance {
+                name,
+                confidence,
+                partial,
+                listening_mode,
+                scan: None,
+                data,
+                warnings,
+            },
+            UNIX_EPOCH + Duration::from_millis(observed_at),
+            output_args,
+        ),
+        Ok(Response::Error { message }) => Err(message.into()),
+        Ok(
+            Response::Daemon(_)
+            | Response::Scanning { .. }
+            | Response::Snoozed { .. }
+            | Response::Fleet(_),
+        ) => Err("unexpected answer from the daemon".into()),
+        Err(e) => Err(format!(
+            "could not reach the daemon at {} ({}). Is `podpower daemon` running?",
+            path.display(),
+            e
+        )
+        .into()),
     }
+}
 
-    // Check if left/right are flipped
-    let flip = (data[BYTE_FLIP] & MASK_FLIP_BIT) == 0;
-
-    // Detect model from 2-byte identifier
-    let model_byte = low_nibble(data[BYTE_MODEL_HIGH]);
-    let model_full = ((data[BYTE_MODEL_HIGH] as u16) << 8) | (data[BYTE_MODEL_LOW] as u16);
+/// `podpower set-mode`
+#[cfg(all(feature = "aap", target_os = "linux"))]
+async fn set_mode(
+    mode: podpower_core::ListeningMode,
+    model: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use podpower_daemon::aap;
 
-    // See: https://github.com/d4rken-org/capod/blob/5860bbffb6b2e59feca450bc234595314e842366/app/src/main/java/eu/darken/capod/pods/core/apple/airpods/AirPodsGen4.kt#L78
-    let model = match model_full {
-        0x0220 => "AirPods 1",
-        0x0F20 => "AirPods 2",
-        0x1320 => "AirPods 3",
-        0x1920 => "AirPods 4",
-        0x0E20 => "AirPods Pro",
-        0x1420 | 0x2420 => "AirPods Pro 2",
-        0x2720 => "AirPods Pro 3",
-        0x0A20 | 0x1F20 => "AirPods Max",
-        _ => "AirPods",
+    let Some((name, address)) = aap::connected_airpods(model).await? else {
+        return Err("no AirPods connected to this machine".into());
     };
+    aap::set_listening_mode(address, mode)
+        .await
+        .map_err(|e| format!("{}: {}", name, e))?;
+    println!("{}: listening mode set to {}", name, mode);
+    Ok(())
+}
 
-    // Check if this is a single-battery device (AirPods Max)
-    let is_max_device = model_byte == 0x0A;
-
-    let battery_byte = data[BYTE_BATTERY_PODS];
-
-    let case_charge_byte = data[BYTE_BATTERY_CASE_AND_CHARGING];
-    let case_battery_raw = low_nibble(case_charge_byte);
-    let charging_flags = high_nibble(case_charge_byte);
-
-    if is_max_device {
-        // For single-battery devices (AirPods Max), use low nibble of byte 6
-        let single_raw = low_nibble(battery_byte);
-        let battery = battery_level(single_raw)?;
-        let charging = (charging_flags & MASK_CHARGING_LEFT) != 0;
+/// A shell prompt can't wait seconds for a scan, so the prompt format only
+/// asks a running daemon and treats a slow or missing one as "not found"
+#[cfg(feature = "json")]
+async fn prompt(output_args: &OutputArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use podpower_daemon::control::{self, Response};
 
-        let components = vec![Component {
-            name: "headphones".into(),
-            battery,
-            charging,
-        }];
+    let path = control::default_socket_path();
+    let status = match tokio::time::timeout(PROMPT_TIMEOUT, control::request(&path, "status")).await
+    {
+        Ok(Ok(Response::Status { status, .. })) => status,
+        _ => None,
+    };
+    print_status(status, Default::default(), SystemTime::now(), output_args)
+}
 
-        Some(AirPodsStatus::OverEar {
-            model: model.into(),
-            battery,
-            components,
-        })
+/// `--verbose` line on stderr for an advertisement seen during the scan
+fn print_advertisement(advertisement: &podpower_daemon::Advertisement) {
+    use podpower_daemon::Verdict;
+
+    let rssi = advertisement
+        .rssi
+        .map_or_else(|| "? dBm".to_string(), |rssi| format!("{} dBm", rssi));
+    let payload = output::hex(&advertisement.data);
+    let verdict = match &advertisement.verdict {
+        Verdict::Accepted(status) if advertisement.vendor == AppleDecoder::NAME => {
+            let model = podpower_core::model_id(&advertisement.data).unwrap_or_default();
+            match podpower_core::model_name(model) {
+                Some(_) => format!("accepted: {} (model 0x{:04x})", status.model(), model),
+                None => format!("accepted: unknown model 0x{:04x}", model),
+            }
+        }
+        Verdict::Accepted(status) => format!("accepted: {}", status.model()),
+        Verdict::WeakSignal(_) => "rejected: signal too weak, probably not yours".to_string(),
+        Verdict::Filtered(status) => format!(
+            "rejected: {} doesn't match --model/--address",
+            status.model()
+        ),
+        Verdict::Rejected(e) => format!("rejected: {}", e),
+    };
+    let connected = if advertisement.connected {
+        " (connected to this host)"
     } else {
-        // For dual-pod devices (AirPods, AirPods Pro), extract left and right
-        let (left_raw, right_raw) = if flip {
-            (high_nibble(battery_byte), low_nibble(battery_byte))
-        } else {
-            (low_nibble(battery_byte), high_nibble(battery_byte))
-        };
-
-        let left = battery_level(left_raw);
-        let right = battery_level(right_raw);
-        let case = battery_level(case_battery_raw);
-
-        // Parse charging flags (respecting flip bit)
-        let (left_mask, right_mask) = if flip {
-            (MASK_CHARGING_LEFT, MASK_CHARGING_RIGHT)
-        } else {
-            (MASK_CHARGING_RIGHT, MASK_CHARGING_LEFT)
-        };
-        let charging_left = (charging_flags & left_mask) != 0;
-        let charging_right = (charging_flags & right_mask) != 0;
-        let charging_case = (charging_flags & MASK_CHARGING_CASE) != 0;
-
-        // Build components array (only include connected components)
-        let mut components = Vec::new();
+        ""
+    };
+    eprintln!(
+        "{}  {:>7}  {:<8}  {}  {}{}",
+        advertisement.address, rssi, advertisement.vendor, payload, verdict, connected
+    );
+}
 
-        if let Some(left_battery) = left {
-            components.push(Component {
-                name: "left".into(),
-                battery: left_battery,
-                charging: charging_left,
-            });
-        }
+/// `--verbose` summary on stderr once the scan is over
+fn print_scan_stats(stats: &ScanStats) {
+    eprintln!(
+        "Scanned for {:.1} s: {} advertisements ({} from Apple) from {} devices, {} turned down",
+        stats.duration_ms as f64 / 1000.0,
+        stats.advertisements,
+        stats.apple_advertisements,
+        stats.devices_considered,
+        stats.devices_rejected
+    );
+}
 
-        if let Some(right_battery) = right {
-            components.push(Component {
-                name: "right".into(),
-                battery: right_battery,
-                charging: charging_right,
-            });
+/// `observed_at` is when the scan that produced `status` finished
+fn print_status(
+    status: Option<AirPodsStatus>,
+    provenance: output::Provenance,
+    observed_at: SystemTime,
+    output_args: &OutputArgs,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match status {
+        Some(status) => {
+            let options = output_args.render_options();
+            for note in output::warning_notes(&provenance.warnings, output_args.format, &options) {
+                eprintln!("{}", note);
+            }
+            let bytes = output::render(
+                &status,
+                provenance,
+                observed_at,
+                output_args.format,
+                &options,
+            )?;
+            output::write(&bytes, output_args.out.as_deref())?;
+            Ok(())
         }
-
-        if let Some(case_battery) = case {
-            components.push(Component {
-                name: "case".into(),
-                battery: case_battery,
-                charging: charging_case,
-            });
+        None => {
+            if let Some(text) = output::render_missing(output_args.format) {
+                output::write(format!("{}\n", text).as_bytes(), output_args.out.as_deref())?;
+                return Ok(());
+            }
+            let options = output_args.render_options();
+            if output_args.empty_ok {
+                if let Some(bytes) = output::render_empty(output_args.format, &options)? {
+                    output::write(&bytes, output_args.out.as_deref())?;
+                }
+                return Ok(());
+            }
+            // Machine-readable errors and plain-v2 read the same in every
+            // language, for scripts
+            let not_found = Lang::En.text(Text::NotFound);
+            match output::render_error(
+                not_found,
+                "not_found",
+                provenance.scan,
+                output_args.format,
+                &options,
+            )? {
+                Some(bytes) => {
+                    output::write(&bytes, output_args.out.as_deref())?;
+                }
+                None if options.quiet => {}
+                None if output_args.format == output::Format::PlainV2 => eprintln!("{}", not_found),
+                // The other text formats are translated
+                None => eprintln!("{}", options.lang.text(Text::NotFound)),
+            }
+            Exit::NotFound.exit()
         }
-
-        // Calculate top-level battery: minimum of connected earbuds (ignore case)
-        let battery = match (left, right) {
-            (Some(l), Some(r)) => Some(l.min(r)),
-            (Some(l), None) => Some(l),
-            (None, Some(r)) => Some(r),
-            (None, None) => None,
-        };
-
-        Some(AirPodsStatus::InEar {
-            model: model.into(),
-            battery,
-            components,
-        })
-    }
-}
-
-/// Convert raw battery value (0-10) to percentage (5-100%)
-/// Returns None if the device is disconnected (value 15)
-fn battery_level(raw: u8) -> Option<u8> {
-    match raw {
-        10 => Some(100),
-        0..=9 => Some(raw * 10 + 5),
-        BATTERY_DISCONNECTED => None,
-        _ => None,
     }
 }
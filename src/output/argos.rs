@@ -0,0 +1,57 @@
+//! Argos/BitBar dropdown format, also understood by Plasma's Command Output
+//! widget: panel text first, then the menu after `---`
+
+use super::style::Level;
+use super::{RenderOptions, plain, prompt};
+use podpower_core::AirPodsStatus;
+use std::fmt::Write;
+
+const ICON: &str = "iconName=audio-headphones";
+const REFRESH: &str = "Refresh | refresh=true";
+
+/// ```text
+/// L85 R90 | iconName=audio-headphones
+/// ---
+/// AirPods Pro
+/// --Left: 85% | color=green
+/// --Right: 90% (charging) | color=green
+/// --Case: 45% | color=orange
+/// ---
+/// Refresh | refresh=true
+/// ```
+pub fn render(status: &AirPodsStatus) -> String {
+    let panel = prompt::render(status);
+    let panel = if panel.is_empty() {
+        status.model().to_string()
+    } else {
+        panel
+    };
+    let mut out = String::new();
+    let _ = writeln!(out, "{} | {}", panel, ICON);
+    let _ = writeln!(out, "---");
+    let _ = writeln!(out, "{}", status.model());
+    for component in status.components() {
+        let _ = writeln!(
+            out,
+            "--{} | color={}",
+            plain::component_line(component, &RenderOptions::default()),
+            colour(component.battery)
+        );
+    }
+    let _ = writeln!(out, "---");
+    out.push_str(REFRESH);
+    out
+}
+
+/// Panel and menu shown when no AirPods were found
+pub fn render_missing() -> String {
+    format!("No AirPods | {}\n---\n{}", ICON, REFRESH)
+}
+
+fn colour(battery: u8) -> &'static str {
+    match Level::of(battery) {
+        Level::Low => "red",
+        Level::Medium => "orange",
+        Level::High => "green",
+    }
+}
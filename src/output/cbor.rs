@@ -0,0 +1,50 @@
+use super::{Provenance, RenderOptions};
+use podpower_core::AirPodsStatus;
+use podpower_core::payload::ScanStats;
+use podpower_daemon::Sighting;
+use std::time::SystemTime;
+
+/// The JSON payload encoded as CBOR (RFC 8949)
+pub fn render(
+    status: &AirPodsStatus,
+    provenance: Provenance,
+    observed_at: SystemTime,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let payload = super::json::payload(status, provenance, observed_at, options);
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&payload, &mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn render_all(
+    sightings: &[Sighting],
+    observed_at: SystemTime,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let payloads = super::json::payloads(sightings, observed_at, options);
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&payloads, &mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn render_error(
+    message: &str,
+    code: &str,
+    scan: Option<ScanStats>,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let payload = super::json::error_payload(message, code, scan, options);
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&payload, &mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn render_empty(
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let payload = super::json::empty_payload(options);
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&payload, &mut bytes)?;
+    Ok(bytes)
+}
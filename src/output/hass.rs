@@ -0,0 +1,22 @@
+//! Flat JSON for Home Assistant's command_line sensors, see
+//! [`HassPayload`] and `podpower hass-config`
+
+use podpower_core::AirPodsStatus;
+use podpower_core::payload::HassPayload;
+
+/// ```text
+/// {"available":true,"model":"AirPods Pro","name":"AirPods Pro","battery":85,"left":85,...}
+/// ```
+pub fn render(status: &AirPodsStatus, name: Option<String>) -> String {
+    to_string(&HassPayload::new(Some(status), name))
+}
+
+/// Not finding the AirPods is a state like any other, so sensors turn
+/// unavailable rather than failing
+pub fn render_missing() -> String {
+    to_string(&HassPayload::new(None, None))
+}
+
+fn to_string(payload: &HassPayload) -> String {
+    serde_json::to_string(payload).expect("the payload has only plain fields")
+}
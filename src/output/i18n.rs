@@ -0,0 +1,158 @@
+//! Translations of the few words in the text formats.
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Es,
+    Fr,
+    It,
+    Ja,
+    Nl,
+    Pt,
+    Sv,
+    Zh,
+}
+
+/// A translatable string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    Battery,
+    Charging,
+    Left,
+    Right,
+    Case,
+    Headphones,
+    Disconnected,
+    NotFound,
+}
+
+impl Lang {
+    /// The language of the first of `LC_ALL`, `LC_MESSAGES` and `LANG` that is
+    /// set, English when that isn't one we have
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|locale| Self::from_locale(&locale))
+            .unwrap_or_default()
+    }
+
+    /// `de_DE.UTF-8` -> `De`
+    fn from_locale(locale: &str) -> Option<Self> {
+        let language = locale.split(['_', '.', '@', '-']).next()?;
+        Self::from_str(&language.to_ascii_lowercase(), true).ok()
+    }
+
+    pub fn text(self, text: Text) -> &'static str {
+        use Lang::*;
+        use Text::*;
+        match (self, text) {
+            (En, Battery) => "Battery",
+            (En, Charging) => "charging",
+            (En, Left) => "Left",
+            (En, Right) => "Right",
+            (En, Case) => "Case",
+            (En, Headphones) => "Headphones",
+            (En, Disconnected) => "disconnected",
+            (En, NotFound) => "AirPods not found",
+
+            (De, Battery) => "Akku",
+            (De, Charging) => "lädt",
+            (De, Left) => "Links",
+            (De, Right) => "Rechts",
+            (De, Case) => "Case",
+            (De, Headphones) => "Kopfhörer",
+            (De, Disconnected) => "getrennt",
+            (De, NotFound) => "AirPods nicht gefunden",
+
+            (Es, Battery) => "Batería",
+            (Es, Charging) => "cargando",
+            (Es, Left) => "Izquierdo",
+            (Es, Right) => "Derecho",
+            (Es, Case) => "Estuche",
+            (Es, Headphones) => "Auriculares",
+            (Es, Disconnected) => "desconectado",
+            (Es, NotFound) => "No se encontraron los AirPods",
+
+            (Fr, Battery) => "Batterie",
+            (Fr, Charging) => "en charge",
+            (Fr, Left) => "Gauche",
+            (Fr, Right) => "Droit",
+            (Fr, Case) => "Boîtier",
+            (Fr, Headphones) => "Casque",
+            (Fr, Disconnected) => "déconnecté",
+            (Fr, NotFound) => "AirPods introuvables",
+
+            (It, Battery) => "Batteria",
+            (It, Charging) => "in carica",
+            (It, Left) => "Sinistro",
+            (It, Right) => "Destro",
+            (It, Case) => "Custodia",
+            (It, Headphones) => "Cuffie",
+            (It, Disconnected) => "disconnesso",
+            (It, NotFound) => "AirPods non trovati",
+
+            (Ja, Battery) => "バッテリー",
+            (Ja, Charging) => "充電中",
+            (Ja, Left) => "左",
+            (Ja, Right) => "右",
+            (Ja, Case) => "ケース",
+            (Ja, Headphones) => "ヘッドホン",
+            (Ja, Disconnected) => "未接続",
+            (Ja, NotFound) => "AirPods が見つかりません",
+
+            (Nl, Battery) => "Batterij",
+            (Nl, Charging) => "laadt op",
+            (Nl, Left) => "Links",
+            (Nl, Right) => "Rechts",
+            (Nl, Case) => "Case",
+            (Nl, Headphones) => "Koptelefoon",
+            (Nl, Disconnected) => "niet verbonden",
+            (Nl, NotFound) => "AirPods niet gevonden",
+
+            (Pt, Battery) => "Bateria",
+            (Pt, Charging) => "carregando",
+            (Pt, Left) => "Esquerdo",
+            (Pt, Right) => "Direito",
+            (Pt, Case) => "Estojo",
+            (Pt, Headphones) => "Fones",
+            (Pt, Disconnected) => "desconectado",
+            (Pt, NotFound) => "AirPods não encontrados",
+
+            (Sv, Battery) => "Batteri",
+            (Sv, Charging) => "laddar",
+            (Sv, Left) => "Vänster",
+            (Sv, Right) => "Höger",
+            (Sv, Case) => "Fodral",
+            (Sv, Headphones) => "Hörlurar",
+            (Sv, Disconnected) => "frånkopplad",
+            (Sv, NotFound) => "AirPods hittades inte",
+
+            (Zh, Battery) => "电量",
+            (Zh, Charging) => "充电中",
+            (Zh, Left) => "左耳",
+            (Zh, Right) => "右耳",
+            (Zh, Case) => "充电盒",
+            (Zh, Headphones) => "耳机",
+            (Zh, Disconnected) => "未连接",
+            (Zh, NotFound) => "未找到 AirPods",
+        }
+    }
+
+    /// Label of a component by its JSON `name`, unknown names as they are
+    pub fn component(self, name: &str) -> String {
+        let text = match name {
+            "left" => Text::Left,
+            "right" => Text::Right,
+            "case" => Text::Case,
+            "headphones" => Text::Headphones,
+            _ => return name.to_string(),
+        };
+        self.text(text).to_string()
+    }
+}
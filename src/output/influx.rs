@@ -0,0 +1,43 @@
+//! InfluxDB line protocol, one line per component
+
+use podpower_core::AirPodsStatus;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MEASUREMENT: &str = "airpods";
+
+/// One line per component, timestamped with when the status was observed,
+/// which for a daemon's status can be a while ago:
+///
+/// ```text
+/// airpods,model=AirPods\ Pro,component=left battery=85i,charging=false 1700000000000000000
+/// ```
+pub fn render(status: &AirPodsStatus, observed_at: SystemTime) -> String {
+    let timestamp = observed_at
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    status
+        .components()
+        .iter()
+        .map(|component| {
+            format!(
+                "{},model={},component={} battery={}i,charging={} {}",
+                MEASUREMENT,
+                escape_tag(status.model()),
+                escape_tag(&component.name),
+                component.battery,
+                component.charging,
+                timestamp
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Tag values must escape commas, equals signs and spaces
+fn escape_tag(value: &str) -> String {
+    value
+        .replace(',', r"\,")
+        .replace('=', r"\=")
+        .replace(' ', r"\ ")
+}
@@ -0,0 +1,168 @@
+use super::{Provenance, RenderOptions};
+use podpower_core::AirPodsStatus;
+use podpower_core::payload::{
+    EmptyPayload, ErrorPayload, SCHEMA_VERSION, ScanStats, StatusPayload,
+};
+use podpower_daemon::Sighting;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::time::SystemTime;
+
+pub fn render(
+    status: &AirPodsStatus,
+    provenance: Provenance,
+    observed_at: SystemTime,
+    options: &RenderOptions,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let payload = payload(status, provenance, observed_at, options);
+    match &options.fields {
+        Some(fields) => to_string(&select(&payload, status, fields)?, options),
+        None => to_string(&payload, options),
+    }
+}
+
+/// An array of [`render`]'s payloads
+pub fn render_all(
+    sightings: &[Sighting],
+    observed_at: SystemTime,
+    options: &RenderOptions,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let payloads = payloads(sightings, observed_at, options);
+    match &options.fields {
+        Some(fields) => {
+            let selected = payloads
+                .iter()
+                .zip(sightings)
+                .map(|(payload, sighting)| select(payload, &sighting.status, fields))
+                .collect::<Result<Vec<_>, _>>()?;
+            to_string(&selected, options)
+        }
+        None => to_string(&payloads, options),
+    }
+}
+
+/// The `fields` of a payload in the order asked for, see `--fields`
+struct Selected(Vec<(String, Value)>);
+
+impl Serialize for Selected {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(field, value)| (field, value)))
+    }
+}
+
+/// Top-level fields of the payload by name, then the flat fields of
+/// `podpower --format kv`: `left` for the left earbud's level and
+/// `charging_left` for whether it charges. Fields the payload doesn't have,
+/// e.g. `case` when the case wasn't reported, are null.
+fn select(
+    payload: &StatusPayload,
+    status: &AirPodsStatus,
+    fields: &[String],
+) -> Result<Selected, serde_json::Error> {
+    let Value::Object(all) = serde_json::to_value(payload)? else {
+        return Ok(Selected(Vec::new()));
+    };
+    let flat = |field: &str| {
+        let (name, charging) = match field.strip_prefix("charging_") {
+            Some(name) => (name, true),
+            None => (field, false),
+        };
+        let component = status.components().iter().find(|c| c.name == name)?;
+        Some(match charging {
+            true => Value::from(component.charging),
+            false => Value::from(component.battery),
+        })
+    };
+    Ok(Selected(
+        fields
+            .iter()
+            .map(|field| {
+                let value = all.get(field).cloned().or_else(|| flat(field));
+                (field.clone(), value.unwrap_or(Value::Null))
+            })
+            .collect(),
+    ))
+}
+
+/// Shared by the JSON-shaped binary formats
+pub(super) fn payload(
+    status: &AirPodsStatus,
+    provenance: Provenance,
+    observed_at: SystemTime,
+    options: &RenderOptions,
+) -> StatusPayload {
+    let missing = if options.show_missing {
+        status.missing()
+    } else {
+        Vec::new()
+    };
+    StatusPayload::with_version(
+        status.clone(),
+        options.output_version.unwrap_or(SCHEMA_VERSION),
+    )
+    .with_name(provenance.name)
+    .observed(observed_at)
+    .with_confidence(provenance.confidence)
+    .with_partial(provenance.partial)
+    .with_missing(&missing)
+    .with_listening_mode(provenance.listening_mode)
+    .with_scan(provenance.scan)
+    .with_raw_data(Some(provenance.data.as_slice()).filter(|data| options.raw && !data.is_empty()))
+    .with_warnings(&provenance.warnings)
+}
+
+/// Shared by the JSON-shaped binary formats
+pub(super) fn payloads(
+    sightings: &[Sighting],
+    observed_at: SystemTime,
+    options: &RenderOptions,
+) -> Vec<StatusPayload> {
+    sightings
+        .iter()
+        .map(|sighting| payload(&sighting.status, sighting.into(), observed_at, options))
+        .collect()
+}
+
+pub fn render_error(
+    message: &str,
+    code: &str,
+    scan: Option<ScanStats>,
+    options: &RenderOptions,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    to_string(&error_payload(message, code, scan, options), options)
+}
+
+/// Shared by the JSON-shaped binary formats
+pub(super) fn error_payload(
+    message: &str,
+    code: &str,
+    scan: Option<ScanStats>,
+    options: &RenderOptions,
+) -> ErrorPayload {
+    ErrorPayload::with_version(message, options.output_version.unwrap_or(SCHEMA_VERSION))
+        .with_code(code)
+        .with_scan(scan)
+}
+
+pub fn render_empty(
+    options: &RenderOptions,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    to_string(&empty_payload(options), options)
+}
+
+/// Shared by the JSON-shaped binary formats
+pub(super) fn empty_payload(options: &RenderOptions) -> EmptyPayload {
+    EmptyPayload::with_version(options.output_version.unwrap_or(SCHEMA_VERSION))
+}
+
+/// Pretty-printed, or a single line with `--compact`
+fn to_string(
+    value: &impl Serialize,
+    options: &RenderOptions,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(if options.compact {
+        serde_json::to_string(value)?
+    } else {
+        serde_json::to_string_pretty(value)?
+    })
+}
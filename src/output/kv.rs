@@ -0,0 +1,30 @@
+//! Flat `key=value` line for Zabbix user parameters and Telegraf's logfmt
+//! parser
+
+use podpower_core::AirPodsStatus;
+
+/// ```text
+/// battery=85 left=85 right=90 case=45 charging_left=0 charging_right=1 charging_case=0 model="AirPods Pro"
+/// ```
+pub fn render(status: &AirPodsStatus) -> String {
+    let mut fields = Vec::new();
+    if let Some(battery) = status.battery() {
+        fields.push(format!("battery={}", battery));
+    }
+    for component in status.components() {
+        fields.push(format!("{}={}", component.name, component.battery));
+    }
+    for component in status.components() {
+        fields.push(format!(
+            "charging_{}={}",
+            component.name,
+            u8::from(component.charging)
+        ));
+    }
+    fields.push(format!("model={}", quote(status.model())));
+    fields.join(" ")
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\""))
+}
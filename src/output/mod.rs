@@ -0,0 +1,423 @@
+mod argos;
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "json")]
+mod hass;
+pub mod i18n;
+mod influx;
+#[cfg(feature = "json")]
+pub mod json;
+mod kv;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+pub mod plain;
+mod plain_v2;
+mod prom;
+mod prompt;
+mod sketchybar;
+pub mod style;
+mod tmux;
+
+use crate::exit::UsageError;
+use clap::ValueEnum;
+use i18n::Lang;
+use podpower_core::payload::ScanStats;
+use podpower_core::{AirPodsStatus, ListeningMode, Rounding};
+use podpower_daemon::Sighting;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::SystemTime;
+use style::Icons;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Pretty-printed JSON (requires the `json` feature)
+    #[cfg(feature = "json")]
+    Json,
+    /// CBOR bytes, same fields as JSON (requires the `cbor` feature)
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// MessagePack bytes, same fields as JSON (requires the `msgpack` feature)
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    /// Human-readable text, one component per line
+    Plain,
+    /// Text for scripts: stable `left 78 charging=false` lines in every language
+    #[value(name = "plain-v2")]
+    PlainV2,
+    /// Single tmux status-line segment with colour directives
+    Tmux,
+    /// Short `L78 R80` shell prompt segment, empty when not found
+    #[cfg(feature = "json")]
+    Prompt,
+    /// Argos/BitBar dropdown menu for desktop panel widgets
+    Argos,
+    /// Arguments for `sketchybar --set`
+    Sketchybar,
+    /// InfluxDB line protocol, one line per component
+    Influx,
+    /// Flat `key=value` pairs on one line
+    Kv,
+    /// Prometheus metrics for node_exporter's textfile collector, best with `--out`
+    PromTextfile,
+    /// Flat JSON for Home Assistant sensors, also when not found (requires the `json` feature)
+    #[cfg(feature = "json")]
+    Hass,
+}
+
+impl Default for Format {
+    #[cfg(feature = "json")]
+    fn default() -> Self {
+        Format::Json
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn default() -> Self {
+        Format::Plain
+    }
+}
+
+/// Presentation options for the output formats
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Emit ANSI colors (already resolved from `--color`)
+    pub color: bool,
+    pub icons: Icons,
+    /// Render components as gauges of this many cells
+    pub bars: Option<u8>,
+    /// Language of the words in text formats
+    pub lang: Lang,
+    /// Leave out everything but battery levels
+    pub quiet: bool,
+    /// Show components that weren't reported as disconnected instead of
+    /// leaving them out
+    pub show_missing: bool,
+    /// How approximate levels map from their raw reading
+    pub round: Rounding,
+    /// Show the bytes the status was decoded from, in JSON and plain text
+    pub raw: bool,
+    /// JSON schema version to emit, the latest when `None`
+    #[cfg(feature = "json")]
+    pub output_version: Option<u32>,
+    /// Print JSON on a single line
+    #[cfg(feature = "json")]
+    pub compact: bool,
+    /// Print only these fields of the JSON payload, see `--fields`
+    #[cfg(feature = "json")]
+    pub fields: Option<Vec<String>>,
+}
+
+/// What the scan knows about a status beyond the status itself, which only
+/// the JSON-shaped formats show
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// See [`Sighting::name`]
+    pub name: Option<String>,
+    /// How likely it came from the user's own pair, see
+    /// [`Sighting::confidence`]
+    pub confidence: Option<u8>,
+    /// See [`Sighting::partial`]
+    pub partial: bool,
+    /// See [`Sighting::listening_mode`]
+    pub listening_mode: Option<ListeningMode>,
+    /// What the scan saw, with `--scan-stats`
+    pub scan: Option<ScanStats>,
+    /// See [`Sighting::data`]
+    pub data: Vec<u8>,
+    /// See [`Sighting::warnings`]
+    pub warnings: Vec<String>,
+}
+
+impl From<&Sighting> for Provenance {
+    fn from(sighting: &Sighting) -> Self {
+        Self {
+            name: sighting.name.clone(),
+            confidence: sighting.confidence,
+            partial: sighting.partial,
+            listening_mode: sighting.listening_mode,
+            scan: None,
+            data: sighting.data.clone(),
+            warnings: sighting.warnings.clone(),
+        }
+    }
+}
+
+/// The bytes to write to stdout, including the trailing newline of text
+/// formats. `observed_at` is when the advertisement was received.
+pub fn render(
+    status: &AirPodsStatus,
+    provenance: Provenance,
+    observed_at: SystemTime,
+    format: Format,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    render_rounded(
+        &status.rounded(options.round),
+        provenance,
+        observed_at,
+        format,
+        options,
+    )
+}
+
+/// [`render`] for a status that is already rounded: rounding twice would add
+/// the rounding's offset twice
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+fn render_rounded(
+    status: &AirPodsStatus,
+    provenance: Provenance,
+    observed_at: SystemTime,
+    format: Format,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let text = match format {
+        #[cfg(feature = "json")]
+        Format::Json => json::render(status, provenance, observed_at, options)?,
+        #[cfg(feature = "cbor")]
+        Format::Cbor => return cbor::render(status, provenance, observed_at, options),
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => return msgpack::render(status, provenance, observed_at, options),
+        Format::Plain => plain(status, &provenance.data, options),
+        Format::PlainV2 => plain_v2::render(status, options),
+        Format::Tmux => tmux::render(status, options),
+        #[cfg(feature = "json")]
+        Format::Prompt => prompt::render(status),
+        Format::Argos => argos::render(status),
+        Format::Sketchybar => sketchybar::render(status, options),
+        Format::Influx => influx::render(status, observed_at),
+        Format::Kv => kv::render(status),
+        Format::PromTextfile => prom::render(status, observed_at),
+        #[cfg(feature = "json")]
+        Format::Hass => hass::render(status, provenance.name),
+    };
+    Ok(format!("{}\n", text).into_bytes())
+}
+
+/// [`plain::render`], with the bytes the status was decoded from last with
+/// `--raw`:
+///
+/// ```text
+/// Raw: 0719010e2055997708...
+/// ```
+fn plain(status: &AirPodsStatus, data: &[u8], options: &RenderOptions) -> String {
+    let text = plain::render(status, options);
+    match options.raw && !data.is_empty() {
+        true => format!("{}\nRaw: {}", text, hex(data)),
+        false => text,
+    }
+}
+
+/// `data` as lowercase hex without separators, as `--verbose` and JSON show
+/// it
+pub fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Like [`render`] for several pairs, for `--all`: a JSON-shaped array,
+/// or the text renderings one after another. Formats that embed a single
+/// status into something else have no way to show several.
+pub fn render_all(
+    sightings: &[Sighting],
+    observed_at: SystemTime,
+    format: Format,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let rounded: Vec<Sighting> = sightings
+        .iter()
+        .map(|sighting| Sighting {
+            status: sighting.status.rounded(options.round),
+            ..sighting.clone()
+        })
+        .collect();
+    let sightings = rounded.as_slice();
+    match format {
+        #[cfg(feature = "json")]
+        Format::Json => {
+            Ok(format!("{}\n", json::render_all(sightings, observed_at, options)?).into_bytes())
+        }
+        #[cfg(feature = "cbor")]
+        Format::Cbor => cbor::render_all(sightings, observed_at, options),
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => msgpack::render_all(sightings, observed_at, options),
+        Format::Plain => {
+            let texts: Vec<String> = sightings
+                .iter()
+                .map(|sighting| plain(&sighting.status, &sighting.data, options))
+                .collect();
+            Ok(format!("{}\n", texts.join("\n\n")).into_bytes())
+        }
+        Format::PlainV2 => {
+            let texts: Vec<String> = sightings
+                .iter()
+                .map(|sighting| plain_v2::render(&sighting.status, options))
+                .collect();
+            Ok(format!("{}\n", texts.join("\n\n")).into_bytes())
+        }
+        Format::Influx | Format::Kv => {
+            let mut bytes = Vec::new();
+            for sighting in sightings {
+                bytes.extend(render_rounded(
+                    &sighting.status,
+                    sighting.into(),
+                    observed_at,
+                    format,
+                    options,
+                )?);
+            }
+            Ok(bytes)
+        }
+        Format::Tmux | Format::Argos | Format::Sketchybar | Format::PromTextfile => {
+            check_all(format).map(|()| Vec::new())
+        }
+        #[cfg(feature = "json")]
+        Format::Hass | Format::Prompt => check_all(format).map(|()| Vec::new()),
+    }
+}
+
+/// The lines to print to stderr for what had to be guessed to decode a
+/// status, see [`Sighting::warnings`]. The JSON-shaped formats carry them as
+/// `warnings` instead, and `--quiet` leaves them out.
+pub fn warning_notes(warnings: &[String], format: Format, options: &RenderOptions) -> Vec<String> {
+    let in_payload = match format {
+        #[cfg(feature = "json")]
+        Format::Json => true,
+        #[cfg(feature = "cbor")]
+        Format::Cbor => true,
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => true,
+        _ => false,
+    };
+    if in_payload || options.quiet {
+        return Vec::new();
+    }
+    warnings
+        .iter()
+        .map(|warning| format!("Warning: {}", warning))
+        .collect()
+}
+
+/// Fails for the formats [`render_all`] can't show several pairs in, so
+/// `--all` can fail before scanning
+pub fn check_all(format: Format) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    const ERROR: &str =
+        "--all only works with the json, cbor, msgpack, plain, plain-v2, influx and kv formats";
+    match format {
+        Format::Tmux | Format::Argos | Format::Sketchybar | Format::PromTextfile => {
+            Err(UsageError(ERROR.into()).into())
+        }
+        #[cfg(feature = "json")]
+        Format::Hass | Format::Prompt => Err(UsageError(ERROR.into()).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Fails for the formats other than JSON, which would ignore `--fields`, so
+/// it fails before scanning
+#[cfg(feature = "json")]
+pub fn check_fields(format: Format) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match format {
+        Format::Json => Ok(()),
+        _ => Err(UsageError("--fields only works with the json format".into()).into()),
+    }
+}
+
+/// An error in the output format itself, for formats that have one. `code`
+/// is a stable identifier like `not_found`, `scan` what the failed scan saw
+/// when `--scan-stats` asks for it.
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+pub fn render_error(
+    message: &str,
+    code: &str,
+    scan: Option<ScanStats>,
+    format: Format,
+    options: &RenderOptions,
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+    match format {
+        #[cfg(feature = "json")]
+        Format::Json => Ok(Some(
+            format!("{}\n", json::render_error(message, code, scan, options)?).into_bytes(),
+        )),
+        #[cfg(feature = "cbor")]
+        Format::Cbor => cbor::render_error(message, code, scan, options).map(Some),
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => msgpack::render_error(message, code, scan, options).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// What `--empty-ok` prints when no AirPods were found, for formats that
+/// can say so. The others print nothing.
+#[cfg_attr(not(feature = "json"), allow(unused_variables))]
+pub fn render_empty(
+    format: Format,
+    options: &RenderOptions,
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+    match format {
+        #[cfg(feature = "json")]
+        Format::Json => Ok(Some(
+            format!("{}\n", json::render_empty(options)?).into_bytes(),
+        )),
+        #[cfg(feature = "cbor")]
+        Format::Cbor => cbor::render_empty(options).map(Some),
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => msgpack::render_empty(options).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// What to print instead of an error when no AirPods were found, for formats
+/// that are embedded in something else and must not break it
+pub fn render_missing(format: Format) -> Option<String> {
+    match format {
+        Format::Tmux => Some(String::new()),
+        #[cfg(feature = "json")]
+        Format::Prompt => Some(String::new()),
+        Format::Argos => Some(argos::render_missing()),
+        Format::Sketchybar => Some(sketchybar::render_missing()),
+        Format::PromTextfile => Some(prom::render_missing()),
+        #[cfg(feature = "json")]
+        Format::Hass => Some(hass::render_missing()),
+        _ => None,
+    }
+}
+
+/// Write `bytes` to stdout, or with `--out` replace the file at `out` with
+/// them in one go, so readers like node_exporter's textfile collector never
+/// see it half-written
+pub fn write(bytes: &[u8], out: Option<&Path>) -> std::io::Result<()> {
+    let Some(path) = out else {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(bytes)?;
+        return stdout.flush();
+    };
+    let Some(name) = path.file_name() else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not a file", path.display()),
+        ));
+    };
+    // Next to the file so the rename is atomic, hidden from collectors that
+    // only read `*.prom`, and unique so concurrent writers don't share it
+    static WRITES: AtomicU32 = AtomicU32::new(0);
+    let mut temp = OsString::from(".");
+    temp.push(name);
+    temp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = path.with_file_name(temp);
+    // Synced before the rename, so a crash can't leave an empty file behind
+    let written = std::fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    written.map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
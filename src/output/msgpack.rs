@@ -0,0 +1,51 @@
+use super::{Provenance, RenderOptions};
+use podpower_core::AirPodsStatus;
+use podpower_core::payload::ScanStats;
+use podpower_daemon::Sighting;
+use std::time::SystemTime;
+
+/// The JSON payload encoded as MessagePack, with maps keyed by field name
+pub fn render(
+    status: &AirPodsStatus,
+    provenance: Provenance,
+    observed_at: SystemTime,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(rmp_serde::to_vec_named(&super::json::payload(
+        status,
+        provenance,
+        observed_at,
+        options,
+    ))?)
+}
+
+pub fn render_all(
+    sightings: &[Sighting],
+    observed_at: SystemTime,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(rmp_serde::to_vec_named(&super::json::payloads(
+        sightings,
+        observed_at,
+        options,
+    ))?)
+}
+
+pub fn render_error(
+    message: &str,
+    code: &str,
+    scan: Option<ScanStats>,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(rmp_serde::to_vec_named(&super::json::error_payload(
+        message, code, scan, options,
+    ))?)
+}
+
+pub fn render_empty(
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(rmp_serde::to_vec_named(&super::json::empty_payload(
+        options,
+    ))?)
+}
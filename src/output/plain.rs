@@ -0,0 +1,177 @@
+use super::RenderOptions;
+use super::i18n::Text;
+use super::style::{bar, paint};
+use podpower_core::{AirPodsStatus, Component};
+use std::fmt::Write;
+
+/// Render the status as plain text:
+///
+/// ```text
+/// AirPods Pro
+/// Battery: 85%
+/// Left: 85%
+/// Right: 90% (charging)
+/// Case: 45%
+/// ```
+///
+/// without the model line with `--quiet`, with `Left: disconnected` for an
+/// earbud out of range with `--show-missing`, or, with `--bars`, one gauge
+/// per component:
+///
+/// ```text
+/// AirPods Pro
+/// L ▰▰▰▰▱ 85%
+/// R ▰▰▰▰▰ 90% (charging)
+/// C ▰▰▱▱▱ 45%
+/// ```
+pub fn render(status: &AirPodsStatus, options: &RenderOptions) -> String {
+    let mut out = String::new();
+    if !options.quiet {
+        let _ = writeln!(out, "{}", status.model());
+    }
+    if let Some(width) = options.bars {
+        for (name, component) in slots(status, options) {
+            let line = match component {
+                Some(component) => bar_line(component, width, options),
+                None => format!("{} {} –", initial(name, options), bar(0, width)),
+            };
+            let _ = writeln!(out, "{}", line);
+        }
+        out.truncate(out.trim_end().len());
+        return out;
+    }
+    if let Some(battery) = status.battery() {
+        let charging = status.components().iter().any(|c| c.charging);
+        let _ = writeln!(
+            out,
+            "{}",
+            line(
+                options.lang.text(Text::Battery),
+                battery,
+                charging,
+                false,
+                options
+            )
+        );
+    }
+    for (name, component) in slots(status, options) {
+        let line = match component {
+            Some(component) => component_line(component, options),
+            None => format!(
+                "{}: {}",
+                capitalize(&options.lang.component(name)),
+                options.lang.text(Text::Disconnected)
+            ),
+        };
+        let _ = writeln!(out, "{}", line);
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// The components in the order to print them, with `--show-missing` also
+/// the ones that weren't reported, as `None`
+fn slots<'a>(
+    status: &'a AirPodsStatus,
+    options: &RenderOptions,
+) -> Vec<(&'a str, Option<&'a Component>)> {
+    let mut slots: Vec<_> = status
+        .components()
+        .iter()
+        .map(|c| (c.name.as_str(), Some(c)))
+        .collect();
+    if options.show_missing {
+        slots.extend(status.missing().into_iter().map(|name| (name, None)));
+        // The order the decoders report them in, so the layout stays put
+        let order = ["left", "right", "case", "headphones"];
+        slots.sort_by_key(|(name, _)| order.iter().position(|n| n == name).unwrap_or(order.len()));
+    }
+    slots
+}
+
+pub fn component_line(component: &Component, options: &RenderOptions) -> String {
+    line(
+        &capitalize(&options.lang.component(&component.name)),
+        component.battery,
+        component.charging,
+        component.charging,
+        options,
+    )
+}
+
+/// `[icon ]L ▰▰▰▰▱ NN%[ (charging)]`, with the gauge colored by level
+fn bar_line(component: &Component, width: u8, options: &RenderOptions) -> String {
+    let icon = icon(component.battery, component.charging, options);
+    let initial = initial(&component.name, options);
+    let gauge = bar(component.battery, width);
+    let gauge = if options.color {
+        paint(&gauge, component.battery)
+    } else {
+        gauge
+    };
+    let charging = if component.charging {
+        charging_suffix(options)
+    } else {
+        String::new()
+    };
+    format!(
+        "{}{} {} {}%{}",
+        icon, initial, gauge, component.battery, charging
+    )
+}
+
+/// First letter of the component's label, `L` for the left earbud
+fn initial(name: &str, options: &RenderOptions) -> String {
+    options
+        .lang
+        .component(name)
+        .chars()
+        .take(1)
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// `[icon ]Label: NN%[ (charging)]`, with the percentage colored by level
+fn line(
+    label: &str,
+    battery: u8,
+    charging: bool,
+    show_charging: bool,
+    options: &RenderOptions,
+) -> String {
+    let icon = icon(battery, charging, options);
+    let percent = format!("{}%", battery);
+    let percent = if options.color {
+        paint(&percent, battery)
+    } else {
+        percent
+    };
+    let charging = if show_charging {
+        charging_suffix(options)
+    } else {
+        String::new()
+    };
+    format!("{}{}: {}{}", icon, label, percent, charging)
+}
+
+/// ` (charging)`
+fn charging_suffix(options: &RenderOptions) -> String {
+    format!(" ({})", options.lang.text(Text::Charging))
+}
+
+/// The `--icons` glyph followed by a space, or nothing
+fn icon(battery: u8, charging: bool, options: &RenderOptions) -> String {
+    options
+        .icons
+        .glyph(battery, charging)
+        .map(|glyph| format!("{} ", glyph))
+        .unwrap_or_default()
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
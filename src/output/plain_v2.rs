@@ -0,0 +1,41 @@
+//! Plain text for scripts: one `key value` line per fact, the same in every
+//! language and with every presentation option, so awk and grep can rely on
+//! it while [`super::plain`] changes for people reading it. Lines may gain
+//! `name=value` attributes at the end, and new keys may appear, but existing
+//! ones keep their shape.
+
+use super::RenderOptions;
+use podpower_core::AirPodsStatus;
+use std::fmt::Write;
+
+/// ```text
+/// model AirPods Pro
+/// battery 85
+/// left 85 charging=false
+/// right 90 charging=true
+/// case 45 charging=false
+/// ```
+///
+/// with `left disconnected` for an earbud out of range with
+/// `--show-missing`
+pub fn render(status: &AirPodsStatus, options: &RenderOptions) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "model {}", status.model());
+    if let Some(battery) = status.battery() {
+        let _ = writeln!(out, "battery {}", battery);
+    }
+    for component in status.components() {
+        let _ = writeln!(
+            out,
+            "{} {} charging={}",
+            component.name, component.battery, component.charging
+        );
+    }
+    if options.show_missing {
+        for name in status.missing() {
+            let _ = writeln!(out, "{} disconnected", name);
+        }
+    }
+    out.pop();
+    out
+}
@@ -0,0 +1,20 @@
+//! Prometheus exposition format for node_exporter's textfile collector,
+//! usually written with `--out` into its directory, see
+//! [`podpower_daemon::metrics::battery`]
+
+use podpower_core::AirPodsStatus;
+use podpower_daemon::metrics;
+use std::time::SystemTime;
+
+/// Without the final line feed, which the caller adds
+pub fn render(status: &AirPodsStatus, observed_at: SystemTime) -> String {
+    metrics::battery(Some(status), observed_at)
+        .trim_end()
+        .to_string()
+}
+
+pub fn render_missing() -> String {
+    metrics::battery(None, SystemTime::now())
+        .trim_end()
+        .to_string()
+}
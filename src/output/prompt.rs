@@ -0,0 +1,23 @@
+//! Shell prompt segment (Starship `custom` modules and the like)
+
+use podpower_core::AirPodsStatus;
+
+/// Render worn components only, e.g. `L78 R80` or `H65`. The case is left
+/// out, so pods resting in it leave the segment empty
+pub fn render(status: &AirPodsStatus) -> String {
+    status
+        .components()
+        .iter()
+        .filter(|component| component.name != "case")
+        .map(|component| {
+            let initial: String = component
+                .name
+                .chars()
+                .take(1)
+                .flat_map(char::to_uppercase)
+                .collect();
+            format!("{}{}", initial, component.battery)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,61 @@
+//! Arguments for `sketchybar --set`, for a Sketchybar plugin script
+
+use super::style::Level;
+use super::{RenderOptions, prompt};
+use podpower_core::AirPodsStatus;
+
+/// Item to update when not run from a plugin (which sets `$NAME`)
+const DEFAULT_ITEM: &str = "podpower";
+const ICON: &str = "🎧";
+
+/// `<item> drawing=on icon=🎧 label='L85 R90' icon.color=0xffa6da95`, colored
+/// by the lowest worn component
+pub fn render(status: &AirPodsStatus, options: &RenderOptions) -> String {
+    let worn: Vec<_> = status
+        .components()
+        .iter()
+        .filter(|component| component.name != "case")
+        .collect();
+    let lowest = worn.iter().map(|c| c.battery).min().or(status.battery());
+    let charging = worn.iter().any(|c| c.charging);
+    let label = match prompt::render(status) {
+        label if label.is_empty() => status.model().to_string(),
+        label => label,
+    };
+    let icon = lowest
+        .and_then(|battery| options.icons.glyph(battery, charging))
+        .unwrap_or(ICON);
+
+    let mut args = format!(
+        "{} drawing=on icon={} label={}",
+        quote(&item()),
+        quote(icon),
+        quote(&label)
+    );
+    if let Some(battery) = lowest {
+        args.push_str(&format!(" icon.color={}", colour(battery)));
+    }
+    args
+}
+
+/// Hide the item when no AirPods were found
+pub fn render_missing() -> String {
+    format!("{} drawing=off", quote(&item()))
+}
+
+fn item() -> String {
+    std::env::var("NAME").unwrap_or_else(|_| DEFAULT_ITEM.to_string())
+}
+
+fn colour(battery: u8) -> &'static str {
+    match Level::of(battery) {
+        Level::Low => "0xffed8796",
+        Level::Medium => "0xffeed49f",
+        Level::High => "0xffa6da95",
+    }
+}
+
+/// Single-quote for `sh`, which the plugin uses to `eval` the arguments
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
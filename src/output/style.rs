@@ -0,0 +1,104 @@
+//! Level-based coloring and battery glyphs for text output.
+
+use clap::ValueEnum;
+use std::io::IsTerminal;
+
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_YELLOW: &str = "\x1b[33m";
+const ANSI_GREEN: &str = "\x1b[32m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Coarse battery level, used to pick colors and icons
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Low,
+    Medium,
+    High,
+}
+
+impl Level {
+    pub fn of(battery: u8) -> Self {
+        match battery {
+            0..=20 => Level::Low,
+            21..=50 => Level::Medium,
+            _ => Level::High,
+        }
+    }
+
+    fn ansi(self) -> &'static str {
+        match self {
+            Level::Low => ANSI_RED,
+            Level::Medium => ANSI_YELLOW,
+            Level::High => ANSI_GREEN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and NO_COLOR is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Icons {
+    /// Nerd Font battery glyphs
+    Nerd,
+    /// Emoji (🔋, 🪫, ⚡)
+    Emoji,
+    #[default]
+    None,
+}
+
+impl Icons {
+    /// Glyph for a battery level, or `None` when icons are disabled
+    pub fn glyph(self, battery: u8, charging: bool) -> Option<&'static str> {
+        match self {
+            Icons::Nerd if charging => Some("\u{f0084}"),
+            Icons::Nerd => Some(match battery {
+                0..=4 => "\u{f008e}",
+                5..=14 => "\u{f007a}",
+                15..=24 => "\u{f007b}",
+                25..=34 => "\u{f007c}",
+                35..=44 => "\u{f007d}",
+                45..=54 => "\u{f007e}",
+                55..=64 => "\u{f007f}",
+                65..=74 => "\u{f0080}",
+                75..=84 => "\u{f0081}",
+                85..=94 => "\u{f0082}",
+                _ => "\u{f0079}",
+            }),
+            Icons::Emoji if charging => Some("⚡"),
+            Icons::Emoji if Level::of(battery) == Level::Low => Some("🪫"),
+            Icons::Emoji => Some("🔋"),
+            Icons::None => None,
+        }
+    }
+}
+
+/// Wrap `text` in the ANSI color for `battery`'s level
+pub fn paint(text: &str, battery: u8) -> String {
+    format!("{}{}{}", Level::of(battery).ansi(), text, ANSI_RESET)
+}
+
+/// A gauge of `width` cells, e.g. `▰▰▰▰▱` for 78% at width 5
+pub fn bar(battery: u8, width: u8) -> String {
+    let width = usize::from(width);
+    let filled = (usize::from(battery.min(100)) * width + 50) / 100;
+    "▰".repeat(filled) + &"▱".repeat(width - filled)
+}
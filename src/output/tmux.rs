@@ -0,0 +1,57 @@
+//! tmux status-line segment, meant for `status-right`:
+//!
+//! ```text
+//! set -g status-right '#(podpower --format tmux) %H:%M'
+//! ```
+
+use super::RenderOptions;
+use super::style::Level;
+use podpower_core::AirPodsStatus;
+
+/// Render the status as a single tmux segment, e.g.
+/// `#[fg=green]L78 #[fg=green]R80+ #[fg=yellow]C45#[default]`, where `+`
+/// marks a charging component
+pub fn render(status: &AirPodsStatus, options: &RenderOptions) -> String {
+    let segments: Vec<String> = status
+        .components()
+        .iter()
+        .map(|component| {
+            let icon = options
+                .icons
+                .glyph(component.battery, component.charging)
+                .map(|glyph| format!("{} ", escape(glyph)))
+                .unwrap_or_default();
+            let initial: String = component
+                .name
+                .chars()
+                .take(1)
+                .flat_map(char::to_uppercase)
+                .collect();
+            format!(
+                "#[fg={}]{}{}{}{}",
+                colour(component.battery),
+                icon,
+                escape(&initial),
+                component.battery,
+                if component.charging { "+" } else { "" }
+            )
+        })
+        .collect();
+    if segments.is_empty() {
+        return String::new();
+    }
+    format!("{}#[default]", segments.join(" "))
+}
+
+fn colour(battery: u8) -> &'static str {
+    match Level::of(battery) {
+        Level::Low => "red",
+        Level::Medium => "yellow",
+        Level::High => "green",
+    }
+}
+
+/// tmux expands `#` in status-line output, so literal ones must be doubled
+fn escape(text: &str) -> String {
+    text.replace('#', "##")
+}
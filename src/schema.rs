@@ -0,0 +1,29 @@
+//! `podpower schema`: JSON Schema of the JSON output.
+
+use clap::ValueEnum;
+use podpower_core::payload::{EmptyPayload, ErrorPayload, HassPayload, StatusPayload};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SchemaKind {
+    /// A status, as printed by `--format json` and served by `GET /status`
+    #[default]
+    Status,
+    /// An error, e.g. when AirPods are not found
+    Error,
+    /// No AirPods found, with `--empty-ok`
+    Empty,
+    /// Flat status for Home Assistant, as printed by `--format hass` and
+    /// served by `GET /hass`
+    Hass,
+}
+
+pub fn print(kind: SchemaKind) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let schema = match kind {
+        SchemaKind::Status => schemars::schema_for!(StatusPayload),
+        SchemaKind::Error => schemars::schema_for!(ErrorPayload),
+        SchemaKind::Empty => schemars::schema_for!(EmptyPayload),
+        SchemaKind::Hass => schemars::schema_for!(HassPayload),
+    };
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! LaunchAgent generation for `podpower daemon --install-launchd`.
+
+use super::{daemon_command, home_dir};
+use std::path::PathBuf;
+use std::process::Command;
+
+const LABEL: &str = "io.github.t4t5.podpower";
+
+/// Write the LaunchAgent plist and (re)load it so the daemon runs now and at
+/// every login.
+pub fn install() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = plist_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let log = home_dir()?.join("Library/Logs/podpower.log");
+    std::fs::write(&path, plist(&daemon_command()?, &log.to_string_lossy()))?;
+    eprintln!("Wrote {}", path.display());
+
+    // Replace a previously loaded agent so option changes take effect
+    let _ = launchctl(&["bootout", &service_target()]);
+    if !launchctl(&["bootstrap", &domain_target(), &path.to_string_lossy()])? {
+        return Err(format!("launchctl bootstrap failed for {}", path.display()).into());
+    }
+    eprintln!("Loaded {} (logs: {})", LABEL, log.display());
+    Ok(())
+}
+
+/// Unload the agent and remove its plist
+pub fn uninstall() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Best effort: the agent may not be loaded
+    let _ = launchctl(&["bootout", &service_target()]);
+
+    let path = plist_path()?;
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+        eprintln!("Removed {}", path.display());
+    }
+    Ok(())
+}
+
+fn plist_path() -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(home_dir()?
+        .join("Library/LaunchAgents")
+        .join(format!("{}.plist", LABEL)))
+}
+
+/// `gui/<uid>`, the per-user login session domain
+fn domain_target() -> String {
+    // SAFETY: getuid has no preconditions and cannot fail
+    format!("gui/{}", unsafe { libc::getuid() })
+}
+
+fn service_target() -> String {
+    format!("{}/{}", domain_target(), LABEL)
+}
+
+fn launchctl(args: &[&str]) -> std::io::Result<bool> {
+    Ok(Command::new("launchctl").args(args).status()?.success())
+}
+
+fn plist(command: &[String], log: &str) -> String {
+    let arguments: String = command
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", escape(arg)))
+        .collect();
+    let log = escape(log);
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,50 @@
+//! Installing the daemon as a per-user service of the platform's init system.
+
+#[cfg(target_os = "macos")]
+pub mod launchd;
+#[cfg(windows)]
+pub mod startup;
+#[cfg(target_os = "linux")]
+pub mod systemd;
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// Flags that configure the installation itself rather than the daemon
+const INSTALL_FLAGS: &[&str] = &[
+    "--install-systemd",
+    "--socket-activation",
+    "--install-launchd",
+    "--install-startup",
+    "--uninstall",
+];
+
+/// The command line the service should run: this executable with the daemon
+/// options from the current invocation, minus the install flags.
+fn daemon_command() -> std::io::Result<Vec<String>> {
+    let exe = std::env::current_exe()?;
+    let mut command = vec![exe.to_string_lossy().into_owned()];
+    command.extend(
+        std::env::args()
+            .skip(1)
+            .filter(|a| !INSTALL_FLAGS.contains(&a.as_str())),
+    );
+    Ok(command)
+}
+
+#[cfg(unix)]
+fn home_dir() -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(PathBuf::from(
+        std::env::var_os("HOME").ok_or("HOME is not set")?,
+    ))
+}
+
+/// Remove whatever service integration this platform supports
+pub fn uninstall() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    #[cfg(target_os = "linux")]
+    return systemd::uninstall();
+    #[cfg(target_os = "macos")]
+    return launchd::uninstall();
+    #[cfg(windows)]
+    return startup::uninstall();
+}
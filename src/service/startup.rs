@@ -0,0 +1,64 @@
+//! Windows login startup via the per-user `Run` registry key, for
+//! `podpower daemon --install-startup`.
+
+use super::daemon_command;
+use std::process::Command;
+use windows_sys::Win32::System::Console::{FreeConsole, GetConsoleProcessList};
+
+const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+const VALUE_NAME: &str = "podpower";
+
+/// Register the daemon to start at every login
+pub fn install() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let command = daemon_command()?
+        .iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let status = Command::new("reg")
+        .args([
+            "add", RUN_KEY, "/v", VALUE_NAME, "/t", "REG_SZ", "/d", &command, "/f",
+        ])
+        .status()?;
+    if !status.success() {
+        return Err(format!("reg add {} failed", RUN_KEY).into());
+    }
+    eprintln!(
+        "Registered {} in {}; it will start at next login",
+        VALUE_NAME, RUN_KEY
+    );
+    Ok(())
+}
+
+pub fn uninstall() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let status = Command::new("reg")
+        .args(["delete", RUN_KEY, "/v", VALUE_NAME, "/f"])
+        .status()?;
+    if !status.success() {
+        return Err(format!("reg delete {} failed; was it installed?", RUN_KEY).into());
+    }
+    eprintln!("Removed {} from {}", VALUE_NAME, RUN_KEY);
+    Ok(())
+}
+
+/// Hide the console window when the daemon was started at login rather than
+/// from a terminal, i.e. when this process is the only one attached to it.
+pub fn detach_console() {
+    let mut processes = [0u32; 2];
+    // SAFETY: the buffer length passed matches the array
+    unsafe {
+        if GetConsoleProcessList(processes.as_mut_ptr(), processes.len() as u32) == 1 {
+            FreeConsole();
+        }
+    }
+}
+
+/// Quote an argument for a Windows command line
+fn quote(arg: &str) -> String {
+    if arg.is_empty() || arg.contains([' ', '\t', '"']) {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
@@ -0,0 +1,146 @@
+//! systemd user unit generation for `podpower daemon --install-systemd`.
+
+use super::{daemon_command, home_dir};
+use crate::cli::DaemonArgs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const SERVICE_NAME: &str = "podpower.service";
+const SOCKET_NAME: &str = "podpower.socket";
+
+pub fn install(args: &DaemonArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let dir = unit_dir()?;
+    std::fs::create_dir_all(&dir)?;
+
+    let socket_unit = socket_unit(args);
+    let service = dir.join(SERVICE_NAME);
+    std::fs::write(&service, service_unit(socket_unit.is_some())?)?;
+    eprintln!("Wrote {}", service.display());
+
+    let unit = match socket_unit {
+        Some(socket_unit) => {
+            let socket = dir.join(SOCKET_NAME);
+            std::fs::write(&socket, socket_unit)?;
+            eprintln!("Wrote {}", socket.display());
+            SOCKET_NAME
+        }
+        None => SERVICE_NAME,
+    };
+
+    eprintln!();
+    eprintln!("Enable it with:");
+    eprintln!("  systemctl --user daemon-reload");
+    eprintln!("  systemctl --user enable --now {}", unit);
+    Ok(())
+}
+
+/// Stop and disable the units, then remove them
+pub fn uninstall() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let dir = unit_dir()?;
+    let installed: Vec<&str> = [SOCKET_NAME, SERVICE_NAME]
+        .into_iter()
+        .filter(|name| dir.join(name).exists())
+        .collect();
+    if installed.is_empty() {
+        eprintln!("No podpower units installed in {}", dir.display());
+        return Ok(());
+    }
+
+    // Best effort: the units may never have been enabled
+    let _ = Command::new("systemctl")
+        .args(["--user", "disable", "--now"])
+        .args(&installed)
+        .status();
+
+    for name in installed {
+        let path = dir.join(name);
+        std::fs::remove_file(&path)?;
+        eprintln!("Removed {}", path.display());
+    }
+
+    let _ = Command::new("systemctl")
+        .args(["--user", "daemon-reload"])
+        .status();
+    Ok(())
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user`, falling back to `~/.config/systemd/user`
+fn unit_dir() -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()?.join(".config"),
+    };
+    Ok(config.join("systemd").join("user"))
+}
+
+fn service_unit(socket_activation: bool) -> std::io::Result<String> {
+    let exec_start = daemon_command()?
+        .iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let requires = if socket_activation {
+        format!("Requires={}\nAfter={}\n", SOCKET_NAME, SOCKET_NAME)
+    } else {
+        String::new()
+    };
+
+    Ok(format!(
+        "[Unit]
+Description=podpower AirPods battery daemon
+Documentation=https://github.com/t4t5/podpower
+{requires}
+[Service]
+ExecStart={exec_start}
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+"
+    ))
+}
+
+/// The socket unit for `--socket-activation`, `None` without it
+#[cfg(feature = "json")]
+fn socket_unit(args: &DaemonArgs) -> Option<String> {
+    if !args.socket_activation {
+        return None;
+    }
+    let listen = match &args.socket {
+        Some(path) => quote(&path.to_string_lossy()),
+        None => format!("%t/{}", podpower_daemon::control::SOCKET_NAME),
+    };
+
+    Some(format!(
+        "[Unit]
+Description=podpower AirPods battery daemon query socket
+
+[Socket]
+ListenStream={listen}
+
+[Install]
+WantedBy=sockets.target
+"
+    ))
+}
+
+/// There is no query socket to activate without the `json` feature
+#[cfg(not(feature = "json"))]
+fn socket_unit(_args: &DaemonArgs) -> Option<String> {
+    None
+}
+
+/// Quote an argument for a systemd `ExecStart=` line
+fn quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%");
+    if escaped
+        .chars()
+        .any(|c| c.is_whitespace() || c == '"' || c == '\\')
+    {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
@@ -0,0 +1,129 @@
+//! `podpower tray`: a StatusNotifierItem tray icon fed by the daemon's
+//! monitor pipeline.
+
+use crate::exit::Exit;
+use crate::output::i18n::{Lang, Text};
+use crate::output::{RenderOptions, plain};
+use ksni::menu::StandardItem;
+use ksni::{MenuItem, ToolTip, Tray, TrayMethods};
+use podpower_core::AirPodsStatus;
+use podpower_daemon::{Monitor, ScanOptions};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct BatteryTray {
+    status: Option<AirPodsStatus>,
+}
+
+impl Tray for BatteryTray {
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").into()
+    }
+
+    fn title(&self) -> String {
+        "AirPods battery".into()
+    }
+
+    fn icon_name(&self) -> String {
+        let Some(status) = &self.status else {
+            return "battery-missing".into();
+        };
+        let level = match status.battery() {
+            Some(0..=10) => "empty",
+            Some(11..=25) => "caution",
+            Some(26..=50) => "low",
+            Some(51..=80) => "good",
+            Some(_) => "full",
+            None => return "battery-missing".into(),
+        };
+        let charging = if status.components().iter().any(|c| c.charging) {
+            "-charging"
+        } else {
+            ""
+        };
+        format!("battery-{}{}", level, charging)
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let (title, description) = match &self.status {
+            Some(status) => (
+                status.model().to_string(),
+                status
+                    .components()
+                    .iter()
+                    .map(|c| plain::component_line(c, &text_options()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            None => (Lang::detect().text(Text::NotFound).into(), String::new()),
+        };
+        ToolTip {
+            title,
+            description,
+            ..Default::default()
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut items: Vec<MenuItem<Self>> = match &self.status {
+            Some(status) => std::iter::once(status.model().to_string())
+                .chain(
+                    status
+                        .components()
+                        .iter()
+                        .map(|c| plain::component_line(c, &text_options())),
+                )
+                .map(label)
+                .collect(),
+            None => vec![label(Lang::detect().text(Text::NotFound).into())],
+        };
+        items.push(MenuItem::Separator);
+        items.push(
+            StandardItem {
+                label: "Quit".into(),
+                icon_name: "application-exit".into(),
+                activate: Box::new(|_| Exit::Success.exit()),
+                ..Default::default()
+            }
+            .into(),
+        );
+        items
+    }
+}
+
+/// Plain text in the desktop's language
+fn text_options() -> RenderOptions {
+    RenderOptions {
+        lang: Lang::detect(),
+        ..Default::default()
+    }
+}
+
+/// A non-interactive menu line
+fn label(text: String) -> MenuItem<BatteryTray> {
+    StandardItem {
+        label: text,
+        enabled: false,
+        ..Default::default()
+    }
+    .into()
+}
+
+pub async fn run(
+    interval: Duration,
+    scan_options: ScanOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let monitor = Monitor::new(interval).with_scan_options(scan_options);
+    let mut readings = monitor.subscribe();
+    tokio::spawn(monitor.run());
+
+    let handle = BatteryTray::default().spawn().await?;
+    while readings.changed().await.is_ok() {
+        let status = readings
+            .borrow_and_update()
+            .as_ref()
+            .and_then(|r| r.status.clone());
+        handle.update(|tray| tray.status = status).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! `podpower tui`: a live terminal dashboard fed by the daemon's monitor
+//! pipeline.
+
+use crate::output::style::Level;
+use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind, KeyModifiers};
+use podpower_daemon::{Monitor, Reading, ScanOptions};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Gauge, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tokio_stream::StreamExt;
+
+/// Number of samples kept for the history sparkline
+const HISTORY_LEN: usize = 240;
+
+pub async fn run(
+    interval: Duration,
+    scan_options: ScanOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let monitor = Monitor::new(interval).with_scan_options(scan_options);
+    let readings = monitor.subscribe();
+    tokio::spawn(monitor.run());
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, readings, interval).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    mut readings: watch::Receiver<Option<Reading>>,
+    interval: Duration,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut events = EventStream::new();
+    let mut history: VecDeque<u64> = VecDeque::with_capacity(HISTORY_LEN);
+    let mut sample = tokio::time::interval(interval);
+    // Keeps the "updated Ns ago" line ticking between scans
+    let mut redraw = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        let reading = readings.borrow_and_update().clone();
+        terminal.draw(|frame| draw(frame, reading.as_ref(), &history))?;
+
+        tokio::select! {
+            Ok(()) = readings.changed() => {}
+            _ = sample.tick() => {
+                let level = reading.as_ref().and_then(|r| r.status.as_ref()?.battery());
+                if history.len() == HISTORY_LEN {
+                    history.pop_front();
+                }
+                history.push_back(level.map_or(0, u64::from));
+            }
+            _ = redraw.tick() => {}
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                    let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('c');
+                    if ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                        return Ok(());
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, reading: Option<&Reading>, history: &VecDeque<u64>) {
+    let components = reading
+        .and_then(|r| r.status.as_ref())
+        .map(|s| s.components())
+        .unwrap_or_default();
+
+    let mut constraints = vec![Constraint::Length(1)];
+    constraints.extend(components.iter().map(|_| Constraint::Length(3)));
+    constraints.push(Constraint::Min(5));
+    constraints.push(Constraint::Length(1));
+    let areas = Layout::vertical(constraints).split(frame.area());
+
+    frame.render_widget(Paragraph::new(header(reading)), areas[0]);
+
+    for (component, area) in components.iter().zip(&areas[1..]) {
+        let charging = if component.charging {
+            " ⚡ charging"
+        } else {
+            ""
+        };
+        let gauge = Gauge::default()
+            .block(Block::bordered().title(component.name.as_str()))
+            .gauge_style(Style::default().fg(level_color(component.battery)))
+            .percent(u16::from(component.battery.min(100)))
+            .label(format!("{}%{}", component.battery, charging));
+        frame.render_widget(gauge, *area);
+    }
+
+    let history_area: Rect = areas[areas.len() - 2];
+    let width = usize::from(history_area.width.saturating_sub(2));
+    let data: Vec<u64> = history.iter().rev().take(width).rev().copied().collect();
+    let sparkline = Sparkline::default()
+        .block(Block::bordered().title("Battery history"))
+        .max(100)
+        .data(&data);
+    frame.render_widget(sparkline, history_area);
+
+    frame.render_widget(Paragraph::new("q: quit"), areas[areas.len() - 1]);
+}
+
+fn header(reading: Option<&Reading>) -> String {
+    let Some(reading) = reading else {
+        return "Scanning for AirPods...".into();
+    };
+    let age = SystemTime::now()
+        .duration_since(reading.observed_at)
+        .unwrap_or_default()
+        .as_secs();
+    let rssi = reading
+        .rssi
+        .map(|rssi| format!(" · RSSI {} dBm", rssi))
+        .unwrap_or_default();
+    let connected = if reading.connected {
+        " · connected"
+    } else {
+        ""
+    };
+    match &reading.status {
+        Some(status) => format!(
+            "{}{}{} · updated {}s ago",
+            status.model(),
+            connected,
+            rssi,
+            age
+        ),
+        None => format!("AirPods not found · updated {}s ago", age),
+    }
+}
+
+fn level_color(battery: u8) -> Color {
+    match Level::of(battery) {
+        Level::Low => Color::Red,
+        Level::Medium => Color::Yellow,
+        Level::High => Color::Green,
+    }
+}
@@ -0,0 +1,25 @@
+//! Running the binary for the integration tests.
+
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// `podpower --simulate` with `args`, kept away from the user's config and
+/// data by a temp directory of its own, removed once it exits
+pub fn podpower(args: &[&str]) -> Output {
+    static RUNS: AtomicU32 = AtomicU32::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "podpower-test-{}-{}",
+        std::process::id(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let output = Command::new(env!("CARGO_BIN_EXE_podpower"))
+        .arg("--simulate")
+        .args(args)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("XDG_DATA_HOME", &dir)
+        .output()
+        .unwrap();
+    // Nothing may have been written at all
+    let _ = std::fs::remove_dir_all(&dir);
+    output
+}
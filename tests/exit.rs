@@ -0,0 +1,21 @@
+//! Exit statuses of the binary for arguments that parse but can't be used.
+
+mod common;
+
+/// The exit status of `podpower --simulate` with `args`
+fn status(args: &[&str]) -> Option<i32> {
+    common::podpower(args).status.code()
+}
+
+#[test]
+fn unusable_arguments_are_usage_errors() {
+    assert_eq!(status(&["--all", "--format", "tmux"]), Some(5));
+    assert_eq!(status(&["--address", "not-an-address"]), Some(5));
+    assert_eq!(status(&["--address", "5E:00:00:00:00:01"]), Some(0));
+}
+
+#[test]
+fn fields_only_work_with_json() {
+    assert_eq!(status(&["--fields", "left", "--format", "kv"]), Some(5));
+    assert_eq!(status(&["--fields", "left", "--format", "json"]), Some(0));
+}
@@ -0,0 +1,24 @@
+//! Partial sightings of the simulated AirPods, run through the binary.
+
+mod common;
+
+use common::podpower;
+
+#[test]
+fn partial_sighting_keeps_its_warning() {
+    // --strict turns the unknown model down, --partial takes it anyway
+    let output = podpower(&[
+        "unknown-model",
+        "--strict",
+        "--partial",
+        "--format",
+        "plain",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Warning: unknown model id 0x3020, layout guessed"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,24 @@
+//! The `plain-v2` format for scripts, run through the binary.
+
+mod common;
+
+use common::podpower;
+
+#[test]
+fn not_found_reads_the_same_in_every_language() {
+    // The simulated pair is AirPods Pro, so nothing matches
+    let output = podpower(&[
+        "--model",
+        "AirPods Max",
+        "--format",
+        "plain-v2",
+        "--lang",
+        "de",
+    ]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "AirPods not found\n"
+    );
+}
@@ -0,0 +1,32 @@
+//! `--all` output of the simulated AirPods, run through the binary.
+
+mod common;
+
+/// stdout of `podpower --simulate` with `args`
+fn podpower(args: &[&str]) -> String {
+    let output = common::podpower(args);
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// The levels of `text`, the numbers after `key`
+fn levels<'a>(text: &'a str, key: &'a str) -> impl Iterator<Item = u8> + 'a {
+    text.split(key).skip(1).map(|rest| {
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().unwrap()
+    })
+}
+
+#[test]
+fn all_rounds_kv_and_influx_levels_once() {
+    // Simulated levels are approximate, so nearest-10 leaves whole tens
+    let kv = podpower(&["--all", "--round", "nearest-10", "--format", "kv"]);
+    let left: Vec<u8> = levels(&kv, " left=").collect();
+    assert_eq!(left.len(), 1, "{}", kv);
+    assert!(left.iter().all(|level| level % 10 == 0), "{}", kv);
+
+    let influx = podpower(&["--all", "--round", "nearest-10", "--format", "influx"]);
+    let batteries: Vec<u8> = levels(&influx, "battery=").collect();
+    assert_eq!(batteries.len(), 3, "{}", influx);
+    assert!(batteries.iter().all(|level| level % 10 == 0), "{}", influx);
+}